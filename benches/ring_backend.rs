@@ -0,0 +1,66 @@
+use std::cell::OnceCell;
+use std::time::Duration;
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use nalgebra::DVector;
+use num_bigint::BigInt;
+use threshold_decryption::mpc::additive_sharing::AdditiveSecretSharing;
+use threshold_decryption::mpc::batch_decryption::BatchDecryption;
+use threshold_decryption::mpc::lwe_scheme::init_lwe_with_random_ptxt;
+use threshold_decryption::mpc::public_params::PublicParameters;
+use threshold_decryption::mpc::ring::{RingElement, Z128};
+
+// Online phase of one decryption with MACs on the BigInt Party and on the u128 one, over the parameters of the
+// `protocol` binary sweep. With k = 64 the MAC ring of s = 80 is wider than u128, so u128 is measured with s = 64 only.
+
+/// Key shares of every party and a ciphertext, both backends decrypt the same one
+type Setup = (Vec<DVector<BigInt>>, (DVector<BigInt>, BigInt));
+
+fn setup(params: &PublicParameters) -> Setup {
+    let (lwe_scheme, _, a, b) = init_lwe_with_random_ptxt(params.m, params.k, params.lwe_dimension, 0);
+
+    let sk_shares: Vec<DVector<BigInt>> = {
+        let columns: Vec<_> = lwe_scheme.sk.iter().map(|x| AdditiveSecretSharing::share(x, params.n, params.mac_ks)).collect();
+        (0..params.n).map(|i| DVector::from_fn(params.lwe_dimension, |j, _| columns[j][i].clone())).collect()
+    };
+
+    (sk_shares, (a, b))
+}
+
+fn bench_backend<R: RingElement>(c: &mut Criterion, name: &str, label: &str, params: &PublicParameters, setup: &dyn Fn() -> Setup) {
+    c.benchmark_group(name).bench_function(label, |bencher| {
+        let (sk_shares, ciphertext) = setup();
+        let batch = BatchDecryption::<R>::new(params, &sk_shares, &[ciphertext]);
+        bencher.iter_batched(|| batch.clone(), |mut batch| batch.run().unwrap(), BatchSize::SmallInput)
+    });
+}
+
+fn ring_backends(c: &mut Criterion) {
+    for protocol_m in [1, 2, 4] {
+        for protocol_b in 5..9 {
+            for mac_s in [64, 80] {
+                for lwe_a_len in [777, 870, 1024] {
+                    let params = PublicParameters::init(4, 64, protocol_m, protocol_b, lwe_a_len, mac_s);
+                    let name = format!("n = 4, k = 64, m = {}, b = {}, s = {}, LWE a = {}", protocol_m, protocol_b, mac_s, lwe_a_len);
+                    // The key is only shared for the benchmarks that are not filtered out
+                    let cell = OnceCell::new();
+                    let shared = || cell.get_or_init(|| setup(&params)).clone();
+
+                    bench_backend::<BigInt>(c, &name, "bigint party", &params, &shared);
+                    if Z128::fits(params.mac_ks) {
+                        bench_backend::<Z128>(c, &name, "u128 party", &params, &shared);
+                    }
+                }
+            }
+        }
+    }
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default()
+        .sample_size(10)
+        .warm_up_time(Duration::from_millis(300))
+        .measurement_time(Duration::from_secs(1));
+    targets = ring_backends
+}
+criterion_main!(benches);
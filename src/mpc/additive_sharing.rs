@@ -4,11 +4,23 @@ use rand::distributions::uniform::UniformSampler;
 
 use nalgebra::DVector;
 use num_integer::Integer;
+use crate::mpc::secret_sharing::SecretSharing;
 
-pub struct AdditiveSecretSharing;
+/// n-of-n additive sharing mod 2^ring_exponent
+pub struct AdditiveSecretSharing {
+    pub num_parties: usize,
+    pub ring_exponent: usize,
+}
 
 impl AdditiveSecretSharing {
 
+    pub fn new(num_parties: usize, ring_exponent: usize) -> AdditiveSecretSharing {
+        AdditiveSecretSharing {
+            num_parties,
+            ring_exponent
+        }
+    }
+
     pub fn share(secret: &BigInt, num_shares: usize, ring_exponent: usize) -> DVector<BigInt> {
         let mut rng = rand::thread_rng();
 
@@ -33,9 +45,43 @@ impl AdditiveSecretSharing {
     pub fn reveal(shares: &DVector<BigInt>, ring_exponent: usize) -> BigInt {
         let q = BigInt::from(2u32).pow(ring_exponent as u32);
 
-        let revealed  = shares.sum().mod_floor(&q);
+        shares.sum().mod_floor(&q)
+    }
+}
+
+impl SecretSharing for AdditiveSecretSharing {
+    type Share = BigInt;
+
+    fn num_parties(&self) -> usize {
+        self.num_parties
+    }
+
+    fn reconstruction_threshold(&self) -> usize {
+        self.num_parties
+    }
 
-        revealed
+    fn share_secret(&self, secret: &BigInt) -> Vec<BigInt> {
+        AdditiveSecretSharing::share(secret, self.num_parties, self.ring_exponent).iter().cloned().collect()
+    }
+
+    fn reconstruct(&self, shares: &[(usize, BigInt)]) -> Option<BigInt> {
+        let mut parties: Vec<usize> = shares.iter().map(|(party, _)| *party).collect();
+        parties.sort();
+        parties.dedup();
+
+        if parties != (0..self.num_parties).collect::<Vec<_>>() || shares.len() != self.num_parties {
+            return None;
+        }
+
+        let shares = DVector::from_iterator(shares.len(), shares.iter().map(|(_, share)| share.clone()));
+        Some(AdditiveSecretSharing::reveal(&shares, self.ring_exponent))
+    }
+
+    fn to_additive(&self, party: usize, share: &BigInt, parties: &[usize]) -> BigInt {
+        assert_eq!(parties.len(), self.num_parties, "Additive sharing needs every party");
+        assert!(parties.contains(&party));
+
+        share.clone()
     }
 }
 
@@ -59,7 +105,7 @@ mod tests {
 
         let s1_add_s2 = (&shares1).sub(&shares2);
 
-        let revealed =  AdditiveSecretSharing::reveal(&s1_add_s2, field_exponent);
+        let _revealed =  AdditiveSecretSharing::reveal(&s1_add_s2, field_exponent);
         //debug!("s1 = {}",shares1.transpose());
         //debug!("s2 = {}",shares2.transpose());
         //debug!("(s1 + s2) = {}",s1_add_s2.transpose());
@@ -85,4 +131,15 @@ mod tests {
             assert_eq!(revealed, expected)
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_secret_sharing_trait() {
+        let scheme = AdditiveSecretSharing::new(4, 16);
+        let secret = BigInt::from(4242);
+        let shares: Vec<_> = scheme.share_secret(&secret).into_iter().enumerate().collect();
+
+        assert_eq!(scheme.reconstruction_threshold(), 4);
+        assert_eq!(scheme.reconstruct(&shares), Some(secret));
+        assert_eq!(scheme.reconstruct(&shares[1..]), None);
+    }
+}
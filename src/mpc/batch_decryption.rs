@@ -0,0 +1,262 @@
+use nalgebra::DVector;
+use num_bigint::BigInt;
+use crate::mpc::output_recipient::{OutputRecipient, RecipientShare};
+use crate::mpc::party::{Party, PlaintextShare, ProtocolAbort};
+use crate::mpc::preprocessing::Preprocessing;
+use crate::mpc::prss::PrssKeys;
+use crate::mpc::public_params::PublicParameters;
+use crate::mpc::ring::RingElement;
+use crate::network::common::STEP_COUNT;
+use crate::network::ProtocolTransferredData;
+
+// Threshold decryption of many LWE ciphertexts in one set of rounds.
+//
+// Every ciphertext gets its own preprocessing instance and its own Party for every party number. The instances
+// run the steps of Party side by side, and in each round a party sends one message holding its data for all of
+// them, as the network workers do for the ciphertexts of a job. The round count is the one of a single decryption.
+// The dealer works on BigInt, the parties lift their inputs into the ring backend R.
+
+#[derive(Clone)]
+pub struct BatchDecryption<R: RingElement = BigInt> {
+    pub params: PublicParameters,
+
+    /// instances[c][i] is party i decrypting ciphertext c
+    pub instances: Vec<Vec<Party<R>>>,
+
+    prss_keys: Vec<PrssKeys>,
+    /// The dealer, which also shares the keys of output recipients
+    preprocessing: Preprocessing,
+    rounds: usize,
+}
+
+impl<R: RingElement> BatchDecryption<R> {
+    /// Deals fresh preprocessing and MAC material for every ciphertext, `sk_shares[i]` is the key share of party i
+    pub fn new(params: &PublicParameters, sk_shares: &[DVector<BigInt>], ciphertexts: &[(DVector<BigInt>, BigInt)]) -> Self {
+        assert_eq!(sk_shares.len(), params.n);
+
+        // The parties derive s, r, the masks and the MAC pads of instance c with PRSS, nonce c
+        let prss_keys = PrssKeys::deal(params.n);
+
+        // rows = parties; columns = instances
+        let preprocessing = Preprocessing::new(params).with_prss(prss_keys.clone());
+        let preprocessed = preprocessing.run_instances(ciphertexts.len());
+
+        let mac_key_shares = preprocessing.mac_key_shares();
+        let sk_macs = preprocessing.key_macs(sk_shares);
+        let lift = |x: &BigInt| R::from_bigint(x);
+
+        let instances = ciphertexts.iter().enumerate()
+            .map(|(c, (a, b))| {
+                (0..params.n)
+                    .map(|i| {
+                        let mut party = Party::new(i, params);
+                        party.set_preprocessing(preprocessed[i][c].clone(), &prss_keys[i], c as u64);
+                        party.set_sk(sk_shares[i].map(|x| lift(&x)));
+                        party.set_sk_mac(sk_macs[i].map(|x| lift(&x)));
+
+                        party.set_a(a.map(|x| lift(&x)));
+                        party.set_b(lift(b));
+                        party.set_mac_alpha(lift(&mac_key_shares[i]));
+                        party
+                    })
+                    .collect()
+            })
+            .collect();
+
+        BatchDecryption {
+            params: params.clone(),
+            instances,
+            prss_keys,
+            preprocessing,
+            rounds: 0,
+        }
+    }
+
+    /// Rounds in which every party sent one message to every other party
+    pub fn rounds(&self) -> usize {
+        self.rounds
+    }
+
+    /// Runs all steps, the plaintexts come in ciphertext order
+    pub fn run(&mut self) -> Result<Vec<BigInt>, ProtocolAbort> {
+        self.run_steps()?;
+
+        Ok(self.instances.iter().map(|instance| instance[0].get_msg().clone()).collect())
+    }
+
+    /// Runs all steps without revealing the plaintexts, shares[c][i] is the share of party i of plaintext c
+    pub fn run_to_shares(&mut self) -> Result<Vec<Vec<PlaintextShare<R>>>, ProtocolAbort> {
+        for party in self.instances.iter_mut().flatten() {
+            party.keep_plaintext_shared();
+        }
+
+        self.run_steps()?;
+
+        Ok(self.instances.iter()
+            .map(|instance| instance.iter().map(|party| party.get_plaintext_share().clone()).collect())
+            .collect())
+    }
+
+    /// Runs all steps with o' sent only to `recipient`, shares[c][i] is what party i sends it for ciphertext c
+    pub fn run_for_recipient(&mut self, recipient: &OutputRecipient) -> Result<Vec<Vec<RecipientShare<R>>>, ProtocolAbort> {
+        for (c, instance) in self.instances.iter_mut().enumerate() {
+            let recipient_masks = self.preprocessing.recipient_masks(recipient.key());
+            for ((i, party), recipient_mask) in instance.iter_mut().enumerate().zip(recipient_masks) {
+                party.set_recipient_mask(recipient_mask.into_ring());
+                party.set_output_pad(R::from_bigint(&self.prss_keys[i].output_pad(c as u64, &self.params)));
+            }
+        }
+
+        self.run_steps()?;
+
+        Ok(self.instances.iter()
+            .map(|instance| instance.iter().map(|party| party.get_recipient_share().clone()).collect())
+            .collect())
+    }
+
+    fn run_steps(&mut self) -> Result<(), ProtocolAbort> {
+        let n = self.params.n;
+
+        // messages[i][c] is the data of party i for ciphertext c
+        let mut messages = vec![vec![ProtocolTransferredData::empty(); self.instances.len()]; n];
+        self.rounds = 1;
+
+        for step in 0..STEP_COUNT {
+            let mut outputs = vec![Vec::new(); n];
+            for (c, instance) in self.instances.iter_mut().enumerate() {
+                for (i, party) in instance.iter_mut().enumerate() {
+                    let input = (0..n).filter(|&j| j != i).map(|j| messages[j][c].clone()).collect();
+                    outputs[i].push(party.execute_step(step, input)?);
+                }
+            }
+            messages = outputs;
+
+            // The output of the last step stays with the party
+            if step + 1 < STEP_COUNT {
+                self.rounds += 1;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::num::Wrapping;
+    use nalgebra::DVector;
+    use num_bigint::BigInt;
+    use crate::mpc::additive_sharing::AdditiveSecretSharing;
+    use crate::mpc::batch_decryption::BatchDecryption;
+    use crate::mpc::lwe_scheme::LweScheme;
+    use crate::mpc::output_recipient::OutputRecipient;
+    use crate::mpc::party::{PlaintextShare, ProtocolAbort};
+    use crate::mpc::public_params::PublicParameters;
+    use crate::mpc::ring::{RingElement, Z128};
+
+    fn share_key(lwe_scheme: &LweScheme, params: &PublicParameters) -> Vec<DVector<BigInt>> {
+        let columns: Vec<_> = lwe_scheme.sk.iter().map(|x| AdditiveSecretSharing::share(x, params.n, params.k)).collect();
+        (0..params.n).map(|i| DVector::from_fn(params.lwe_dimension, |j, _| columns[j][i].clone())).collect()
+    }
+
+    #[test]
+    fn test_batch_takes_the_rounds_of_a_single_decryption() {
+        let params = PublicParameters::init(4, 32, 2, 6, 16, 40);
+        let lwe_scheme = LweScheme::new(params.m, params.k, params.lwe_dimension, 0);
+        let sk_shares = share_key(&lwe_scheme, &params);
+
+        let mut rounds = Vec::new();
+        for count in [1, 12] {
+            let ptxts: Vec<BigInt> = (0..count).map(|c| BigInt::from(c % 4)).collect();
+            let ciphertexts: Vec<_> = ptxts.iter().map(|m| lwe_scheme.encrypt(m)).collect();
+
+            let mut batch: BatchDecryption = BatchDecryption::new(&params, &sk_shares, &ciphertexts);
+            assert_eq!(batch.run(), Ok(ptxts));
+            rounds.push(batch.rounds());
+        }
+
+        assert_eq!(rounds[0], rounds[1]);
+    }
+
+    #[test]
+    fn test_backends_agree() {
+        // k + s = 72 and 128 fit u128, the 64 bit ciphertext ring with s = 80 needs BigInt
+        for (k, m, b, mac_s) in [(32, 2, 6, 40), (64, 1, 8, 64), (64, 4, 5, 80)] {
+            let params = PublicParameters::init(4, k, m, b, 64, mac_s);
+            let lwe_scheme = LweScheme::new(params.m, params.k, params.lwe_dimension, 0);
+            let sk_shares = share_key(&lwe_scheme, &params);
+
+            let ptxts: Vec<BigInt> = (0..4).map(|c| BigInt::from(c % (1 << m))).collect();
+            let ciphertexts: Vec<_> = ptxts.iter().map(|m| lwe_scheme.encrypt(m)).collect();
+
+            assert_eq!(BatchDecryption::<BigInt>::new(&params, &sk_shares, &ciphertexts).run(), Ok(ptxts.clone()));
+            if Z128::fits(params.mac_ks) {
+                let mut batch = BatchDecryption::<Z128>::new(&params, &sk_shares, &ciphertexts);
+                assert_eq!(batch.run(), Ok(ptxts.clone()));
+
+                // The MACs hold in the fixed-width ring as well
+                let mut batch = BatchDecryption::<Z128>::new(&params, &sk_shares, &ciphertexts);
+                let shares = batch.run_to_shares().unwrap();
+                let mac_key_shares: Vec<Z128> = batch.instances[0].iter().map(|party| *party.get_mac_alpha()).collect();
+                let mut tampered = shares[1].clone();
+                tampered[0].value = (tampered[0].value + Wrapping(1)).reduce(params.mac_ltz_bits());
+                assert_eq!(PlaintextShare::open(&shares[1], &mac_key_shares, &params), Some(ptxts[1].clone()));
+                assert_eq!(PlaintextShare::open(&tampered, &mac_key_shares, &params), None);
+            }
+        }
+    }
+
+    #[test]
+    fn test_shared_plaintexts_open_to_the_decryption() {
+        let params = PublicParameters::init(4, 32, 3, 6, 16, 40);
+        let lwe_scheme = LweScheme::new(params.m, params.k, params.lwe_dimension, 0);
+        let sk_shares = share_key(&lwe_scheme, &params);
+
+        let ciphertexts: Vec<_> = (0..6).map(|m| lwe_scheme.encrypt(&BigInt::from(m))).collect();
+
+        let mut batch: BatchDecryption = BatchDecryption::new(&params, &sk_shares, &ciphertexts);
+        let shares = batch.run_to_shares().unwrap();
+        let mac_key_shares: Vec<BigInt> = batch.instances[0].iter().map(|party| party.get_mac_alpha().clone()).collect();
+
+        for ((a, b), plaintext_shares) in ciphertexts.iter().zip(&shares) {
+            assert_eq!(PlaintextShare::open(plaintext_shares, &mac_key_shares, &params), Some(lwe_scheme.decrypt(a, b)));
+
+            // A share changed without its MAC is caught
+            let mut tampered = plaintext_shares.clone();
+            tampered[1].value += 1;
+            assert_eq!(PlaintextShare::open(&tampered, &mac_key_shares, &params), None);
+        }
+    }
+
+    #[test]
+    fn test_only_the_recipient_opens_the_plaintexts() {
+        let params = PublicParameters::init(4, 32, 3, 6, 16, 40);
+        let lwe_scheme = LweScheme::new(params.m, params.k, params.lwe_dimension, 0);
+        let sk_shares = share_key(&lwe_scheme, &params);
+
+        let ciphertexts: Vec<_> = (0..6).map(|m| lwe_scheme.encrypt(&BigInt::from(m))).collect();
+        let recipient = OutputRecipient::new(&params);
+
+        let mut batch: BatchDecryption = BatchDecryption::new(&params, &sk_shares, &ciphertexts);
+        let shares = batch.run_for_recipient(&recipient).unwrap();
+
+        for ((a, b), recipient_shares) in ciphertexts.iter().zip(&shares) {
+            assert_eq!(recipient.reconstruct(recipient_shares), Ok(lwe_scheme.decrypt(a, b)));
+
+            let mut tampered = recipient_shares.clone();
+            tampered[2].o_prime += &params.big_l;
+            assert_eq!(recipient.reconstruct(&tampered), Err(ProtocolAbort::OutputMacCheckFailed));
+        }
+
+        // A party knows its share of the key only, which does not fix up the MAC of a changed share
+        let party = &batch.instances[0][2];
+        let mut tampered = shares[0].clone();
+        tampered[2].o_prime += &params.big_l;
+        tampered[2].mac += &params.big_l * &party.get_recipient_mask().key;
+        assert_eq!(recipient.reconstruct(&tampered), Err(ProtocolAbort::OutputMacCheckFailed));
+
+        // Another recipient's key does not verify the shares
+        assert_eq!(OutputRecipient::new(&params).reconstruct(&shares[0]), Err(ProtocolAbort::OutputMacCheckFailed));
+    }
+}
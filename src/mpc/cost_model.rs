@@ -0,0 +1,198 @@
+use std::fmt;
+use crate::mpc::public_params::{PublicParameters, PublicParametersBuilder, MAX_TABLE_BITS};
+
+// Predicted cost of one decryption for a choice of the digit layout, to pick it for a network instead of sweeping it.
+//
+// The layout cuts l into d - 1 digits of b bits under a top digit of b' bits, l = (d - 1) * b + b'. It trades the two
+// tables against each other: the sign tables have max(B, B') * d entries and the LTZ table 2^(d+1). A top digit wider
+// than b saves digits, and halves the LTZ table with each, at the price of taller sign tables. The online rounds
+// hardly depend on the layout, only y' has d + 1 bits, so the ranking is driven by dealing the tables over the same
+// network. Each party sends its share of
+//  1. z' mod L,            l bits
+//  2. y' mod 2^(d+1),      d + 1 bits
+//  3. o' mod q,            k bits
+//  4. its MAC check value, k + s bits
+// to the n - 1 others, the output is computed locally.
+
+/// Bytes a share takes in a message, bitcode adds about one byte of length to the value
+fn share_bytes(bits: usize) -> usize {
+    bits.div_ceil(8) + 1
+}
+
+/// Network between the parties, and between the dealer and the parties
+#[derive(Debug, Clone, PartialEq)]
+pub struct NetworkProfile {
+    /// One way delay of a message
+    pub latency_ms: f64,
+
+    pub bandwidth_mbit: f64,
+
+    /// Local time of one operation on a k-bit value, a table entry or a term of <a, sk>
+    pub operation_ns: f64,
+}
+
+impl NetworkProfile {
+    pub fn new(latency_ms: f64, bandwidth_mbit: f64) -> NetworkProfile {
+        NetworkProfile {
+            latency_ms,
+            bandwidth_mbit,
+            operation_ns: 100.0,
+        }
+    }
+
+    pub fn with_operation_ns(mut self, operation_ns: f64) -> NetworkProfile {
+        self.operation_ns = operation_ns;
+        self
+    }
+
+    fn transfer_seconds(&self, bytes: usize) -> f64 {
+        (bytes * 8) as f64 / (self.bandwidth_mbit * 1e6)
+    }
+}
+
+/// What one decryption with some parameters costs
+#[derive(Debug, Clone, PartialEq)]
+pub struct CostEstimate {
+    /// max(B, B') * d
+    pub sign_entries: usize,
+
+    /// 2^(d+1)
+    pub ltz_entries: usize,
+
+    /// Table shares of one party
+    pub preprocessing_bytes: usize,
+
+    /// Bytes a party sends in each of the rounds
+    pub round_bytes: Vec<usize>,
+
+    /// Online operations of a party
+    pub operations: usize,
+
+    pub online_seconds: f64,
+
+    /// Time the dealer needs to sample and send the tables of all parties
+    pub preprocessing_seconds: f64,
+}
+
+impl CostEstimate {
+    pub fn new(params: &PublicParameters, profile: &NetworkProfile) -> CostEstimate {
+        let sign_entries = params.sign_rows() * params.d;
+        let ltz_entries = params.big_d;
+
+        // Sign entries are shares mod 2^(d+1), LTZ entries mod 2^m
+        let preprocessing_bytes = sign_entries * share_bytes(params.d + 1) + ltz_entries * share_bytes(params.m);
+
+        let round_bytes: Vec<usize> = [params.l, params.d + 1, params.k, params.mac_ks].iter()
+            .map(|&bits| (params.n - 1) * share_bytes(bits))
+            .collect();
+
+        // <a, sk>, the digits with their sign lookups, the LTZ lookup and the MAC of three values
+        let operations = params.lwe_dimension + 2 * params.d + 1 + 3;
+
+        let online_seconds = round_bytes.iter()
+            .map(|&bytes| profile.latency_ms / 1e3 + profile.transfer_seconds(bytes))
+            .sum::<f64>()
+            + operations as f64 * profile.operation_ns / 1e9;
+
+        let preprocessing_seconds = profile.transfer_seconds(params.n * preprocessing_bytes)
+            + (params.n * (sign_entries + ltz_entries)) as f64 * profile.operation_ns / 1e9;
+
+        CostEstimate {
+            sign_entries,
+            ltz_entries,
+            preprocessing_bytes,
+            round_bytes,
+            operations,
+            online_seconds,
+            preprocessing_seconds,
+        }
+    }
+
+    /// Online time plus the dealing of the tables it consumes
+    pub fn total_seconds(&self) -> f64 {
+        self.online_seconds + self.preprocessing_seconds
+    }
+}
+
+/// One candidate digit layout (b, b') with its cost
+#[derive(Debug, Clone, PartialEq)]
+pub struct RankedParameters {
+    pub params: PublicParameters,
+    pub cost: CostEstimate,
+}
+
+/// Every pair of a digit size b and a top digit size b' the builder accepts, cheapest first
+pub fn optimize(builder: &PublicParametersBuilder, profile: &NetworkProfile) -> Vec<RankedParameters> {
+    let layouts = (1..=MAX_TABLE_BITS).flat_map(|b| (1..=MAX_TABLE_BITS).map(move |b_prime| (b, b_prime)));
+
+    let mut ranked: Vec<RankedParameters> = layouts
+        .filter_map(|(b, b_prime)| builder.clone().with_digit_bits(b).with_top_digit_bits(b_prime).build().ok())
+        .map(|params| {
+            let cost = CostEstimate::new(&params, profile);
+            RankedParameters { params, cost }
+        })
+        .collect();
+
+    ranked.sort_by(|x, y| x.cost.total_seconds().total_cmp(&y.cost.total_seconds()));
+    ranked
+}
+
+/// A table of candidates as `optimize` ranks them
+pub struct RankingTable<'a>(pub &'a [RankedParameters]);
+
+impl fmt::Display for RankingTable<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{:>4} {:>3} {:>3} {:>3} {:>10} {:>10} {:>12} {:>12} {:>14} {:>14} {:>12}",
+                 "rank", "b", "b'", "d", "sign", "ltz", "prep bytes", "round bytes", "online us", "prep us", "total us")?;
+
+        for (rank, candidate) in self.0.iter().enumerate() {
+            let (params, cost) = (&candidate.params, &candidate.cost);
+            writeln!(f, "{:>4} {:>3} {:>3} {:>3} {:>10} {:>10} {:>12} {:>12} {:>14.1} {:>14.1} {:>12.1}",
+                     rank + 1, params.b, params.b_prime, params.d, cost.sign_entries, cost.ltz_entries,
+                     cost.preprocessing_bytes, cost.round_bytes.iter().sum::<usize>(),
+                     cost.online_seconds * 1e6, cost.preprocessing_seconds * 1e6, cost.total_seconds() * 1e6)?;
+        }
+
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::mpc::cost_model::{optimize, CostEstimate, NetworkProfile, RankingTable};
+    use crate::mpc::public_params::PublicParameters;
+
+    #[test]
+    fn test_optimizer_balances_the_tables() {
+        let params = PublicParameters::init(4, 64, 2, 6, 1024, 40);
+        let cost = CostEstimate::new(&params, &NetworkProfile::new(0.5, 10.0));
+        assert_eq!(params.d, 11);
+        assert_eq!(cost.sign_entries, 64 * 11);
+        assert_eq!(cost.ltz_entries, 1 << 12);
+        assert_eq!(cost.round_bytes, vec![3 * 9, 3 * 3, 3 * 9, 3 * 14]);
+
+        let builder = PublicParameters::builder().with_ciphertext_bits(64).with_plaintext_bits(2).with_mac_security(40);
+        let ranked = optimize(&builder, &NetworkProfile::new(0.5, 10.0));
+
+        assert!(ranked.windows(2).all(|pair| pair[0].cost.total_seconds() <= pair[1].cost.total_seconds()));
+        for candidate in &ranked {
+            assert_eq!(candidate.params.l, (candidate.params.d - 1) * candidate.params.b + candidate.params.b_prime);
+            assert!(candidate.params.d < 20);
+        }
+
+        // Every b from 4 on with the top digit it leaves, b = 1, 2 and 3 give LTZ tables over 2^20 entries
+        for b in 4..=20 {
+            let derived = PublicParameters::init(4, 64, 2, b, 1024, 40);
+            assert!(ranked.iter().any(|candidate| (candidate.params.b, candidate.params.b_prime) == (b, derived.b_prime)));
+        }
+        // and top digits wider than b, 62 = 8 * 6 + 14
+        assert!(ranked.iter().any(|candidate| (candidate.params.b, candidate.params.b_prime, candidate.params.d) == (6, 14, 9)));
+        assert!(!ranked.iter().any(|candidate| candidate.params.b == 2));
+
+        // Neither extreme wins, l = 62 is best cut into a handful of digits
+        let best = &ranked[0].params;
+        assert!(best.b > 4 && best.b < 12, "picked b = {}", best.b);
+        assert!(RankingTable(&ranked).to_string().lines().count() == ranked.len() + 1);
+    }
+}
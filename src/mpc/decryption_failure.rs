@@ -0,0 +1,167 @@
+use nalgebra::DVector;
+use num_bigint::{BigInt, UniformBigInt};
+use num_integer::Integer;
+use num_traits::{ToPrimitive, Zero};
+use rand::distributions::uniform::UniformSampler;
+use crate::mpc::lwe_scheme::{LweParameters, LweScheme};
+use crate::mpc::protocol::Protocol;
+use crate::mpc::public_params::PublicParameters;
+
+// Probability that a decryption returns the wrong plaintext because of the noise.
+//
+// The parties compute z = b + <a, sk> + L/2 = L * msg + e + L/2 and take z mod L as the noise plus L/2, which is only
+// right while e lies in [-L/2, L/2); the comparison with r and the digit decomposition are exact for every z'. A
+// ciphertext that is the sum of h + 1 fresh ones (h homomorphic additions) carries the sum of their errors:
+//  - a fresh secret-key ciphertext has one error,
+//  - a public-key one adds the errors of about half of the pk_rows public samples.
+// By the central limit theorem the noise is close to a Gaussian of the summed variance, and it can never exceed the
+// sum of the bounds, below L/2 a decryption cannot fail at all.
+
+/// Analytic failure probability of one decryption
+#[derive(Debug, Clone, PartialEq)]
+pub struct FailureEstimate {
+    /// Standard deviation of the noise of the decrypted ciphertext
+    pub noise_sigma: f64,
+
+    /// Largest noise the decrypted ciphertext can carry
+    pub noise_bound: BigInt,
+
+    /// log2 of the failure probability, -inf when the noise can not reach L/2
+    pub log2_probability: f64,
+}
+
+impl FailureEstimate {
+    pub fn new(params: &PublicParameters, pk_rows: usize, additions: usize) -> FailureEstimate {
+        let ciphertexts = additions + 1;
+        let error_variance = params.error_distribution.standard_deviation().powi(2);
+
+        // Each public sample is in the subset with probability 1/2
+        let fresh_variance = error_variance * (1.0 + pk_rows as f64 / 2.0);
+        let noise_sigma = (fresh_variance * ciphertexts as f64).sqrt();
+        let noise_bound = LweParameters::from_public_parameters(params, pk_rows).noise_bound() * ciphertexts;
+
+        let half_l = &params.big_l >> 1;
+        let log2_probability = if noise_bound < half_l {
+            f64::NEG_INFINITY
+        } else {
+            // P[|e| >= L/2] = erfc(L/2 / (sigma * sqrt(2)))
+            let x = half_l.to_f64().unwrap() / (noise_sigma * 2f64.sqrt());
+            (ln_erfc(x) / std::f64::consts::LN_2).min(0.0)
+        };
+
+        FailureEstimate {
+            noise_sigma,
+            noise_bound,
+            log2_probability,
+        }
+    }
+
+    pub fn probability(&self) -> f64 {
+        self.log2_probability.exp2()
+    }
+}
+
+/// ln erfc(x) for x >= 0, with a relative error below 1.2e-7 (Numerical Recipes, erfcc), without underflow
+fn ln_erfc(x: f64) -> f64 {
+    let t = 1.0 / (1.0 + 0.5 * x);
+    let poly = [-1.26551223, 1.00002368, 0.37409196, 0.09678418, -0.18628806,
+        0.27886807, -1.13520398, 1.48851587, -0.82215223, 0.17087277]
+        .iter()
+        .rev()
+        .fold(0.0, |acc, coefficient| coefficient + t * acc);
+
+    t.ln() - x * x + poly
+}
+
+/// Empirical failure rate of `Protocol::decrypt`
+#[derive(Debug, Clone, PartialEq)]
+pub struct MonteCarloResult {
+    pub trials: usize,
+    pub failures: usize,
+}
+
+impl MonteCarloResult {
+    pub fn rate(&self) -> f64 {
+        self.failures as f64 / self.trials as f64
+    }
+
+    /// Standard error of the rate as an estimate of the failure probability
+    pub fn standard_error(&self) -> f64 {
+        let rate = self.rate();
+        (rate * (1.0 - rate) / self.trials as f64).sqrt()
+    }
+}
+
+/// Decrypts `trials` sums of `additions + 1` random ciphertexts with the protocol and counts the wrong plaintexts.
+/// The scheme encrypts with the public key when `pk_rows` is positive, and every trial gets fresh preprocessing.
+pub fn monte_carlo(params: &PublicParameters, pk_rows: usize, additions: usize, trials: usize) -> MonteCarloResult {
+    let mut rng = rand::thread_rng();
+
+    let lwe_scheme = LweScheme::with_parameters(&LweParameters::from_public_parameters(params, pk_rows));
+    let mut protocol = Protocol::new(params);
+    protocol.share_sk(lwe_scheme.sk.clone());
+
+    let failures = (0..trials)
+        .filter(|_| {
+            let mut a = DVector::from_element(params.lwe_dimension, BigInt::zero());
+            let mut b = BigInt::zero();
+            let mut msg = BigInt::zero();
+
+            for _ in 0..=additions {
+                let ptxt = UniformBigInt::new(BigInt::zero(), &params.p).sample(&mut rng);
+                let (a_i, b_i) = if pk_rows > 0 {
+                    lwe_scheme.encrypt_with_public_key(&ptxt)
+                } else {
+                    lwe_scheme.encrypt(&ptxt)
+                };
+
+                a = (a + a_i).map(|x| x.mod_floor(&params.q));
+                b = (b + b_i).mod_floor(&params.q);
+                msg = (msg + ptxt).mod_floor(&params.p);
+            }
+
+            let s = UniformBigInt::new(BigInt::zero(), BigInt::from(params.big_d)).sample(&mut rng);
+            let r = UniformBigInt::new(BigInt::zero(), &params.big_l).sample(&mut rng);
+            protocol.preprocess(s, r);
+
+            protocol.decrypt(a, b) != msg
+        })
+        .count();
+
+    MonteCarloResult { trials, failures }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::mpc::decryption_failure::{ln_erfc, monte_carlo, FailureEstimate};
+    use crate::mpc::distributions::{ErrorDistribution, KeyDistribution};
+    use crate::mpc::public_params::PublicParameters;
+
+    #[test]
+    fn test_analytic_and_empirical_failure_rates_agree() {
+        assert!((ln_erfc(1.0) - 0.157299207f64.ln()).abs() < 1e-6);
+        assert!((ln_erfc(10.0) - 2.088487583e-45f64.ln()).abs() < 1e-6);
+
+        // L/2 = 512 is out of reach of one ciphertext, errors bounded by 480, but not of a sum of nine
+        let params = PublicParameters::init(4, 12, 2, 5, 16, 40)
+            .with_distributions(ErrorDistribution::RoundedGaussian { sigma: 80.0 }, KeyDistribution::Uniform);
+
+        let fresh = FailureEstimate::new(&params, 0, 0);
+        assert_eq!(fresh.log2_probability, f64::NEG_INFINITY);
+        assert_eq!(monte_carlo(&params, 0, 0, 50).failures, 0);
+
+        let summed = FailureEstimate::new(&params, 0, 8);
+        let probability = summed.probability();
+        assert!(probability > 0.02 && probability < 0.05, "{:?}", summed);
+
+        // Within four standard errors of the analytic value
+        let result = monte_carlo(&params, 0, 8, 2000);
+        let tolerance = 4.0 * (probability * (1.0 - probability) / result.trials as f64).sqrt();
+        assert!((result.rate() - probability).abs() < tolerance,
+                "{} failures in {} trials, expected a rate of {}", result.failures, result.trials, probability);
+
+        // More public samples in a ciphertext, more noise
+        assert!(FailureEstimate::new(&params, 8, 8).log2_probability > summed.log2_probability);
+    }
+}
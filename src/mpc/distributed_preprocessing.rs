@@ -0,0 +1,621 @@
+use std::collections::HashMap;
+use nalgebra::{DMatrix, DVector};
+use num_bigint::{BigInt, UniformBigInt};
+use num_integer::Integer;
+use num_traits::{One, ToPrimitive, Zero};
+use rand::distributions::uniform::UniformSampler;
+use serde::{Deserialize, Serialize};
+use crate::mpc::additive_sharing::AdditiveSecretSharing;
+use crate::mpc::preprocessed_gate::{LessThanZeroFunction, ProcessingFunction, SignFunction};
+use crate::mpc::preprocessing::{MacShares, OpeningMask, PreprocessedShare, Tables, TableShares};
+use crate::mpc::public_params::PublicParameters;
+
+// Dealer-free generation of the s, r, LTZ and sign tables.
+//
+// Every secret the online phase needs is a public linear function of a secret-shared one-hot vector:
+//  - r = sum_t B^t * r_t, where r_t is the digit encoded by the one-hot vector e_(r_t)
+//  - s is the value encoded by the one-hot vector e_s
+//  - signs[x][t] = sum_v Sign(x - v) * e_(r_t)[v]
+//  - ltz[x] = sum_v LTZ(x - v) * e_s[v]
+//  - every opening mask rho = sum_j 2^j * b_j, where the bit b_j is the value encoded by a one-hot vector of size 2
+//
+// Next to its share of every one-hot vector a party holds its share of the MAC alpha * e under the global MAC key.
+// Both start at e_0 and alpha * e_0 and go through the same linear steps, so every output comes with its MAC.
+//
+// The shared one-hot vectors start at e_0 and are rotated once per subset G of n - t parties by an offset
+// known only to the members of G. Parties outside of G hand their shares to the leader of G, the members
+// rotate their shares locally and then re-share the result to everybody. Any coalition of at most t parties
+// misses the offset of the subset formed by the remaining parties, so the final offsets are uniform from its
+// point of view. Semi-honest security requires an honest majority (n >= 2t + 1).
+
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum PreprocessingMessage {
+    /// Rotation offsets of every subset led by the sender, one entry per one-hot vector
+    Offsets(Vec<(usize, Vec<BigInt>)>),
+
+    /// Shares of a party outside the rotating subset, handed over to the subset leader, the one-hot vectors followed
+    /// by their MACs
+    Handover(Vec<DVector<BigInt>>),
+
+    /// Fresh additive pieces of the rotated vectors, the one-hot vectors followed by their MACs
+    Reshare(Vec<DVector<BigInt>>),
+}
+
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DistributedPreprocessing {
+    pub params: PublicParameters,
+
+    /// Maximal number of colluding parties that learn nothing about s, r or the tables
+    pub privacy_threshold: usize,
+
+    /// Rotating subsets, every subset holds n - t parties and its leader is the first member
+    pub subsets: Vec<Vec<usize>>,
+
+    /// Number of independent instances generated together, one per ciphertext
+    pub instances: usize,
+}
+
+impl DistributedPreprocessing {
+    pub fn new(params: &PublicParameters, privacy_threshold: usize) -> DistributedPreprocessing {
+        assert!(privacy_threshold >= 1);
+        assert!(params.n > 2 * privacy_threshold, "Distributed preprocessing requires n >= 2t + 1");
+
+        let subsets = combinations(params.n, privacy_threshold)
+            .into_iter()
+            .map(|excluded| (0..params.n).filter(|i| !excluded.contains(i)).collect())
+            .collect();
+
+        DistributedPreprocessing {
+            params: params.clone(),
+            privacy_threshold,
+            subsets,
+            instances: 1,
+        }
+    }
+
+    pub fn with_instances(mut self, instances: usize) -> DistributedPreprocessing {
+        assert!(instances >= 1);
+        self.instances = instances;
+        self
+    }
+
+    pub fn rounds(&self) -> usize {
+        self.subsets.len()
+    }
+
+    /// Steps are numbered from 0 (offset distribution) to `step_count() - 1` (share expansion)
+    pub fn step_count(&self) -> usize {
+        2 * self.rounds() + 2
+    }
+
+    fn leader(&self, round: usize) -> usize {
+        self.subsets[round][0]
+    }
+
+    fn is_member(&self, round: usize, party: usize) -> bool {
+        self.subsets[round].contains(&party)
+    }
+
+    /// Parties whose messages `party` has to receive before it can execute `step`
+    pub fn expected_senders(&self, party: usize, step: usize) -> Vec<usize> {
+        if step == 0 {
+            return Vec::new();
+        }
+
+        if step == 1 {
+            let mut leaders: Vec<usize> = (0..self.rounds())
+                .filter(|&round| self.is_member(round, party) && self.leader(round) != party)
+                .map(|round| self.leader(round))
+                .collect();
+            leaders.sort();
+            leaders.dedup();
+            return leaders;
+        }
+
+        let round = (step - 1) / 2;
+        if step.is_multiple_of(2) {
+            // Handover of the parties outside the subset
+            if self.leader(round) == party {
+                (0..self.params.n).filter(|&i| !self.is_member(round, i)).collect()
+            } else {
+                Vec::new()
+            }
+        } else {
+            // Reshare of the previous subset
+            self.subsets[round - 1].iter().copied().filter(|&i| i != party).collect()
+        }
+    }
+
+    /// Sizes of the one-hot vectors of an instance: one per digit of r, one that encodes s, then one per bit of the
+    /// opening masks in the order of `OpeningMask::ALL`
+    fn instance_sizes(&self) -> Vec<usize> {
+        let mut sizes = vec![self.params.big_b; self.params.d];
+        sizes[self.params.d - 1] = self.params.big_b_prime.to_usize().unwrap();
+        sizes.push(self.params.big_d);
+        for mask in OpeningMask::ALL {
+            sizes.extend(vec![2; mask.bits(&self.params)]);
+        }
+        sizes
+    }
+
+    /// Sizes of all one-hot vectors, instance after instance
+    fn one_hot_sizes(&self) -> Vec<usize> {
+        self.instance_sizes().repeat(self.instances)
+    }
+
+    /// All one-hot vectors are shared in the largest ring any of the outputs lives in
+    fn ring_exponent(&self) -> usize {
+        self.params.mac_ks.max(self.params.mac_sign_bits())
+    }
+
+    /// Party with its share of the global MAC key, the MACs of its one-hot vectors start at this share times e_0
+    pub fn new_party(&self, party_number: usize, mac_key_share: &BigInt) -> PreprocessingParty {
+        let (one_hot, one_hot_macs) = self.one_hot_sizes()
+            .into_iter()
+            .map(|size| {
+                let mut vector = DVector::<BigInt>::zeros(size);
+                if party_number == 0 {
+                    vector[0] = BigInt::one();
+                }
+                let mut mac = DVector::<BigInt>::zeros(size);
+                mac[0] = mac_key_share.clone();
+                (vector, mac)
+            })
+            .unzip();
+
+        PreprocessingParty {
+            party_number,
+            setup: self.clone(),
+            offsets: HashMap::new(),
+            one_hot,
+            one_hot_macs,
+        }
+    }
+
+    /// Runs the protocol between `n` local parties with the given shares of the MAC key, returns the instances of
+    /// every party
+    pub fn run(&self, mac_key_shares: &DVector<BigInt>) -> Vec<Vec<PreprocessedShare>> {
+        let mut parties: Vec<PreprocessingParty> = (0..self.params.n)
+            .map(|i| self.new_party(i, &mac_key_shares[i]))
+            .collect();
+
+        let mut inboxes: Vec<Vec<(usize, PreprocessingMessage)>> = vec![Vec::new(); self.params.n];
+
+        for step in 0..self.step_count() {
+            let mut next_inboxes = vec![Vec::new(); self.params.n];
+
+            for (i, party) in parties.iter_mut().enumerate() {
+                let input = std::mem::take(&mut inboxes[i]);
+                for (receiver, message) in party.execute_step(step, input) {
+                    next_inboxes[receiver].push((i, message));
+                }
+            }
+
+            inboxes = next_inboxes;
+        }
+
+        parties.iter().map(|party| party.finish()).collect()
+    }
+}
+
+
+pub struct PreprocessingParty {
+    pub party_number: usize,
+
+    setup: DistributedPreprocessing,
+
+    /// Rotation offsets of the subsets this party is a member of
+    offsets: HashMap<usize, Vec<BigInt>>,
+
+    /// Shares of the one-hot vectors: per instance the digits of r from LSD to MSD, then s, then the mask bits
+    one_hot: Vec<DVector<BigInt>>,
+
+    /// Shares of the MACs of the one-hot vectors, in the same order
+    one_hot_macs: Vec<DVector<BigInt>>,
+}
+
+impl PreprocessingParty {
+
+    /// Consumes the messages sent to this party during `step - 1`, returns (receiver, message) pairs
+    pub fn execute_step(&mut self, step: usize, input: Vec<(usize, PreprocessingMessage)>) -> Vec<(usize, PreprocessingMessage)> {
+        let last_step = self.setup.step_count() - 1;
+        assert!(step <= last_step);
+
+        let mut expected = self.setup.expected_senders(self.party_number, step);
+        let mut senders: Vec<usize> = input.iter().map(|(sender, _)| *sender).collect();
+        expected.sort();
+        senders.sort();
+        assert_eq!(senders, expected, "Party {} received unexpected senders at step {}", self.party_number, step);
+
+        if step == 0 {
+            return self.distribute_offsets();
+        }
+
+        if step == 1 {
+            for (_, message) in input {
+                match message {
+                    PreprocessingMessage::Offsets(offsets) => self.offsets.extend(offsets),
+                    _ => panic!("Expected rotation offsets at step 1"),
+                }
+            }
+            return self.handover(0);
+        }
+
+        let round = (step - 1) / 2;
+        if step.is_multiple_of(2) {
+            for (_, message) in input {
+                match message {
+                    PreprocessingMessage::Handover(shares) => self.add_shares(&shares),
+                    _ => panic!("Expected handed over shares at step {}", step),
+                }
+            }
+            self.rotate_and_reshare(round)
+        } else {
+            for (_, message) in input {
+                match message {
+                    PreprocessingMessage::Reshare(shares) => self.add_shares(&shares),
+                    _ => panic!("Expected re-shared pieces at step {}", step),
+                }
+            }
+
+            if step == last_step {
+                Vec::new()
+            } else {
+                self.handover(round)
+            }
+        }
+    }
+
+    fn distribute_offsets(&mut self) -> Vec<(usize, PreprocessingMessage)> {
+        let mut rng = rand::thread_rng();
+        let sizes = self.setup.one_hot_sizes();
+
+        let mut led_offsets = Vec::new();
+        for (round, subset) in self.setup.subsets.iter().enumerate() {
+            if subset[0] != self.party_number {
+                continue;
+            }
+
+            let offsets: Vec<BigInt> = sizes.iter()
+                .map(|size| UniformBigInt::new(BigInt::zero(), BigInt::from(*size)).sample(&mut rng))
+                .collect();
+
+            self.offsets.insert(round, offsets.clone());
+            led_offsets.push((round, offsets));
+        }
+
+        let mut output = Vec::new();
+        for receiver in 0..self.setup.params.n {
+            if receiver == self.party_number {
+                continue;
+            }
+
+            let receiver_offsets: Vec<(usize, Vec<BigInt>)> = led_offsets.iter()
+                .filter(|(round, _)| self.setup.is_member(*round, receiver))
+                .cloned()
+                .collect();
+
+            if !receiver_offsets.is_empty() {
+                output.push((receiver, PreprocessingMessage::Offsets(receiver_offsets)));
+            }
+        }
+
+        output
+    }
+
+    fn handover(&mut self, round: usize) -> Vec<(usize, PreprocessingMessage)> {
+        if self.setup.is_member(round, self.party_number) {
+            return Vec::new();
+        }
+
+        let shares = [self.one_hot.clone(), self.one_hot_macs.clone()].concat();
+        for vector in self.one_hot.iter_mut().chain(self.one_hot_macs.iter_mut()) {
+            vector.fill(BigInt::zero());
+        }
+
+        vec![(self.setup.leader(round), PreprocessingMessage::Handover(shares))]
+    }
+
+    fn rotate_and_reshare(&mut self, round: usize) -> Vec<(usize, PreprocessingMessage)> {
+        if !self.setup.is_member(round, self.party_number) {
+            return Vec::new();
+        }
+
+        let offsets = self.offsets.get(&round)
+            .unwrap_or_else(|| panic!("Party {} has no offsets for round {}", self.party_number, round));
+
+        let n = self.setup.params.n;
+        let ring_exponent = self.setup.ring_exponent();
+
+        // A MAC vector is rotated by the offset of its one-hot vector
+        let offsets = offsets.iter().chain(offsets);
+
+        let mut pieces_per_party = vec![Vec::new(); n];
+        for (vector, offset) in self.one_hot.iter().chain(&self.one_hot_macs).zip(offsets) {
+            let size = vector.nrows();
+            let offset = offset.to_usize().unwrap();
+
+            // e_v -> e_(v + offset)
+            let rotated = DVector::from_fn(size, |x, _| vector[(x + size - offset) % size].clone());
+
+            let pieces = DMatrix::from_columns(&rotated.iter()
+                .map(|value| AdditiveSecretSharing::share(value, n, ring_exponent))
+                .collect::<Vec<_>>());
+
+            for (receiver, receiver_pieces) in pieces_per_party.iter_mut().enumerate() {
+                receiver_pieces.push(pieces.row(receiver).transpose());
+            }
+        }
+
+        let mut output = Vec::new();
+        for (receiver, mut pieces) in pieces_per_party.into_iter().enumerate() {
+            if receiver == self.party_number {
+                self.one_hot_macs = pieces.split_off(self.one_hot.len());
+                self.one_hot = pieces;
+            } else {
+                output.push((receiver, PreprocessingMessage::Reshare(pieces)));
+            }
+        }
+
+        output
+    }
+
+    fn add_shares(&mut self, shares: &[DVector<BigInt>]) {
+        assert_eq!(shares.len(), self.one_hot.len() + self.one_hot_macs.len());
+
+        let modulo = BigInt::one() << self.setup.ring_exponent();
+        for (vector, share) in self.one_hot.iter_mut().chain(self.one_hot_macs.iter_mut()).zip(shares) {
+            assert_eq!(vector.nrows(), share.nrows());
+            *vector = (&*vector + share).map(|x| x.mod_floor(&modulo));
+        }
+    }
+
+    /// Expands the one-hot shares into this party's preprocessing material, one share per instance
+    pub fn finish(&self) -> Vec<PreprocessedShare> {
+        let instance_len = self.setup.instance_sizes().len();
+
+        self.one_hot.chunks(instance_len)
+            .zip(self.one_hot_macs.chunks(instance_len))
+            .map(|(one_hot, one_hot_macs)| {
+                let (s, r, masks) = self.encoded_values(one_hot);
+                let (s_mac, r_mac, mask_macs) = self.encoded_values(one_hot_macs);
+                let (ltz, signs) = self.expand_tables(one_hot);
+                let (ltz_macs, sign_macs) = self.expand_tables(one_hot_macs);
+
+                PreprocessedShare {
+                    s: Some(s),
+                    r: Some(r),
+                    masks: Some(masks),
+                    macs: MacShares { s: s_mac, r: r_mac, masks: mask_macs },
+                    tables: TableShares::Explicit(Tables { ltz, signs, ltz_macs, sign_macs }),
+                }
+            })
+            .collect()
+    }
+
+    /// Shares of s, r and the masks of an instance, or of their MACs when given the MAC vectors
+    fn encoded_values(&self, one_hot: &[DVector<BigInt>]) -> (BigInt, BigInt, DVector<BigInt>) {
+        let params = &self.setup.params;
+
+        let r = one_hot[..params.d].iter()
+            .enumerate()
+            .fold(BigInt::zero(), |acc, (i, digit)| {
+                acc + (encoded_value(digit) << (i * params.b))
+            })
+            .mod_floor(&params.mac_big_ks);
+
+        let s = encoded_value(&one_hot[params.d]).mod_floor(&(BigInt::one() << params.mac_sign_bits()));
+
+        let mut bits = one_hot[params.d + 1..].iter();
+        let masks = OpeningMask::ALL.iter()
+            .map(|mask| {
+                (0..mask.bits(params))
+                    .fold(BigInt::zero(), |acc, j| acc + (encoded_value(bits.next().unwrap()) << j))
+                    .mod_floor(&(BigInt::one() << mask.ring_bits(params)))
+            })
+            .collect::<Vec<_>>();
+
+        (s, r, DVector::from_vec(masks))
+    }
+
+    /// Shares of the LTZ and sign tables of an instance, or of the MACs of their entries when given the MAC vectors
+    fn expand_tables(&self, one_hot: &[DVector<BigInt>]) -> (DVector<BigInt>, DMatrix<BigInt>) {
+        let params = &self.setup.params;
+
+        let ltz_function = LessThanZeroFunction {
+            modulo: BigInt::from(params.big_d)
+        };
+        let ltz = expand_table(&ltz_function, &one_hot[params.d], params.big_d, params.mac_ltz_bits());
+
+        let mut signs = DMatrix::<BigInt>::zeros(params.sign_rows(), params.d);
+        for (i, digit) in one_hot[..params.d].iter().enumerate() {
+            signs.set_column(i, &expand_table(&SignFunction, digit, params.sign_rows(), params.mac_sign_bits()));
+        }
+
+        (ltz, signs)
+    }
+}
+
+
+/// Share of sum_v v * e[v], i.e. of the value encoded by a shared one-hot vector
+fn encoded_value(one_hot: &DVector<BigInt>) -> BigInt {
+    one_hot.iter()
+        .enumerate()
+        .fold(BigInt::zero(), |acc, (v, share)| acc + share * BigInt::from(v))
+}
+
+/// Share of the truth table x -> func(x, v) for the value v encoded by a shared one-hot vector
+fn expand_table<F: ProcessingFunction>(func: &F, one_hot: &DVector<BigInt>, num_rows: usize, field_exponent: usize) -> DVector<BigInt> {
+    let modulo = BigInt::one() << field_exponent;
+
+    DVector::from_fn(num_rows, |x, _| {
+        let index = BigInt::from(x);
+        one_hot.iter()
+            .enumerate()
+            .fold(BigInt::zero(), |acc, (v, share)| acc + func.apply(&index, &BigInt::from(v)) * share)
+            .mod_floor(&modulo)
+    })
+}
+
+/// All subsets of {0, .., n - 1} of the given size, in lexicographic order
+fn combinations(n: usize, size: usize) -> Vec<Vec<usize>> {
+    fn extend(start: usize, n: usize, size: usize, current: &mut Vec<usize>, result: &mut Vec<Vec<usize>>) {
+        if current.len() == size {
+            result.push(current.clone());
+            return;
+        }
+
+        for i in start..n {
+            current.push(i);
+            extend(i + 1, n, size, current, result);
+            current.pop();
+        }
+    }
+
+    let mut result = Vec::new();
+    extend(0, n, size, &mut Vec::new(), &mut result);
+    result
+}
+
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::DVector;
+    use num_bigint::BigInt;
+    use num_integer::Integer;
+    use num_traits::{ToPrimitive, Zero};
+    use crate::mpc::additive_sharing::AdditiveSecretSharing;
+    use crate::mpc::distributed_preprocessing::{combinations, DistributedPreprocessing};
+    use crate::mpc::lwe_scheme::init_lwe_with_random_ptxt;
+    use crate::mpc::protocol::Protocol;
+    use crate::mpc::preprocessing::{OpeningMask, Preprocessing};
+    use crate::mpc::public_params::PublicParameters;
+
+    fn mac_key_shares(params: &PublicParameters) -> DVector<BigInt> {
+        Preprocessing::new(params).mac_key_shares()
+    }
+
+    #[test]
+    fn test_combinations() {
+        assert_eq!(combinations(4, 1), vec![vec![0], vec![1], vec![2], vec![3]]);
+        assert_eq!(combinations(4, 2).len(), 6);
+        assert_eq!(combinations(5, 2)[4], vec![1, 2]);
+    }
+
+    #[test]
+    fn test_tables_match_revealed_secrets() {
+        let params = PublicParameters::init(5, 32, 2, 6, 16, 0);
+        let setup = DistributedPreprocessing::new(&params, 2);
+        assert_eq!(setup.rounds(), 10);
+
+        let shares: Vec<_> = setup.run(&mac_key_shares(&params)).into_iter().map(|mut instances| instances.remove(0)).collect();
+
+        let reveal = |column: Vec<BigInt>, exponent: usize| {
+            AdditiveSecretSharing::reveal(&DVector::from_vec(column), exponent)
+        };
+
+        let tables: Vec<_> = shares.iter().map(|share| share.tables.expand(&params)).collect();
+
+        let s = reveal(shares.iter().map(|share| share.s.clone().unwrap()).collect(), params.d + 1);
+        let r = reveal(shares.iter().map(|share| share.r.clone().unwrap()).collect(), params.k);
+        assert!(r < params.big_l);
+
+        for x in 0..params.big_d {
+            let ltz = reveal(tables.iter().map(|tables| tables.ltz[x].clone()).collect(), params.m);
+            let diff = (BigInt::from(x) - &s).to_i64().unwrap().rem_euclid(params.big_d as i64);
+            let expected = if diff >= (params.big_d as i64) / 2 { 1 } else { 0 };
+            assert_eq!(ltz, BigInt::from(expected));
+        }
+
+        let r_digits = params.digits(&r);
+        let sign_modulo = BigInt::from(params.big_d);
+        for t in 0..params.d {
+            let r_digit = r_digits[t].clone();
+            for x in 0..params.sign_rows() {
+                let sign = reveal(tables.iter().map(|tables| tables.signs[(x, t)].clone()).collect(), params.d + 1);
+                let expected = match BigInt::from(x).cmp(&r_digit) {
+                    std::cmp::Ordering::Less => &sign_modulo - 1,
+                    std::cmp::Ordering::Equal => BigInt::zero(),
+                    std::cmp::Ordering::Greater => BigInt::from(1),
+                };
+                assert_eq!(sign, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_outputs_come_with_their_macs() {
+        let params = PublicParameters::init(3, 32, 2, 5, 8, 16);
+        let setup = DistributedPreprocessing::new(&params, 1);
+
+        let key_shares = mac_key_shares(&params);
+        let alpha = AdditiveSecretSharing::reveal(&key_shares, params.mac_ks);
+        let shares: Vec<_> = setup.run(&key_shares).into_iter().map(|mut instances| instances.remove(0)).collect();
+        let tables: Vec<_> = shares.iter().map(|share| share.tables.expand(&params)).collect();
+
+        let check = |values: Vec<BigInt>, macs: Vec<BigInt>, bits: usize| {
+            let value = AdditiveSecretSharing::reveal(&DVector::from_vec(values), bits);
+            let mac = AdditiveSecretSharing::reveal(&DVector::from_vec(macs), bits);
+            assert_eq!(mac, (&alpha * value).mod_floor(&(BigInt::from(1) << bits)));
+        };
+
+        check(shares.iter().map(|share| share.s.clone().unwrap()).collect(), shares.iter().map(|share| share.macs.s.clone()).collect(), params.mac_sign_bits());
+        check(shares.iter().map(|share| share.r.clone().unwrap()).collect(), shares.iter().map(|share| share.macs.r.clone()).collect(), params.mac_ks);
+        for mask in OpeningMask::ALL {
+            let j = mask as usize;
+            check(shares.iter().map(|share| share.masks.as_ref().unwrap()[j].clone()).collect(),
+                  shares.iter().map(|share| share.macs.masks[j].clone()).collect(), mask.ring_bits(&params));
+        }
+        for x in 0..params.big_d {
+            check(tables.iter().map(|tables| tables.ltz[x].clone()).collect(), tables.iter().map(|tables| tables.ltz_macs[x].clone()).collect(), params.mac_ltz_bits());
+        }
+        for x in 0..params.sign_rows() {
+            check(tables.iter().map(|tables| tables.signs[(x, 0)].clone()).collect(), tables.iter().map(|tables| tables.sign_macs[(x, 0)].clone()).collect(), params.mac_sign_bits());
+        }
+    }
+
+    #[test]
+    fn test_decrypt_with_distributed_preprocessing() {
+        let params = PublicParameters::init(4, 64, 1, 7, 1024, 0);
+
+        let (lwe_scheme, ptxt, a, b) = init_lwe_with_random_ptxt(params.m, params.k, params.lwe_dimension, 0);
+
+        let mut protocol = Protocol::new(&params);
+        protocol.preprocess_distributed(1);
+        protocol.share_sk(lwe_scheme.sk);
+
+        assert_eq!(protocol.decrypt(a, b), ptxt);
+    }
+
+    #[test]
+    fn test_random_masks_differ_between_runs() {
+        let params = PublicParameters::init(3, 32, 1, 5, 8, 0);
+        let setup = DistributedPreprocessing::new(&params, 1);
+
+        let runs: Vec<BigInt> = (0..4).map(|_| {
+            let shares = setup.run(&mac_key_shares(&params));
+            AdditiveSecretSharing::reveal(&DVector::from_fn(params.n, |i, _| shares[i][0].r.clone().unwrap()), params.k)
+        }).collect();
+
+        assert!(runs.iter().any(|r| r != &runs[0]));
+    }
+
+    #[test]
+    fn test_instances_use_independent_masks() {
+        let params = PublicParameters::init(3, 32, 1, 5, 8, 0);
+        let setup = DistributedPreprocessing::new(&params, 1).with_instances(4);
+
+        let shares = setup.run(&mac_key_shares(&params));
+        assert!(shares.iter().all(|instances| instances.len() == 4));
+
+        let reveal_r = |instance: usize| {
+            AdditiveSecretSharing::reveal(&DVector::from_fn(params.n, |i, _| shares[i][instance].r.clone().unwrap()), params.k)
+        };
+
+        let masks: Vec<BigInt> = (0..4).map(reveal_r).collect();
+        assert!(masks.iter().all(|r| r < &params.big_l));
+        assert!(masks.iter().any(|r| r != &masks[0]));
+    }
+}
@@ -0,0 +1,204 @@
+use nalgebra::DVector;
+use num_bigint::{BigInt, UniformBigInt};
+use num_integer::Integer;
+use num_traits::{One, ToPrimitive, Zero};
+use rand::distributions::uniform::UniformSampler;
+use rand::seq::index;
+use rand::Rng;
+use rand_distr::{Distribution, Normal};
+use serde::{Deserialize, Serialize};
+
+/// Rounded Gaussian samples further than TAIL_CUT * sigma from 0 are rejected, which bounds the error
+pub const TAIL_CUT: f64 = 6.0;
+
+/// Bits of the noise budget the default error leaves free, for up to 2^20 errors summed into one decryption: the
+/// public key rows, the key shares of a distributed key generation and homomorphic additions
+pub const DEFAULT_ERROR_HEADROOM_BITS: usize = 20;
+
+/// Distribution of the LWE errors, centered around 0
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ErrorDistribution {
+    /// Uniform in [-bound, bound]
+    Uniform { bound: BigInt },
+
+    /// Gaussian of standard deviation sigma rounded to the nearest integer, tail cut at TAIL_CUT * sigma
+    RoundedGaussian { sigma: f64 },
+
+    /// Sum of eta differences of two fair bits
+    CenteredBinomial { eta: usize },
+
+    /// Uniform in {-1, 0, 1}
+    Ternary,
+}
+
+impl ErrorDistribution {
+    /// The default, a uniform error as wide as a noise budget of `l` bits allows after DEFAULT_ERROR_HEADROOM_BITS.
+    /// A narrow error such as the rounded Gaussian of sigma 3.2 of the homomorphic encryption standard is far from
+    /// secure mod 2^64 at the dimensions used here, the width is what makes the key hard to find
+    pub fn wide_uniform(l: usize) -> ErrorDistribution {
+        let bits = l.saturating_sub(DEFAULT_ERROR_HEADROOM_BITS + 1);
+        ErrorDistribution::Uniform { bound: BigInt::one() << bits }
+    }
+
+    pub fn sample<R: Rng>(&self, rng: &mut R) -> BigInt {
+        match self {
+            ErrorDistribution::Uniform { bound } => {
+                UniformBigInt::new_inclusive(-bound, bound).sample(rng)
+            }
+            ErrorDistribution::RoundedGaussian { sigma } => {
+                let normal = Normal::new(0.0, *sigma).unwrap();
+                loop {
+                    let x = normal.sample(rng).round();
+                    if x.abs() <= TAIL_CUT * sigma {
+                        return BigInt::from(x as i64);
+                    }
+                }
+            }
+            ErrorDistribution::CenteredBinomial { eta } => {
+                let ones = (0..*eta).filter(|_| rng.gen::<bool>()).count() as i64;
+                let minus_ones = (0..*eta).filter(|_| rng.gen::<bool>()).count() as i64;
+                BigInt::from(ones - minus_ones)
+            }
+            ErrorDistribution::Ternary => BigInt::from(rng.gen_range(-1..=1)),
+        }
+    }
+
+    /// Largest absolute value of a sample
+    pub fn bound(&self) -> BigInt {
+        match self {
+            ErrorDistribution::Uniform { bound } => bound.clone(),
+            ErrorDistribution::RoundedGaussian { sigma } => BigInt::from((TAIL_CUT * sigma).floor() as i64),
+            ErrorDistribution::CenteredBinomial { eta } => BigInt::from(*eta),
+            ErrorDistribution::Ternary => BigInt::one(),
+        }
+    }
+
+    /// Standard deviation of a sample, the width the lattice attacks see
+    pub fn standard_deviation(&self) -> f64 {
+        match self {
+            ErrorDistribution::Uniform { bound } => {
+                let bound = bound.to_f64().unwrap();
+                (bound * (bound + 1.0) / 3.0).sqrt()
+            }
+            // Rounding adds a uniform error in [-1/2, 1/2]
+            ErrorDistribution::RoundedGaussian { sigma } => (sigma * sigma + 1.0 / 12.0).sqrt(),
+            ErrorDistribution::CenteredBinomial { eta } => (*eta as f64 / 2.0).sqrt(),
+            ErrorDistribution::Ternary => (2.0f64 / 3.0).sqrt(),
+        }
+    }
+}
+
+/// Distribution of the LWE secret key
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum KeyDistribution {
+    /// Uniform mod q
+    #[default]
+    Uniform,
+
+    /// Uniform in {0, 1}
+    Binary,
+
+    /// Uniform in {-1, 0, 1}
+    Ternary,
+
+    /// Exactly h coordinates uniform in {-1, 1}, the others 0
+    FixedHammingWeight(usize),
+}
+
+impl KeyDistribution {
+    /// Key of length `dimension`, coordinates reduced mod q
+    pub fn sample<R: Rng>(&self, dimension: usize, q: &BigInt, rng: &mut R) -> DVector<BigInt> {
+        let key = match self {
+            KeyDistribution::Uniform => {
+                return DVector::from_fn(dimension, |_, _| UniformBigInt::new(BigInt::zero(), q).sample(rng));
+            }
+            KeyDistribution::Binary => DVector::from_fn(dimension, |_, _| BigInt::from(rng.gen_range(0..=1))),
+            KeyDistribution::Ternary => DVector::from_fn(dimension, |_, _| BigInt::from(rng.gen_range(-1..=1))),
+            KeyDistribution::FixedHammingWeight(weight) => {
+                assert!(*weight <= dimension, "Hamming weight {} exceeds the dimension {}", weight, dimension);
+
+                let mut key = DVector::zeros(dimension);
+                for i in index::sample(rng, dimension, *weight) {
+                    key[i] = if rng.gen::<bool>() { BigInt::one() } else { -BigInt::one() };
+                }
+                key
+            }
+        };
+
+        key.map(|x| x.mod_floor(q))
+    }
+
+    /// Standard deviation of a coordinate of a key of length `dimension`, binary keys centered first
+    pub fn standard_deviation(&self, dimension: usize, q: &BigInt) -> f64 {
+        match self {
+            KeyDistribution::Uniform => q.to_f64().unwrap() / 12f64.sqrt(),
+            KeyDistribution::Binary => 0.5,
+            KeyDistribution::Ternary => (2.0f64 / 3.0).sqrt(),
+            KeyDistribution::FixedHammingWeight(weight) => (*weight as f64 / dimension as f64).sqrt(),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use num_bigint::BigInt;
+    use num_integer::Integer;
+    use num_traits::{One, Signed, Zero};
+    use crate::mpc::distributions::{ErrorDistribution, KeyDistribution};
+    use crate::mpc::public_params::PublicParameters;
+
+    #[test]
+    fn test_errors_stay_within_bound() {
+        let mut rng = rand::thread_rng();
+
+        for distribution in [
+            ErrorDistribution::Uniform { bound: BigInt::from(100) },
+            ErrorDistribution::RoundedGaussian { sigma: 3.2 },
+            ErrorDistribution::CenteredBinomial { eta: 3 },
+            ErrorDistribution::Ternary,
+        ] {
+            let bound = distribution.bound();
+            let samples: Vec<BigInt> = (0..2000).map(|_| distribution.sample(&mut rng)).collect();
+
+            assert!(samples.iter().all(|e| e.abs() <= bound), "{:?}", distribution);
+            // Centered around 0
+            assert!(samples.iter().any(|e| e.is_positive()) && samples.iter().any(|e| e.is_negative()));
+        }
+
+        assert_eq!(ErrorDistribution::RoundedGaussian { sigma: 3.2 }.bound(), BigInt::from(19));
+    }
+
+    #[test]
+    fn test_default_error_is_wide() {
+        // The dimensions the benchmarks run with, mod 2^64
+        for lwe_dimension in [777, 870, 1024] {
+            let params = PublicParameters::init(4, 64, 4, 7, lwe_dimension, 80);
+            assert_eq!(params.error_distribution, ErrorDistribution::wide_uniform(60));
+            assert!(params.security_estimate().bits() >= 128.0, "{} coordinates", lwe_dimension);
+            assert!(params.tolerates_noise(1 << 19));
+        }
+
+        assert_eq!(ErrorDistribution::wide_uniform(8).bound(), BigInt::one());
+    }
+
+    #[test]
+    fn test_key_distributions() {
+        let mut rng = rand::thread_rng();
+        let q = BigInt::one() << 32;
+        let centered = |x: &BigInt| if x > &(&q / 2) { x - &q } else { x.clone() };
+
+        let binary = KeyDistribution::Binary.sample(64, &q, &mut rng);
+        assert!(binary.iter().all(|x| x.is_zero() || x.is_one()));
+
+        let ternary = KeyDistribution::Ternary.sample(64, &q, &mut rng);
+        assert!(ternary.iter().all(|x| centered(x).abs() <= BigInt::one()));
+
+        let sparse = KeyDistribution::FixedHammingWeight(10).sample(64, &q, &mut rng);
+        assert_eq!(sparse.iter().filter(|x| !x.is_zero()).count(), 10);
+        assert!(sparse.iter().all(|x| centered(x).abs() <= BigInt::one()));
+
+        let uniform = KeyDistribution::Uniform.sample(64, &q, &mut rng);
+        assert!(uniform.iter().all(|x| x.mod_floor(&q) == *x));
+    }
+}
@@ -0,0 +1,224 @@
+use nalgebra::DVector;
+use num_bigint::{BigInt, UniformBigInt};
+use num_integer::Integer;
+use num_traits::{One, Zero};
+use rand::distributions::uniform::UniformSampler;
+use rand::Rng;
+
+// Galois ring GR(2^k, D) = Z_(2^k)[X] / (f(X)) with f monic of degree D and irreducible modulo 2.
+//
+// Z_(2^k) has only two elements whose difference is a unit, which is not enough for Shamir sharing.
+// In GR(2^k, D) the 2^D elements with 0/1 coefficients have pairwise invertible differences, and Z_(2^k)
+// embeds as the constant polynomials.
+// Elements are coefficient vectors of length D, from the constant term up.
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct GaloisRing {
+    /// Coefficients live in Z_(2^ring_exponent)
+    pub ring_exponent: usize,
+
+    /// Extension degree D
+    pub degree: usize,
+
+    /// Coefficients of f(X) below X^D, f(X) = X^D + sum_j modulus[j] * X^j
+    pub modulus: Vec<bool>,
+
+    q: BigInt,
+}
+
+impl GaloisRing {
+    pub fn new(ring_exponent: usize, degree: usize) -> GaloisRing {
+        assert!((1..32).contains(&degree));
+
+        let polynomial = (1u64 << degree..1u64 << (degree + 1))
+            .find(|&f| is_irreducible_mod_2(f))
+            .unwrap();
+
+        GaloisRing {
+            ring_exponent,
+            degree,
+            modulus: (0..degree).map(|j| (polynomial >> j) & 1 == 1).collect(),
+            q: BigInt::one() << ring_exponent,
+        }
+    }
+
+    /// Smallest extension holding an evaluation point for each of `num_parties` parties plus the secret
+    pub fn for_parties(ring_exponent: usize, num_parties: usize) -> GaloisRing {
+        let mut degree = 1;
+        while (1usize << degree) < num_parties + 1 {
+            degree += 1;
+        }
+
+        GaloisRing::new(ring_exponent, degree)
+    }
+
+    pub fn zero(&self) -> DVector<BigInt> {
+        DVector::zeros(self.degree)
+    }
+
+    pub fn one(&self) -> DVector<BigInt> {
+        self.from_int(&BigInt::one())
+    }
+
+    /// Embeds x mod 2^k as a constant polynomial
+    pub fn from_int(&self, x: &BigInt) -> DVector<BigInt> {
+        let mut element = self.zero();
+        element[0] = x.mod_floor(&self.q);
+        element
+    }
+
+    pub fn constant_term(&self, a: &DVector<BigInt>) -> BigInt {
+        a[0].clone()
+    }
+
+    /// The element whose coefficients are the bits of `index`, differences of two such elements are units
+    pub fn exceptional_point(&self, index: usize) -> DVector<BigInt> {
+        assert!(index < 1 << self.degree, "GR(2^k, {}) has only {} exceptional points", self.degree, 1usize << self.degree);
+
+        DVector::from_fn(self.degree, |j, _| BigInt::from((index >> j) & 1))
+    }
+
+    pub fn random<R: Rng>(&self, rng: &mut R) -> DVector<BigInt> {
+        DVector::from_fn(self.degree, |_, _| UniformBigInt::new(BigInt::zero(), &self.q).sample(rng))
+    }
+
+    pub fn add(&self, a: &DVector<BigInt>, b: &DVector<BigInt>) -> DVector<BigInt> {
+        (a + b).map(|x| x.mod_floor(&self.q))
+    }
+
+    pub fn sub(&self, a: &DVector<BigInt>, b: &DVector<BigInt>) -> DVector<BigInt> {
+        (a - b).map(|x| x.mod_floor(&self.q))
+    }
+
+    pub fn mul(&self, a: &DVector<BigInt>, b: &DVector<BigInt>) -> DVector<BigInt> {
+        let mut product = vec![BigInt::zero(); 2 * self.degree - 1];
+        for (i, a_i) in a.iter().enumerate() {
+            for (j, b_j) in b.iter().enumerate() {
+                product[i + j] += a_i * b_j;
+            }
+        }
+
+        // X^D = -sum_j f_j * X^j
+        for i in (self.degree..product.len()).rev() {
+            let top = std::mem::take(&mut product[i]);
+            for (j, _) in self.modulus.iter().enumerate().filter(|(_, bit)| **bit) {
+                product[i - self.degree + j] -= &top;
+            }
+        }
+
+        DVector::from_fn(self.degree, |i, _| product[i].mod_floor(&self.q))
+    }
+
+    pub fn pow(&self, a: &DVector<BigInt>, exponent: &BigInt) -> DVector<BigInt> {
+        let mut result = self.one();
+        for i in (0..exponent.bits()).rev() {
+            result = self.mul(&result, &result);
+            if exponent.bit(i) {
+                result = self.mul(&result, a);
+            }
+        }
+        result
+    }
+
+    /// a is a unit iff it is non zero modulo 2
+    pub fn is_unit(&self, a: &DVector<BigInt>) -> bool {
+        a.iter().any(|x| x.is_odd())
+    }
+
+    pub fn inverse(&self, a: &DVector<BigInt>) -> Option<DVector<BigInt>> {
+        if !self.is_unit(a) {
+            return None;
+        }
+
+        // a^(2^D - 2) is the inverse modulo 2, as GR(2^k, D) / 2 is the field GF(2^D)
+        let mut inverse = self.pow(a, &((BigInt::one() << self.degree) - 2));
+
+        // Newton iteration x <- x * (2 - a * x) doubles the number of correct bits
+        let two = self.from_int(&BigInt::from(2));
+        let mut precision = 1;
+        while precision < self.ring_exponent {
+            inverse = self.mul(&inverse, &self.sub(&two, &self.mul(a, &inverse)));
+            precision *= 2;
+        }
+
+        Some(inverse)
+    }
+}
+
+
+/// Polynomial over GF(2) given by the bits of f, irreducible iff no polynomial of degree at most deg(f) / 2 divides it
+fn is_irreducible_mod_2(f: u64) -> bool {
+    let degree = 63 - f.leading_zeros() as usize;
+
+    (2u64..1 << (degree / 2 + 1)).all(|g| polynomial_rem_mod_2(f, g) != 0)
+}
+
+fn polynomial_rem_mod_2(mut f: u64, g: u64) -> u64 {
+    let g_degree = 63 - g.leading_zeros();
+    while f != 0 && 63 - f.leading_zeros() >= g_degree {
+        f ^= g << (63 - f.leading_zeros() - g_degree);
+    }
+    f
+}
+
+
+#[cfg(test)]
+mod tests {
+    use num_bigint::BigInt;
+    use crate::mpc::galois_ring::{is_irreducible_mod_2, GaloisRing};
+
+    #[test]
+    fn test_irreducible_polynomials() {
+        // x^2 + x + 1, x^3 + x + 1
+        assert!(is_irreducible_mod_2(0b111));
+        assert!(is_irreducible_mod_2(0b1011));
+        // x^2 + 1 = (x + 1)^2, x^4 + x^2 + 1 = (x^2 + x + 1)^2
+        assert!(!is_irreducible_mod_2(0b101));
+        assert!(!is_irreducible_mod_2(0b10101));
+
+        assert_eq!(GaloisRing::new(8, 3).modulus, vec![true, true, false]);
+        assert_eq!(GaloisRing::for_parties(8, 4).degree, 3);
+        assert_eq!(GaloisRing::for_parties(8, 7).degree, 3);
+        assert_eq!(GaloisRing::for_parties(8, 8).degree, 4);
+    }
+
+    #[test]
+    fn test_ring_axioms() {
+        let mut rng = rand::thread_rng();
+        let ring = GaloisRing::new(16, 4);
+
+        for _ in 0..20 {
+            let (a, b, c) = (ring.random(&mut rng), ring.random(&mut rng), ring.random(&mut rng));
+
+            assert_eq!(ring.mul(&ring.mul(&a, &b), &c), ring.mul(&a, &ring.mul(&b, &c)));
+            assert_eq!(ring.mul(&a, &b), ring.mul(&b, &a));
+            assert_eq!(ring.mul(&a, &ring.add(&b, &c)), ring.add(&ring.mul(&a, &b), &ring.mul(&a, &c)));
+            assert_eq!(ring.mul(&a, &ring.one()), a);
+            assert_eq!(ring.sub(&ring.add(&a, &b), &b), a);
+        }
+
+        // Z_(2^k) embeds as the constants
+        let (x, y) = (BigInt::from(40000), BigInt::from(-3));
+        assert_eq!(ring.mul(&ring.from_int(&x), &ring.from_int(&y)), ring.from_int(&(x * y)));
+    }
+
+    #[test]
+    fn test_exceptional_points_have_invertible_differences() {
+        let ring = GaloisRing::new(32, 3);
+
+        for i in 0..8 {
+            for j in 0..8 {
+                let difference = ring.sub(&ring.exceptional_point(i), &ring.exceptional_point(j));
+                match ring.inverse(&difference) {
+                    Some(inverse) => {
+                        assert_ne!(i, j);
+                        assert_eq!(ring.mul(&difference, &inverse), ring.one());
+                    }
+                    None => assert_eq!(i, j),
+                }
+            }
+        }
+
+        assert!(ring.inverse(&ring.from_int(&BigInt::from(2))).is_none());
+    }
+}
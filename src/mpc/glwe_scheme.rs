@@ -0,0 +1,195 @@
+use nalgebra::DVector;
+use num_bigint::{BigInt, UniformBigInt};
+use num_integer::Integer;
+use num_traits::Zero;
+use rand::distributions::uniform::UniformSampler;
+use serde::{Deserialize, Serialize};
+use crate::mpc::public_params::PublicParameters;
+
+// GLWE over R_q = Z_q[X] / (X^N + 1), RLWE being the case of a single mask polynomial.
+//
+// A ciphertext (a_1, ..., a_k, b) with b = -sum_i a_i * s_i + e + (q/p) * m carries the N coefficients of m.
+// Coefficient j of b + sum_i a_i * s_i is an LWE phase for the key made of the coefficients of s_1, ..., s_k,
+// so sample extraction turns the ciphertext into N LWE ciphertexts of dimension k * N that the threshold
+// decryption handles as they are.
+
+/// Product of two polynomials of R_q, given by their N coefficients from the constant term up
+pub fn negacyclic_mul(a: &DVector<BigInt>, b: &DVector<BigInt>, q: &BigInt) -> DVector<BigInt> {
+    assert_eq!(a.nrows(), b.nrows());
+    let n = a.nrows();
+
+    let mut product = DVector::from_element(n, BigInt::zero());
+    for (i, a_i) in a.iter().enumerate() {
+        for (j, b_j) in b.iter().enumerate() {
+            // X^N = -1
+            if i + j < n {
+                product[i + j] += a_i * b_j;
+            } else {
+                product[i + j - n] -= a_i * b_j;
+            }
+        }
+    }
+
+    product.map(|x| x.mod_floor(q))
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GlweCiphertext {
+    /// Mask polynomials a_1, ..., a_k
+    pub a: Vec<DVector<BigInt>>,
+    pub b: DVector<BigInt>,
+}
+
+impl GlweCiphertext {
+    pub fn polynomial_size(&self) -> usize {
+        self.b.nrows()
+    }
+
+    /// LWE ciphertext of coefficient `coefficient` under the concatenated coefficients of s_1, ..., s_k
+    pub fn sample_extract(&self, coefficient: usize) -> (DVector<BigInt>, BigInt) {
+        let n = self.polynomial_size();
+        assert!(coefficient < n);
+
+        // Coefficient j of a * s is sum_t s_t * a_(j - t), where a_(j - t + N) comes with a minus sign when t > j
+        let a = DVector::from_iterator(self.a.len() * n, self.a.iter().flat_map(|mask| {
+            (0..n).map(move |t| {
+                if t <= coefficient {
+                    mask[coefficient - t].clone()
+                } else {
+                    -&mask[n + coefficient - t]
+                }
+            })
+        }));
+
+        (a, self.b[coefficient].clone())
+    }
+
+    /// One LWE ciphertext per plaintext slot, in slot order
+    pub fn sample_extract_all(&self) -> Vec<(DVector<BigInt>, BigInt)> {
+        (0..self.polynomial_size()).map(|j| self.sample_extract(j)).collect()
+    }
+}
+
+pub struct GlweScheme {
+    pub params: PublicParameters,
+
+    /// Number k of mask polynomials, RLWE when 1
+    pub glwe_dimension: usize,
+
+    /// Degree N of X^N + 1
+    pub polynomial_size: usize,
+
+    /// s_1, ..., s_k, their coefficients concatenated are the LWE key of the extracted samples
+    pub sk: Vec<DVector<BigInt>>,
+}
+
+impl GlweScheme {
+    /// The extracted samples have dimension k * N, which must be the LWE dimension of `params`
+    pub fn new(params: &PublicParameters, glwe_dimension: usize, polynomial_size: usize) -> GlweScheme {
+        assert_eq!(glwe_dimension * polynomial_size, params.lwe_dimension,
+                   "k * N must equal the LWE dimension the parties hold key shares for");
+
+        let mut rng = rand::thread_rng();
+        let sk = (0..glwe_dimension)
+            .map(|_| params.key_distribution.sample(polynomial_size, &params.q, &mut rng))
+            .collect();
+
+        GlweScheme {
+            params: params.clone(),
+            glwe_dimension,
+            polynomial_size,
+            sk,
+        }
+    }
+
+    /// Key of the extracted LWE samples
+    pub fn flattened_sk(&self) -> DVector<BigInt> {
+        DVector::from_iterator(self.params.lwe_dimension, self.sk.iter().flat_map(|s| s.iter().cloned()))
+    }
+
+    /// Encrypts the N slots of `m`, each mod p
+    pub fn encrypt(&self, m: &DVector<BigInt>) -> GlweCiphertext {
+        assert_eq!(m.nrows(), self.polynomial_size);
+        let mut rng = rand::thread_rng();
+        let q = &self.params.q;
+
+        let a: Vec<DVector<BigInt>> = (0..self.glwe_dimension)
+            .map(|_| DVector::from_fn(self.polynomial_size, |_, _| UniformBigInt::new(BigInt::zero(), q).sample(&mut rng)))
+            .collect();
+
+        let e = DVector::from_fn(self.polynomial_size, |_, _| self.params.error_distribution.sample(&mut rng));
+
+        // b = -sum_i a_i * s_i + e + (q/p) * m in [0, q)
+        let a_dot_sk = a.iter().zip(&self.sk)
+            .fold(DVector::zeros(self.polynomial_size), |acc, (a_i, s_i)| acc + negacyclic_mul(a_i, s_i, q));
+        let b = (-a_dot_sk + e + m.map(|m_j| m_j.mod_floor(&self.params.p) * &self.params.big_l))
+            .map(|x| x.mod_floor(q));
+
+        GlweCiphertext { a, b }
+    }
+
+    pub fn decrypt(&self, ciphertext: &GlweCiphertext) -> DVector<BigInt> {
+        let q = &self.params.q;
+
+        // b + sum_i a_i * s_i = e + (q/p) * m
+        let phase = ciphertext.a.iter().zip(&self.sk)
+            .fold(ciphertext.b.clone(), |acc, (a_i, s_i)| acc + negacyclic_mul(a_i, s_i, q));
+
+        // round to the nearest multiple of q/p
+        let half_l: BigInt = &self.params.big_l >> 1;
+        phase.map(|x| ((x + &half_l).mod_floor(q) / &self.params.big_l).mod_floor(&self.params.p))
+    }
+
+    /// Plaintext of N random slots
+    pub fn random_plaintext(&self) -> DVector<BigInt> {
+        let mut rng = rand::thread_rng();
+        DVector::from_fn(self.polynomial_size, |_, _| {
+            UniformBigInt::new(BigInt::zero(), &self.params.p).sample(&mut rng)
+        })
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::DVector;
+    use num_bigint::BigInt;
+    use num_integer::Integer;
+    use num_traits::One;
+    use crate::mpc::glwe_scheme::{negacyclic_mul, GlweScheme};
+    use crate::mpc::public_params::PublicParameters;
+
+    fn monomial(j: usize, polynomial_size: usize) -> DVector<BigInt> {
+        let mut x = DVector::zeros(polynomial_size);
+        x[j] = BigInt::one();
+        x
+    }
+
+    #[test]
+    fn test_negacyclic_wrap_around() {
+        let q = BigInt::one() << 32;
+
+        // X^5 * X^4 = X^9 = -X
+        let product = negacyclic_mul(&monomial(5, 8), &monomial(4, 8), &q);
+        assert_eq!(product, monomial(1, 8).map(|x: BigInt| (-x).mod_floor(&q)));
+    }
+
+    #[test]
+    fn test_encrypt_decrypt_and_sample_extraction() {
+        for (glwe_dimension, polynomial_size) in [(1, 16), (2, 8)] {
+            let params = PublicParameters::init(4, 32, 2, 6, 16, 0);
+            let scheme = GlweScheme::new(&params, glwe_dimension, polynomial_size);
+
+            let m = scheme.random_plaintext();
+            let ciphertext = scheme.encrypt(&m);
+            assert_eq!(scheme.decrypt(&ciphertext), m);
+
+            // Every extracted sample is an LWE encryption of its slot under the flattened key
+            let sk = scheme.flattened_sk();
+            let half_l: BigInt = &params.big_l >> 1;
+            let slots: DVector<BigInt> = DVector::from_iterator(polynomial_size, ciphertext.sample_extract_all().into_iter()
+                .map(|(a, b)| ((b + a.dot(&sk) + &half_l).mod_floor(&params.q) / &params.big_l).mod_floor(&params.p)));
+            assert_eq!(slots, m);
+        }
+    }
+}
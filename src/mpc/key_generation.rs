@@ -0,0 +1,133 @@
+use nalgebra::{DMatrix, DVector};
+use num_bigint::{BigInt, UniformBigInt};
+use num_integer::Integer;
+use num_traits::Zero;
+use rand::distributions::uniform::UniformSampler;
+use crate::mpc::lwe_scheme::LwePublicKey;
+use crate::mpc::public_params::PublicParameters;
+
+// Distributed generation of the LWE secret key.
+//
+// The public matrix A is common random. Party i samples its key share sk_i and an error e_i from the distributions
+// of the public parameters, and publishes b_i = -A * sk_i + e_i. The public key is (A, b = sum_i b_i), an LWE
+// instance for the key sk = sum_i sk_i with error sum_i e_i. For a small key distribution the coordinates of sk
+// reach n times the bound of a single share. The key only ever exists as the additive shares sk_i, which are exactly the
+// shares `Party::execute_step_one` works with.
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DistributedKeyGeneration {
+    pub params: PublicParameters,
+
+    /// Public matrix A, one row per public LWE sample
+    pub public_a: DMatrix<BigInt>,
+}
+
+/// What a party gets out of the key generation, sk_share stays with the party and b_share is published
+#[derive(Clone, Debug, PartialEq)]
+pub struct KeyContribution {
+    pub sk_share: DVector<BigInt>,
+    pub b_share: DVector<BigInt>,
+}
+
+impl DistributedKeyGeneration {
+    /// Samples a fresh public matrix with `pk_rows` rows
+    pub fn new(params: &PublicParameters, pk_rows: usize) -> DistributedKeyGeneration {
+        let mut rng = rand::thread_rng();
+
+        let public_a = DMatrix::from_fn(pk_rows, params.lwe_dimension, |_, _| {
+            UniformBigInt::new(BigInt::zero(), &params.q).sample(&mut rng)
+        });
+
+        DistributedKeyGeneration::with_public_matrix(params, public_a)
+    }
+
+    pub fn with_public_matrix(params: &PublicParameters, public_a: DMatrix<BigInt>) -> DistributedKeyGeneration {
+        assert_eq!(public_a.ncols(), params.lwe_dimension);
+        // A public-key ciphertext carries the errors of n parties on every row plus its own
+        assert!(params.tolerates_noise(params.n * public_a.nrows() + 1),
+                "The error distribution is too wide for {} public samples of {} parties", public_a.nrows(), params.n);
+
+        DistributedKeyGeneration {
+            params: params.clone(),
+            public_a,
+        }
+    }
+
+    pub fn contribute(&self) -> KeyContribution {
+        let mut rng = rand::thread_rng();
+
+        let sk_share = self.params.key_distribution.sample(self.params.lwe_dimension, &self.params.q, &mut rng);
+        let e = DVector::from_fn(self.public_a.nrows(), |_, _| self.params.error_distribution.sample(&mut rng));
+
+        // b_i = -A * sk_i + e_i in [0, q)
+        let b_share = (-(&self.public_a * &sk_share) + e)
+            .map(|x| x.mod_floor(&self.params.q));
+
+        KeyContribution {
+            sk_share,
+            b_share,
+        }
+    }
+
+    /// b of the public samples from the published shares of all parties
+    pub fn combine(&self, b_shares: &[DVector<BigInt>]) -> DVector<BigInt> {
+        assert_eq!(b_shares.len(), self.params.n, "Key generation needs the public share of every party");
+
+        b_shares.iter()
+            .fold(DVector::zeros(self.public_a.nrows()), |acc, b_share| acc + b_share)
+            .map(|x| x.mod_floor(&self.params.q))
+    }
+
+    pub fn public_key(&self, b: &DVector<BigInt>) -> LwePublicKey {
+        LwePublicKey {
+            big_a: self.public_a.clone(),
+            b: b.clone(),
+            p_exponent: self.params.m,
+            q_exponent: self.params.k,
+            error_distribution: self.params.error_distribution.clone(),
+        }
+    }
+
+    /// Public sample `row` as a ciphertext (a, b), it encrypts 0
+    pub fn public_sample(&self, b: &DVector<BigInt>, row: usize) -> (DVector<BigInt>, BigInt) {
+        (self.public_a.row(row).transpose(), b[row].clone())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::DVector;
+    use num_bigint::BigInt;
+    use num_integer::Integer;
+    use num_traits::Signed;
+    use crate::mpc::key_generation::DistributedKeyGeneration;
+    use crate::mpc::public_params::PublicParameters;
+
+    #[test]
+    fn test_public_samples_match_the_joint_key() {
+        let params = PublicParameters::init(5, 32, 2, 6, 16, 0);
+        let key_generation = DistributedKeyGeneration::new(&params, 8);
+
+        let contributions: Vec<_> = (0..params.n).map(|_| key_generation.contribute()).collect();
+        let b = key_generation.combine(&contributions.iter().map(|c| c.b_share.clone()).collect::<Vec<_>>());
+
+        // Only a test puts the key together
+        let sk = contributions.iter()
+            .fold(DVector::zeros(params.lwe_dimension), |acc, c| acc + &c.sk_share)
+            .map(|x: BigInt| x.mod_floor(&params.q));
+
+        let half_q = &params.q / 2;
+        let bound = params.noise_bound(params.n);
+        for row in 0..key_generation.public_a.nrows() {
+            let (a, b_row) = key_generation.public_sample(&b, row);
+
+            // b + <a, sk> is the joint error, centered around 0
+            let mut e = (b_row + a.dot(&sk)).mod_floor(&params.q);
+            if e > half_q {
+                e -= &params.q;
+            }
+            assert!(e.abs() <= bound);
+        }
+    }
+}
@@ -0,0 +1,97 @@
+use nalgebra::{DMatrix, DVector};
+use num_bigint::BigInt;
+use num_integer::Integer;
+use num_traits::Zero;
+use crate::mpc::additive_sharing::AdditiveSecretSharing;
+use crate::mpc::public_params::PublicParameters;
+
+// Proactive refresh of the additive sk shares.
+//
+// Every party deals an additive sharing of the zero vector and sends one piece to each party. The new share of
+// a party is its old share plus all the pieces it received, its own included. The key stays the same while the
+// new shares are independent of the old ones, so shares stolen before a refresh are useless combined with
+// shares stolen after it.
+//
+// Shares live mod 2^(k+s), the ring of the MACs, and the MAC shares of sk are refreshed the same way.
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct KeyRefresh {
+    pub params: PublicParameters,
+}
+
+impl KeyRefresh {
+    pub fn new(params: &PublicParameters) -> KeyRefresh {
+        KeyRefresh {
+            params: params.clone()
+        }
+    }
+
+    /// Additive sharing of the zero vector of length `dimension`, piece i is meant for party i
+    pub fn zero_sharing(&self, dimension: usize) -> Vec<DVector<BigInt>> {
+        // rows = key coordinates; columns = parties
+        let pieces = DMatrix::from_rows(&(0..dimension)
+            .map(|_| AdditiveSecretSharing::share(&BigInt::zero(), self.params.n, self.params.mac_ks).transpose())
+            .collect::<Vec<_>>());
+
+        pieces.column_iter().map(|piece| piece.into()).collect()
+    }
+
+    /// New share of a party from its old share and the pieces of all parties
+    pub fn refresh(&self, sk: &DVector<BigInt>, pieces: &[DVector<BigInt>]) -> DVector<BigInt> {
+        assert_eq!(pieces.len(), self.params.n, "Key refresh needs a piece from every party");
+
+        pieces.iter()
+            .fold(sk.clone(), |acc, piece| acc + piece)
+            .map(|x| x.mod_floor(&self.params.mac_big_ks))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::DVector;
+    use num_bigint::BigInt;
+    use num_integer::Integer;
+    use num_traits::Zero;
+    use crate::mpc::additive_sharing::AdditiveSecretSharing;
+    use crate::mpc::key_refresh::KeyRefresh;
+    use crate::mpc::public_params::PublicParameters;
+
+    #[test]
+    fn test_zero_sharing_sums_to_zero() {
+        let params = PublicParameters::init(5, 32, 1, 6, 16, 0);
+        let pieces = KeyRefresh::new(&params).zero_sharing(params.lwe_dimension);
+
+        assert_eq!(pieces.len(), params.n);
+        assert!(pieces.iter().all(|piece| piece.nrows() == params.lwe_dimension));
+
+        let sum = pieces.iter().fold(DVector::zeros(params.lwe_dimension), |acc, piece| acc + piece);
+        assert!(sum.iter().all(|x| x.mod_floor(&params.mac_big_ks).is_zero()));
+    }
+
+    #[test]
+    fn test_refresh_keeps_the_key() {
+        let params = PublicParameters::init(4, 32, 1, 6, 8, 0);
+        let key_refresh = KeyRefresh::new(&params);
+
+        let sk = DVector::from_fn(params.lwe_dimension, |i, _| BigInt::from(i * 1000));
+        let shares: Vec<DVector<BigInt>> = {
+            let columns: Vec<_> = sk.iter().map(|x| AdditiveSecretSharing::share(x, params.n, params.k)).collect();
+            (0..params.n).map(|i| DVector::from_fn(params.lwe_dimension, |j, _| columns[j][i].clone())).collect()
+        };
+
+        let pieces_per_sender: Vec<_> = (0..params.n).map(|_| key_refresh.zero_sharing(params.lwe_dimension)).collect();
+        let refreshed: Vec<DVector<BigInt>> = shares.iter().enumerate()
+            .map(|(i, share)| {
+                let received: Vec<_> = pieces_per_sender.iter().map(|pieces| pieces[i].clone()).collect();
+                key_refresh.refresh(share, &received)
+            })
+            .collect();
+
+        let revealed = refreshed.iter()
+            .fold(DVector::zeros(params.lwe_dimension), |acc, share| acc + share)
+            .map(|x| x.mod_floor(&params.q));
+        assert_eq!(revealed, sk);
+        assert_ne!(refreshed, shares);
+    }
+}
@@ -0,0 +1,179 @@
+use nalgebra::DVector;
+use num_bigint::{BigInt, UniformBigInt};
+use num_integer::Integer;
+use num_traits::{One, ToPrimitive, Zero};
+use rand::distributions::uniform::UniformSampler;
+use crate::mpc::additive_sharing::AdditiveSecretSharing;
+use crate::mpc::preprocessed_gate::{PreprocessedGate, ProcessingFunction};
+
+// Evaluation of any function f: Z_M -> Z_2^e on a secret x, M = 2^m.
+//
+// The preprocessing picks a random mask s, shares it mod M and shares the table whose entry v is f(v - s mod M).
+// Online, the parties open x + s mod M, which hides x, and each party looks up its share of f(x) at that index.
+// This is the mechanism of the LTZ and sign gates of the decryption, for functions given by the caller.
+
+/// A function of the unmasked value
+pub trait TableFunction {
+    fn eval(&self, x: &BigInt) -> BigInt;
+}
+
+impl<F: Fn(&BigInt) -> BigInt> TableFunction for F {
+    fn eval(&self, x: &BigInt) -> BigInt {
+        self(x)
+    }
+}
+
+/// 1 when x equals the value, 0 otherwise
+pub struct Equals(pub BigInt);
+
+impl TableFunction for Equals {
+    fn eval(&self, x: &BigInt) -> BigInt {
+        if x == &self.0 { BigInt::one() } else { BigInt::zero() }
+    }
+}
+
+/// Bit number i of x
+pub struct Bit(pub usize);
+
+impl TableFunction for Bit {
+    fn eval(&self, x: &BigInt) -> BigInt {
+        if x.bit(self.0 as u64) { BigInt::one() } else { BigInt::zero() }
+    }
+}
+
+/// 1 when low <= x < high, 0 otherwise
+pub struct InRange {
+    pub low: BigInt,
+    pub high: BigInt,
+}
+
+impl TableFunction for InRange {
+    fn eval(&self, x: &BigInt) -> BigInt {
+        if &self.low <= x && x < &self.high { BigInt::one() } else { BigInt::zero() }
+    }
+}
+
+/// Table entry v of f for the mask s is f(v - s mod M)
+struct Masked<'a, T: TableFunction> {
+    function: &'a T,
+    modulo: BigInt,
+}
+
+impl<T: TableFunction> ProcessingFunction for Masked<'_, T> {
+    fn apply(&self, value: &BigInt, secret: &BigInt) -> BigInt {
+        self.function.eval(&(value - secret).mod_floor(&self.modulo))
+    }
+}
+
+/// Lookup gate for inputs shared mod 2^input_bits and outputs shared mod 2^output_bits
+#[derive(Clone, Debug, PartialEq)]
+pub struct LookupGate {
+    pub num_parties: usize,
+    pub input_bits: usize,
+    pub output_bits: usize,
+}
+
+/// What a party holds for one evaluation of the gate
+#[derive(Clone, Debug, PartialEq)]
+pub struct LookupGateShare {
+    pub mask: BigInt,
+    pub table: DVector<BigInt>,
+}
+
+impl LookupGate {
+    pub fn new(num_parties: usize, input_bits: usize, output_bits: usize) -> LookupGate {
+        // The table has 2^input_bits entries
+        assert!(input_bits <= 20, "A table of 2^{} entries is too large", input_bits);
+
+        LookupGate {
+            num_parties,
+            input_bits,
+            output_bits,
+        }
+    }
+
+    fn input_modulo(&self) -> BigInt {
+        BigInt::one() << self.input_bits
+    }
+
+    /// Shares of a fresh mask and of the table of `function`, one per party
+    pub fn preprocess<T: TableFunction>(&self, function: &T) -> Vec<LookupGateShare> {
+        let mut rng = rand::thread_rng();
+        let s = UniformBigInt::new(BigInt::zero(), self.input_modulo()).sample(&mut rng);
+
+        let mask_shares = AdditiveSecretSharing::share(&s, self.num_parties, self.input_bits);
+
+        let masked = Masked {
+            function,
+            modulo: self.input_modulo(),
+        };
+        let gate = PreprocessedGate::build(masked, s, self.num_parties, 1 << self.input_bits, self.output_bits);
+
+        (0..self.num_parties)
+            .map(|i| LookupGateShare {
+                mask: mask_shares[i].clone(),
+                table: gate.get_party_shares(i),
+            })
+            .collect()
+    }
+
+    /// Share of x + s a party broadcasts, for its share of x mod 2^input_bits
+    pub fn mask(&self, share: &LookupGateShare, x_share: &BigInt) -> BigInt {
+        (x_share + &share.mask).mod_floor(&self.input_modulo())
+    }
+
+    /// x + s from the broadcast shares of all parties
+    pub fn open(&self, masked_shares: &[BigInt]) -> usize {
+        assert_eq!(masked_shares.len(), self.num_parties);
+
+        let masked_shares = DVector::from_column_slice(masked_shares);
+        AdditiveSecretSharing::reveal(&masked_shares, self.input_bits).to_usize().unwrap()
+    }
+
+    /// Share of f(x) mod 2^output_bits
+    pub fn lookup(&self, share: &LookupGateShare, opened: usize) -> BigInt {
+        share.table[opened].clone()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::DVector;
+    use num_bigint::BigInt;
+    use num_integer::Integer;
+    use num_traits::One;
+    use crate::mpc::additive_sharing::AdditiveSecretSharing;
+    use crate::mpc::lookup_gate::{Bit, Equals, InRange, LookupGate, TableFunction};
+
+    /// f(x) computed by the parties on shares of x
+    fn evaluate<T: TableFunction>(gate: &LookupGate, function: &T, x: &BigInt) -> BigInt {
+        let preprocessed = gate.preprocess(function);
+        let x_shares = AdditiveSecretSharing::share(x, gate.num_parties, gate.input_bits);
+
+        let masked: Vec<BigInt> = preprocessed.iter().zip(x_shares.iter())
+            .map(|(share, x_share)| gate.mask(share, x_share))
+            .collect();
+        let opened = gate.open(&masked);
+
+        let outputs = DVector::from_iterator(gate.num_parties, preprocessed.iter().map(|share| gate.lookup(share, opened)));
+        AdditiveSecretSharing::reveal(&outputs, gate.output_bits)
+    }
+
+    #[test]
+    fn test_built_in_and_custom_functions() {
+        let gate = LookupGate::new(3, 6, 8);
+        let square = |x: &BigInt| x * x;
+
+        for x in [0, 1, 17, 42, 63] {
+            let x = BigInt::from(x);
+            let bit = |condition: bool| if condition { BigInt::one() } else { BigInt::from(0) };
+
+            assert_eq!(evaluate(&gate, &Equals(BigInt::from(42)), &x), bit(x == BigInt::from(42)));
+            assert_eq!(evaluate(&gate, &Bit(4), &x), bit(x.bit(4)));
+            assert_eq!(evaluate(&gate, &InRange { low: BigInt::from(10), high: BigInt::from(43) }, &x),
+                       bit(BigInt::from(10) <= x && x < BigInt::from(43)));
+            assert_eq!(evaluate(&gate, &square, &x), (&x * &x).mod_floor(&BigInt::from(256)));
+        }
+    }
+}
@@ -1,17 +1,111 @@
 use std::fmt;
 use std::ops::Neg;
-use nalgebra::{DMatrix, DVector, dvector, stack};
+use nalgebra::{DMatrix, DVector};
 use num_bigint::{BigInt, UniformBigInt};
 use num_traits::{One, Zero};
 use num_integer::{Integer};
 use rand::distributions::uniform::UniformSampler;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use crate::mpc::distributions::{ErrorDistribution, KeyDistribution};
+use crate::mpc::public_params::PublicParameters;
+
+
+/// Parameters of an LWE scheme, the distributions are the ones recorded in `PublicParameters`
+#[derive(Clone, Debug, PartialEq)]
+pub struct LweParameters {
+    pub p_exponent: usize,
+    pub q_exponent: usize,
+    pub dimension: usize,
+    pub pk_rows: usize,
+    pub error_distribution: ErrorDistribution,
+    pub key_distribution: KeyDistribution,
+}
+
+impl LweParameters {
+    pub fn new(p_exponent: usize, q_exponent: usize, dimension: usize, pk_rows: usize) -> LweParameters {
+        LweParameters {
+            p_exponent,
+            q_exponent,
+            dimension,
+            pk_rows,
+            error_distribution: ErrorDistribution::wide_uniform(q_exponent - p_exponent),
+            key_distribution: KeyDistribution::default(),
+        }
+    }
+
+    pub fn from_public_parameters(params: &PublicParameters, pk_rows: usize) -> LweParameters {
+        LweParameters {
+            p_exponent: params.m,
+            q_exponent: params.k,
+            dimension: params.lwe_dimension,
+            pk_rows,
+            error_distribution: params.error_distribution.clone(),
+            key_distribution: params.key_distribution.clone(),
+        }
+    }
+
+    /// Fewest public samples a public key may have, (n + 1) * log q. A ciphertext is a random subset sum of them, with
+    /// fewer rows the subset, and so the message, can be solved for from a = A^T * subset
+    pub fn min_public_key_rows(&self) -> usize {
+        (self.dimension + 1) * self.q_exponent
+    }
+
+    /// Bound on the noise of a public-key ciphertext, the errors of all public samples plus its own
+    pub fn noise_bound(&self) -> BigInt {
+        self.error_distribution.bound() * (self.pk_rows + 1)
+    }
+}
+
+
+/// Public LWE samples (A, b = -A * sk + e), anyone holding them encrypts without the key
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LwePublicKey {
+    pub big_a: DMatrix<BigInt>,
+    pub b: DVector<BigInt>,
+
+    pub p_exponent: usize,
+    pub q_exponent: usize,
+
+    /// Distribution of the error of a fresh encryption
+    pub error_distribution: ErrorDistribution,
+}
+
+impl LwePublicKey {
+    /// Random subset sum of the public samples plus the scaled message, refused for keys of fewer than (n + 1) * log q
+    /// samples, whose subset sums do not hide the subset
+    pub fn encrypt(&self, m: &BigInt) -> (DVector<BigInt>, BigInt) {
+        let min_rows = (self.big_a.ncols() + 1) * self.q_exponent;
+        assert!(self.big_a.nrows() >= min_rows,
+                "Public key of {} samples is below the {} subset sums need to hide the message", self.big_a.nrows(), min_rows);
+
+        let mut rng = rand::thread_rng();
+
+        let q = BigInt::one() << self.q_exponent;
+        let q_div_p = BigInt::one() << (self.q_exponent - self.p_exponent);
+
+        // subset of the rows
+        let subset = DVector::from_fn(self.big_a.nrows(), |_, _| BigInt::from(rng.gen_range(0..2u8)));
+
+        let e = self.error_distribution.sample(&mut rng);
+
+        // a = A^T * subset in [0, q)
+        let a = (self.big_a.transpose() * &subset)
+            .map(|x| x.mod_floor(&q));
+
+        // b = <b, subset> + e + (q/p) * m in [0, q)
+        let b = (self.b.dot(&subset) + &e + q_div_p * m).mod_floor(&q);
+
+        (a, b)
+    }
+}
 
 
 pub struct LweScheme {
     pub q: BigInt,
     pub p: BigInt,
     pub sk: DVector<BigInt>,
-    // pub pk: DMatrix<BigInt>,
+    pub pk: LwePublicKey,
 
     pub dimension: usize,
     // pub pk_rows: usize,
@@ -24,28 +118,35 @@ pub struct LweScheme {
 
 impl LweScheme {
     pub fn new(p_exponent: usize, q_exponent: usize, dimension: usize, pk_rows: usize) -> Self {
+        LweScheme::with_parameters(&LweParameters::new(p_exponent, q_exponent, dimension, pk_rows))
+    }
+
+    pub fn with_parameters(params: &LweParameters) -> Self {
+        let (p_exponent, q_exponent, dimension, pk_rows) = (params.p_exponent, params.q_exponent, params.dimension, params.pk_rows);
         assert!(p_exponent <= q_exponent);
 
         let p = BigInt::one() << p_exponent;
         let q = BigInt::one() << q_exponent;
 
+        // Without samples the scheme only encrypts with the key
+        assert!(pk_rows == 0 || pk_rows >= params.min_public_key_rows(),
+                "Public key of {} samples is below the {} subset sums need to hide the message", pk_rows, params.min_public_key_rows());
+
+        // Decryption rounds to the nearest multiple of q/p
+        assert!(params.noise_bound() < &q / (&p * 2),
+                "Noise of a public-key ciphertext reaches {}, decryption tolerates less than q/2p", params.noise_bound());
+
         let mut rng = rand::thread_rng();
 
-        // s random elements in [0, q) of size n
-        let sk = DVector::from_fn(dimension, |_i, _| {
-            UniformBigInt::new(BigInt::zero(), &q).sample(&mut rng)
-        });
+        // s in [0, q) of size n
+        let sk = params.key_distribution.sample(dimension, &q, &mut rng);
 
         // A random elements in [0, q) of size N x n
         let big_a = DMatrix::from_fn(pk_rows, dimension, |_, _| {
             UniformBigInt::new(BigInt::zero(), &q).sample(&mut rng)
         });
 
-        let q_div_2p: BigInt = &q / (&p * 2);
-        // random elements |e| < q/2p
-        let  e = DVector::from_fn(pk_rows, |_, _| {
-            UniformBigInt::new_inclusive((&q_div_2p).neg(), &q_div_2p).sample(&mut rng)
-        });
+        let  e = DVector::from_fn(pk_rows, |_, _| params.error_distribution.sample(&mut rng));
 
 
 
@@ -53,19 +154,22 @@ impl LweScheme {
 
         // b = -(A * s) + e in [0, q)
         let b = (-(&big_a * &sk) + &e)
-            .map(|x| x.mod_floor(&p));
-
+            .map(|x| x.mod_floor(&q));
 
 
-
-
-        let _pk = stack![big_a, b];
+        let pk = LwePublicKey {
+            big_a,
+            b,
+            p_exponent,
+            q_exponent,
+            error_distribution: params.error_distribution.clone(),
+        };
 
         LweScheme {
             q,
             p,
             sk,
-            // pk,
+            pk,
             dimension,
             // pk_rows,
             p_exponent,
@@ -76,8 +180,7 @@ impl LweScheme {
     pub fn encrypt(&self, m: &BigInt) -> (DVector<BigInt>, BigInt) {
         let mut rng = rand::thread_rng();
 
-        let q_div_2p: BigInt = &self.q / (&self.p * 2);
-        let e = UniformBigInt::new_inclusive(BigInt::zero(), &q_div_2p).sample(&mut rng);
+        let e = self.pk.error_distribution.sample(&mut rng);
 
         // a random elements in [0, q)
         let a = DVector::from_fn(self.dimension, |_, _| {
@@ -88,27 +191,32 @@ impl LweScheme {
         let m_scaled = (&self.q / &self.p) * m;
 
         // b = (-<a,sk> + e + (q/p) * m) in [0, q)
-        let b = ((&a).dot(&self.sk).neg() + &e + &m_scaled).mod_floor(&self.q);
+        let b = (a.dot(&self.sk).neg() + &e + &m_scaled).mod_floor(&self.q);
 
         (a, b)
     }
 
+    pub fn encrypt_with_public_key(&self, m: &BigInt) -> (DVector<BigInt>, BigInt) {
+        self.pk.encrypt(m)
+    }
+
     pub fn decrypt(&self, a: &DVector<BigInt>, b: &BigInt) -> BigInt {
         // c = (a, b)
-        let c = stack![a.clone(); dvector![b.clone()]];
+        let c = a.clone().push(b.clone());
 
         // s = (sk, 1)
-        let s = stack![self.sk; dvector![BigInt::one()]];
+        let s = self.sk.clone().push(BigInt::one());
 
         // m1 = <(a,b),(sk,1)> in [0,q)
-        let mut m = (&c).dot(&s).mod_floor(&self.q);
+        let mut m = c.dot(&s).mod_floor(&self.q);
 
         // m2 = m1 + q/2p in [0,q)
-        m = m + (&self.q / (&self.p * BigInt::from(2))).mod_floor(&self.q);
+        m += (&self.q / (&self.p * BigInt::from(2))).mod_floor(&self.q);
 
-        m = m >> (self.q_exponent - self.p_exponent);
+        m >>= self.q_exponent - self.p_exponent;
 
-        m
+        // a negative error close to q wraps m around to p
+        m.mod_floor(&self.p)
     }
 }
 
@@ -135,10 +243,14 @@ impl fmt::Display for LweScheme {
 
 
 pub fn init_lwe_with_random_ptxt(p_exponent: usize, q_exponent: usize, dimension: usize, pk_rows: usize) -> (LweScheme, BigInt, DVector<BigInt>, BigInt, ) {
-    let scheme = LweScheme::new(p_exponent, q_exponent, dimension, pk_rows);
+    init_lwe_with_parameters(&LweParameters::new(p_exponent, q_exponent, dimension, pk_rows))
+}
+
+pub fn init_lwe_with_parameters(params: &LweParameters) -> (LweScheme, BigInt, DVector<BigInt>, BigInt, ) {
+    let scheme = LweScheme::with_parameters(params);
     let mut rng = rand::thread_rng();
 
-    let ptxt = UniformBigInt::new(&BigInt::zero(), &BigInt::one() << p_exponent).sample(&mut rng);
+    let ptxt = UniformBigInt::new(BigInt::zero(), &BigInt::one() << params.p_exponent).sample(&mut rng);
 
     let (a, b) = scheme.encrypt(&ptxt);
 
@@ -159,11 +271,11 @@ mod tests {
 
     #[test]
     fn test() {
-        loop {
+        for _ in 0..16 {
             let q_exponent = 32;
             let p_exponent = 1;
             let dimension = 1024;
-            let pk_rows = 1;
+            let pk_rows = 0;
 
             let lwe = LweScheme::new(p_exponent, q_exponent, dimension, pk_rows);
 
@@ -185,4 +297,77 @@ mod tests {
             }
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_public_key_encryption() {
+        let (p_exponent, q_exponent, dimension, pk_rows) = (2, 32, 64, 65 * 32);
+        let lwe = LweScheme::new(p_exponent, q_exponent, dimension, pk_rows);
+
+        for _ in 0..16 {
+            for m in 0..(1 << p_exponent) {
+                let m_in = BigInt::from(m);
+                let (a, b) = lwe.encrypt_with_public_key(&m_in);
+
+                assert_eq!(lwe.decrypt(&a, &b), m_in);
+            }
+        }
+    }
+
+    #[test]
+    fn test_public_key_serialization() {
+        let lwe = LweScheme::new(1, 32, 16, 17 * 32);
+
+        let bytes = bitcode::serialize(&lwe.pk).unwrap();
+        let pk: LwePublicKey = bitcode::deserialize(&bytes).unwrap();
+        assert_eq!(pk, lwe.pk);
+
+        let (a, b) = pk.encrypt(&BigInt::one());
+        assert_eq!(lwe.decrypt(&a, &b), BigInt::one());
+    }
+
+    #[test]
+    fn test_selectable_distributions() {
+        let errors = [
+            ErrorDistribution::Uniform { bound: BigInt::from(1000) },
+            ErrorDistribution::RoundedGaussian { sigma: 3.2 },
+            ErrorDistribution::CenteredBinomial { eta: 4 },
+            ErrorDistribution::Ternary,
+        ];
+        let keys = [KeyDistribution::Uniform, KeyDistribution::Binary, KeyDistribution::Ternary, KeyDistribution::FixedHammingWeight(8)];
+
+        for (error_distribution, key_distribution) in errors.into_iter().zip(keys) {
+            let params = LweParameters {
+                error_distribution,
+                key_distribution,
+                ..LweParameters::new(2, 32, 64, 65 * 32)
+            };
+            let lwe = LweScheme::with_parameters(&params);
+
+            for m in 0..(1 << params.p_exponent) {
+                let m_in = BigInt::from(m);
+
+                let (a, b) = lwe.encrypt(&m_in);
+                assert_eq!(lwe.decrypt(&a, &b), m_in);
+
+                let (a, b) = lwe.encrypt_with_public_key(&m_in);
+                assert_eq!(lwe.decrypt(&a, &b), m_in);
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "decryption tolerates less than q/2p")]
+    fn test_too_wide_error_is_refused() {
+        let params = LweParameters {
+            error_distribution: ErrorDistribution::Uniform { bound: BigInt::one() << 24 },
+            ..LweParameters::new(2, 32, 16, 17 * 32)
+        };
+        LweScheme::with_parameters(&params);
+    }
+
+    #[test]
+    #[should_panic(expected = "subset sums need to hide the message")]
+    fn test_short_public_key_is_refused() {
+        LweScheme::new(2, 32, 64, 128);
+    }
+}
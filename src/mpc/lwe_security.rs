@@ -0,0 +1,249 @@
+use std::f64::consts::{E, PI};
+use num_bigint::BigInt;
+use num_traits::One;
+use crate::mpc::distributions::KeyDistribution;
+use crate::mpc::lwe_scheme::LweParameters;
+use crate::mpc::public_params::PublicParameters;
+
+// Bit security of LWE against the two standard lattice attacks, in the core-SVP cost model: BKZ with block size
+// beta costs one SVP call in dimension beta, 2^(0.292 beta) operations classically. Polynomial factors and the
+// number of BKZ tours are ignored, which makes the estimates lower than those of the lattice estimator.
+//
+//  - Primal: the error is an unusually short vector of an embedding lattice of dimension d, BKZ-beta finds it once
+//    sigma * sqrt(beta) <= delta^(2 beta - d - 1) * vol^(1/d) [ADPS16].
+//  - Dual: a vector v of length l = delta^m * q^(n/m) of the dual lattice distinguishes <v, e> from uniform with
+//    advantage exp(-pi * (l * alpha)^2), alpha = sqrt(2 pi) * sigma / q; a sieve gives 2^(0.2075 beta) such vectors
+//    per call and about 1 / advantage^2 are needed. The key is treated as uniform.
+//
+// Both are minimized over the block size and the number of samples m.
+
+/// log2 of the cost of BKZ with block size beta is CORE_SVP_EXPONENT * beta
+pub const CORE_SVP_EXPONENT: f64 = 0.292;
+
+/// A sieve in dimension beta outputs about 2^(SIEVE_OUTPUT_EXPONENT * beta) short vectors
+const SIEVE_OUTPUT_EXPONENT: f64 = 0.2075;
+
+/// The root Hermite factor formula only holds for larger block sizes
+const MIN_BLOCK_SIZE: usize = 40;
+
+/// An attacker gets at most this many samples per coordinate of the key
+const MAX_SAMPLES_PER_DIMENSION: usize = 3;
+
+/// Security of parameters of the homomorphic encryption standard (2018) for errors of sigma = 3.2, classical attacks
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReferencePoint {
+    pub dimension: usize,
+    /// Largest log2 q with this security
+    pub log_q: usize,
+    pub key_distribution: KeyDistribution,
+    pub security_bits: usize,
+}
+
+const fn reference(dimension: usize, log_q: usize, key_distribution: KeyDistribution, security_bits: usize) -> ReferencePoint {
+    ReferencePoint { dimension, log_q, key_distribution, security_bits }
+}
+
+pub const REFERENCE_POINTS: [ReferencePoint; 24] = [
+    reference(1024, 29, KeyDistribution::Uniform, 128),
+    reference(2048, 56, KeyDistribution::Uniform, 128),
+    reference(4096, 111, KeyDistribution::Uniform, 128),
+    reference(8192, 220, KeyDistribution::Uniform, 128),
+    reference(1024, 21, KeyDistribution::Uniform, 192),
+    reference(2048, 39, KeyDistribution::Uniform, 192),
+    reference(4096, 77, KeyDistribution::Uniform, 192),
+    reference(8192, 154, KeyDistribution::Uniform, 192),
+    reference(1024, 16, KeyDistribution::Uniform, 256),
+    reference(2048, 31, KeyDistribution::Uniform, 256),
+    reference(4096, 60, KeyDistribution::Uniform, 256),
+    reference(8192, 120, KeyDistribution::Uniform, 256),
+    reference(1024, 27, KeyDistribution::Ternary, 128),
+    reference(2048, 54, KeyDistribution::Ternary, 128),
+    reference(4096, 109, KeyDistribution::Ternary, 128),
+    reference(8192, 218, KeyDistribution::Ternary, 128),
+    reference(1024, 19, KeyDistribution::Ternary, 192),
+    reference(2048, 37, KeyDistribution::Ternary, 192),
+    reference(4096, 75, KeyDistribution::Ternary, 192),
+    reference(8192, 152, KeyDistribution::Ternary, 192),
+    reference(1024, 14, KeyDistribution::Ternary, 256),
+    reference(2048, 29, KeyDistribution::Ternary, 256),
+    reference(4096, 58, KeyDistribution::Ternary, 256),
+    reference(8192, 118, KeyDistribution::Ternary, 256),
+];
+
+/// Smallest reference dimension with at least `security_bits` for a modulus of `log_q` bits and this key
+pub fn reference_dimension(log_q: usize, key_distribution: &KeyDistribution, security_bits: usize) -> Option<usize> {
+    REFERENCE_POINTS.iter()
+        .filter(|point| &point.key_distribution == key_distribution && point.security_bits >= security_bits && point.log_q >= log_q)
+        .map(|point| point.dimension)
+        .min()
+}
+
+/// What the attacks see of an LWE configuration
+#[derive(Clone, Debug, PartialEq)]
+pub struct LweInstance {
+    pub dimension: usize,
+    pub log_q: usize,
+    pub error_sigma: f64,
+    /// None for a key uniform mod q
+    pub key_sigma: Option<f64>,
+}
+
+impl LweInstance {
+    pub fn from_public_parameters(params: &PublicParameters) -> LweInstance {
+        LweInstance::from_lwe_parameters(&LweParameters::from_public_parameters(params, 0))
+    }
+
+    pub fn from_lwe_parameters(params: &LweParameters) -> LweInstance {
+        let q = BigInt::one() << params.q_exponent;
+
+        LweInstance {
+            dimension: params.dimension,
+            log_q: params.q_exponent,
+            error_sigma: params.error_distribution.standard_deviation(),
+            key_sigma: match params.key_distribution {
+                KeyDistribution::Uniform => None,
+                ref key_distribution => Some(key_distribution.standard_deviation(params.dimension, &q)),
+            },
+        }
+    }
+}
+
+/// Cheapest way found to run one attack
+#[derive(Clone, Debug, PartialEq)]
+pub struct AttackCost {
+    /// log2 of the operations
+    pub bits: f64,
+    pub block_size: usize,
+    pub samples: usize,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SecurityEstimate {
+    pub primal: AttackCost,
+    pub dual: AttackCost,
+}
+
+impl SecurityEstimate {
+    /// Bits of security, the cost of the cheaper attack
+    pub fn bits(&self) -> f64 {
+        self.primal.bits.min(self.dual.bits)
+    }
+}
+
+/// log2 of the root Hermite factor delta reached by BKZ with block size beta
+fn log2_root_hermite_factor(beta: usize) -> f64 {
+    let beta = beta as f64;
+    ((PI * beta).powf(1.0 / beta) * beta / (2.0 * PI * E)).log2() / (2.0 * (beta - 1.0))
+}
+
+fn max_samples(instance: &LweInstance) -> usize {
+    MAX_SAMPLES_PER_DIMENSION * instance.dimension
+}
+
+/// Samples are tried in about 256 steps
+fn sample_step(instance: &LweInstance) -> usize {
+    (max_samples(instance) / 256).max(1)
+}
+
+/// uSVP attack on the embedding of m samples, with the key scaled up to the error when it is small
+pub fn primal_attack(instance: &LweInstance) -> AttackCost {
+    let n = instance.dimension as f64;
+    let log_q = instance.log_q as f64;
+    let log_sigma = instance.error_sigma.log2();
+
+    // dimension and log2 volume of the lattice for m samples
+    let lattice = |m: usize| -> Option<(usize, f64)> {
+        match instance.key_sigma {
+            // Only the error is short, the q-ary lattice of A has dimension m and volume q^(m - n)
+            None if m > instance.dimension => Some((m + 1, (m as f64 - n) * log_q)),
+            None => None,
+            Some(key_sigma) => {
+                let scale = (instance.error_sigma / key_sigma).max(1.0);
+                Some((m + instance.dimension + 1, m as f64 * log_q + n * scale.log2()))
+            }
+        }
+    };
+
+    let max_block_size = max_samples(instance) + instance.dimension + 1;
+    for beta in MIN_BLOCK_SIZE..=max_block_size {
+        let log_delta = log2_root_hermite_factor(beta);
+        let lhs = log_sigma + 0.5 * (beta as f64).log2();
+
+        let samples = (1..=max_samples(instance)).step_by(sample_step(instance)).find(|&m| {
+            lattice(m).is_some_and(|(d, log_volume)| {
+                beta <= d && lhs <= (2.0 * beta as f64 - d as f64 - 1.0) * log_delta + log_volume / d as f64
+            })
+        });
+
+        if let Some(samples) = samples {
+            return AttackCost { bits: CORE_SVP_EXPONENT * beta as f64, block_size: beta, samples };
+        }
+    }
+
+    AttackCost { bits: CORE_SVP_EXPONENT * max_block_size as f64, block_size: max_block_size, samples: max_samples(instance) }
+}
+
+/// Distinguishing attack with short vectors of the dual lattice of m samples
+pub fn dual_attack(instance: &LweInstance) -> AttackCost {
+    let n = instance.dimension as f64;
+    let log_q = instance.log_q as f64;
+    let log_alpha = (instance.error_sigma * (2.0 * PI).sqrt()).log2() - log_q;
+
+    (MIN_BLOCK_SIZE..=max_samples(instance))
+        .map(|beta| {
+            let log_delta = log2_root_hermite_factor(beta);
+
+            // m minimizing m * log delta + n * log q / m, at least the block size
+            let samples = ((n * log_q / log_delta).sqrt() as usize).clamp(beta, max_samples(instance));
+            let log_length = samples as f64 * log_delta + n * log_q / samples as f64;
+
+            // log2 of the advantage exp(-pi * (l * alpha)^2)
+            let log_advantage = -PI * (2.0 * (log_length + log_alpha)).exp2() * E.log2();
+            let repetitions = (-2.0 * log_advantage - SIEVE_OUTPUT_EXPONENT * beta as f64).max(0.0);
+
+            AttackCost { bits: CORE_SVP_EXPONENT * beta as f64 + repetitions, block_size: beta, samples }
+        })
+        .min_by(|x, y| x.bits.total_cmp(&y.bits))
+        .unwrap()
+}
+
+pub fn estimate(instance: &LweInstance) -> SecurityEstimate {
+    SecurityEstimate {
+        primal: primal_attack(instance),
+        dual: dual_attack(instance),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::mpc::distributions::KeyDistribution;
+    use crate::mpc::lwe_security::{estimate, reference_dimension, LweInstance, REFERENCE_POINTS};
+
+    #[test]
+    fn test_reference_points() {
+        for point in REFERENCE_POINTS.iter() {
+            let instance = LweInstance {
+                dimension: point.dimension,
+                log_q: point.log_q,
+                error_sigma: 3.2,
+                key_sigma: match point.key_distribution {
+                    KeyDistribution::Uniform => None,
+                    _ => Some((2.0f64 / 3.0).sqrt()),
+                },
+            };
+            // Core-SVP leaves out the factors the standard counts, but stays within 30% of it
+            let bits = estimate(&instance).bits();
+            let reference = point.security_bits as f64;
+            assert!(bits <= reference && bits >= 0.7 * reference, "{:?} estimated at {:.1} bits", point, bits);
+
+            // One more bit of modulus makes both attacks cheaper
+            let wider = LweInstance { log_q: point.log_q + 1, ..instance.clone() };
+            assert!(estimate(&wider).bits() <= bits);
+        }
+
+        assert_eq!(reference_dimension(50, &KeyDistribution::Ternary, 128), Some(2048));
+        assert_eq!(reference_dimension(64, &KeyDistribution::Uniform, 256), Some(8192));
+        assert_eq!(reference_dimension(300, &KeyDistribution::Uniform, 128), None);
+    }
+}
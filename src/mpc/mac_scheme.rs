@@ -3,7 +3,11 @@ use num_bigint::{BigInt, UniformBigInt};
 use num_integer::Integer;
 use num_traits::{One, Zero};
 use rand::distributions::uniform::UniformSampler;
+use sha2::{Digest, Sha256};
 use crate::mpc::additive_sharing::AdditiveSecretSharing;
+use crate::mpc::ring::RingElement;
+
+use bitcode::serialize;
 
 
 #[derive(Default, Clone, Debug, PartialEq)]
@@ -135,14 +139,14 @@ impl AuthenticatedSharingScheme {
 
 
 
-        let y_tilde = (&chi_vals).dot(&x_tilde_shares).mod_floor(&self.params.big_ks);
+        let y_tilde = chi_vals.dot(x_tilde_shares).mod_floor(&self.params.big_ks);
 
 
 
         let combined_mac_shares = DVector::from_fn(self.params.n, |i,_| {
             let player_mac_shares = m_tilde_collection.row(i).transpose();
 
-            (&chi_vals).dot(&player_mac_shares).mod_floor(&self.params.big_ks)
+            chi_vals.dot(&player_mac_shares).mod_floor(&self.params.big_ks)
         });
 
         let z_shares = DVector::from_fn(self.params.n, |i, _| {
@@ -179,14 +183,10 @@ impl AuthenticatedSharingScheme {
 
 
         // [y] = [x + 2^k * r]
-        let opened_secrets = DVector::from_fn(self.params.n, |i, _| {
+        DVector::from_fn(self.params.n, |i, _| {
             let y = &x_shares[i] + (&self.params.big_k * &r_shares[i]);
             y.mod_floor(&self.params.big_ks)
-        });
-
-
-
-        opened_secrets
+        })
     }
 
 
@@ -221,6 +221,28 @@ impl AuthenticatedSharingScheme {
     }
 }
 
+/// Additive shares mod 2^bits of the MAC alpha * x of a value x, which is shared on its own
+pub fn mac_shares(x: &BigInt, alpha: &BigInt, num_parties: usize, bits: usize) -> DVector<BigInt> {
+    AdditiveSecretSharing::share(&(alpha * x), num_parties, bits)
+}
+
+/// This party's check value mac_i - alpha_i * x + pad_i of an opened x. The values of all parties add up to
+/// alpha * x' - alpha * x mod 2^bits for the x' the MAC shares belong to, which is zero only if x was opened correctly
+/// or with probability 2^-s. The pads are shares of zero, without them the check values would reveal alpha_i
+pub fn check_value<R: RingElement>(x: &R, mac: &R, alpha_share: &R, pad: &R, bits: usize) -> R {
+    (mac.clone() - alpha_share.clone() * x.clone() + pad.clone()).reduce(bits)
+}
+
+/// Commitment to the check values of a party. A party that saw the others' check values first could pick its own
+/// to make the sum zero, so all parties commit before any of them opens
+pub fn commit_check_values<R: RingElement>(party_number: usize, check_values: &DVector<R>, nonce: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update((party_number as u64).to_le_bytes());
+    hasher.update(serialize(check_values).unwrap());
+    hasher.update(nonce);
+    hasher.finalize().into()
+}
+
 
 #[cfg(test)]
 mod tests {
@@ -254,7 +276,7 @@ mod tests {
 
         let mut x_shares_collection = Vec::new();
         let mut x_values = Vec::new();
-        for i in 0..t {
+        for _i in 0..t {
             // k bits
             let x = UniformBigInt::new(BigInt::zero(), &BigInt::one() << k).sample(&mut rng);
 
@@ -1,32 +1,26 @@
-use std::time::Instant;
-use nalgebra::{DMatrix, DVector};
-use num_bigint::{BigInt, UniformBigInt};
-use num_traits::Zero;
-use rand::distributions::uniform::UniformSampler;
-use threshold_decryption::mpc::mac_scheme::{AuthenticatedSharingScheme, MACSchemeParams};
-use threshold_decryption::mpc::protocol::Protocol;
+use std::collections::BTreeSet;
+use std::time::{Duration, Instant};
+use nalgebra::DVector;
+use num_bigint::BigInt;
+use threshold_decryption::mpc::additive_sharing::AdditiveSecretSharing;
+use threshold_decryption::mpc::batch_decryption::BatchDecryption;
 use threshold_decryption::mpc::public_params::PublicParameters;
 use threshold_decryption::mpc::lwe_scheme::init_lwe_with_random_ptxt;
+use threshold_decryption::mpc::ring::{RingElement, Z128};
 
-fn decrypt_with_macs(mut protocol: Protocol, mac_scheme: AuthenticatedSharingScheme, a: DVector<BigInt>, b: BigInt, ptxt: BigInt, alpha_shares: DVector<BigInt>) {
+/// Parameters estimated below this many bits of LWE security are benchmarked with a warning
+const WARN_SECURITY_BITS: usize = 128;
 
-    let out = protocol.decrypt(a, b);
+/// Online phase of the decryption with MACs by parties computing in R, the dealing is not timed
+fn decrypt_with_macs<R: RingElement>(params: &PublicParameters, sk_shares: &[DVector<BigInt>], a: DVector<BigInt>, b: BigInt, ptxt: BigInt) -> Duration {
+    let mut batch = BatchDecryption::<R>::new(params, sk_shares, &[(a, b)]);
 
-    assert_eq!(out, ptxt);
+    let start = Instant::now();
+    let out = batch.run().unwrap();
+    let duration = start.elapsed();
 
-    let x_shares_collection = DMatrix::from_fn(protocol.params.n, 3,|i, j| {
-        match j {
-            0 => protocol.parties[i].get_z_prime().clone(),
-            1 => protocol.parties[i].get_y_prime().clone(),
-            2 => protocol.parties[i].get_o_prime().clone(),
-            _ => BigInt::zero() // error
-        }
-    });
-
-    let (x_tilde_shares, m_tilde_collection) = mac_scheme.batch_open(&x_shares_collection);
-
-    let checked_values = mac_scheme.batch_check(&x_tilde_shares, &alpha_shares, &m_tilde_collection);
-    assert!(checked_values.is_some());
+    assert_eq!(out, vec![ptxt]);
+    duration
 }
 
 fn main() {
@@ -40,7 +34,7 @@ fn main() {
                 // Protocol: "Digit" bit length
                 for protocol_b in 5..9 {
                     // MACs: security parameter
-                    for mac_s in [80] {
+                    for mac_s in [64, 80] {
                         // LWE: sample length
                         for lwe_a_len in [777, 870, 1024] {
                             bench_tuples.push((protocol_n, protocol_k, protocol_m, protocol_b, mac_s, lwe_a_len));
@@ -51,45 +45,58 @@ fn main() {
         }
     }
 
+    // Every combination is checked before the first one runs
+    let mut bench_params = Vec::new();
+    // The estimate only depends on k and the LWE dimension, each pair is warned about once
+    let mut warned = BTreeSet::new();
     for (protocol_n, protocol_k, protocol_m, protocol_b, mac_s, lwe_a_len) in bench_tuples {
-        let params = PublicParameters::init(protocol_n, protocol_k, protocol_m, protocol_b, lwe_a_len, mac_s);
-
-        let mut rng = rand::thread_rng();
-
-        let (lwe_scheme, ptxt, lwe_a, lwe_b, ) = init_lwe_with_random_ptxt(params.m, params.k, lwe_a_len, 1);
-
-        let protocol_s = UniformBigInt::new(&BigInt::zero(), &BigInt::from(params.big_d)).sample(&mut rng);
-
-        let protocol_r = UniformBigInt::new(&BigInt::zero(), &params.big_l).sample(&mut rng);
-
-        let mac_alpha = UniformBigInt::new(&BigInt::zero(), &params.big_l).sample(&mut rng);
-
-        let mac_scheme_params = MACSchemeParams::init(protocol_n, protocol_k, mac_s, 3);
-
-        let mac_scheme = AuthenticatedSharingScheme::new(mac_alpha, mac_scheme_params);
-
-        let mut protocol = Protocol::new(&params);
-
-        let mac_alpha_shares = mac_scheme.share_global_key();
-
-        protocol.preprocess(protocol_s, protocol_r);
+        let params = PublicParameters::builder()
+            .with_parties(protocol_n)
+            .with_ciphertext_bits(protocol_k)
+            .with_plaintext_bits(protocol_m)
+            .with_digit_bits(protocol_b)
+            .with_lwe_dimension(lwe_a_len)
+            .with_mac_security(mac_s)
+            .build();
+
+        match params {
+            Ok(params) => {
+                let estimated = params.security_estimate().bits();
+                if estimated < WARN_SECURITY_BITS as f64 && warned.insert((protocol_k, lwe_a_len)) {
+                    eprintln!("Warning: (Protocol k = {}) (LWE a = {}) has about {:.1} bits of LWE security, below {}",
+                              protocol_k, lwe_a_len, estimated, WARN_SECURITY_BITS);
+                }
+                bench_params.push(params)
+            },
+            Err(err) => {
+                eprintln!("Invalid parameters (Protocol n = {}, k = {}, m = {}, b = {}) (MACs s = {}) (LWE a = {}): {}",
+                          protocol_n, protocol_k, protocol_m, protocol_b, mac_s, lwe_a_len, err);
+                return;
+            }
+        }
+    }
 
-        protocol.share_sk(lwe_scheme.sk);
+    for params in bench_params {
+        let (protocol_n, protocol_k, protocol_m, protocol_b, mac_s, lwe_a_len) =
+            (params.n, params.k, params.m, params.b, params.mac_s, params.lwe_dimension);
 
-        let start = Instant::now();
+        let (lwe_scheme, ptxt, lwe_a, lwe_b, ) = init_lwe_with_random_ptxt(params.m, params.k, lwe_a_len, 0);
 
-        decrypt_with_macs(protocol,
-                          mac_scheme,
-                          lwe_a,
-                          lwe_b,
-                          ptxt,
-                          mac_alpha_shares);
+        let sk_shares: Vec<DVector<BigInt>> = {
+            let columns: Vec<_> = lwe_scheme.sk.iter().map(|x| AdditiveSecretSharing::share(x, params.n, params.mac_ks)).collect();
+            (0..params.n).map(|i| DVector::from_fn(lwe_a_len, |j, _| columns[j][i].clone())).collect()
+        };
 
-        let duration = start.elapsed();
+        // BigInt only when the MAC ring is wider than u128
+        let (ring, duration) = if Z128::fits(params.mac_ks) {
+            ("u128", decrypt_with_macs::<Z128>(&params, &sk_shares, lwe_a, lwe_b, ptxt))
+        } else {
+            ("bigint", decrypt_with_macs::<BigInt>(&params, &sk_shares, lwe_a, lwe_b, ptxt))
+        };
 
         let microseconds = duration.as_micros();
 
-        println!("(Protocol n = {}, k = {}, m = {}, b = {}) (MACs s = {}) (LWE a = {}): {} microseconds",
-                 protocol_n, protocol_k, protocol_m, protocol_b, mac_s, lwe_a_len, microseconds);
+        println!("(Protocol n = {}, k = {}, m = {}, b = {}) (MACs s = {}) (LWE a = {}) (ring {}): {} microseconds",
+                 protocol_n, protocol_k, protocol_m, protocol_b, mac_s, lwe_a_len, ring, microseconds);
     }
-}
\ No newline at end of file
+}
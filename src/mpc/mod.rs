@@ -4,12 +4,31 @@ pub mod mac_scheme;
 pub mod protocol;
 pub mod utils;
 pub mod additive_sharing;
+pub mod secret_sharing;
+pub mod galois_ring;
+pub mod shamir_sharing;
+pub mod key_refresh;
+pub mod resharing;
+pub mod key_generation;
 pub mod lwe_scheme;
+pub mod glwe_scheme;
+pub mod batch_decryption;
+pub mod output_recipient;
+pub mod noise_flooding;
+pub mod noise_check;
+pub mod lwe_security;
+pub mod cost_model;
+pub mod decryption_failure;
+pub mod ring;
+pub mod prss;
+pub mod distributions;
 pub mod public_params;
 pub mod preprocessed_gate;
+pub mod lookup_gate;
 pub mod base_decomposition;
 
 pub mod preprocessing;
+pub mod distributed_preprocessing;
 
 
 
@@ -0,0 +1,77 @@
+use nalgebra::DVector;
+use num_bigint::{BigInt, UniformBigInt};
+use num_integer::Integer;
+use num_traits::{One, Zero};
+use rand::distributions::uniform::UniformSampler;
+use crate::mpc::additive_sharing::AdditiveSecretSharing;
+use crate::mpc::preprocessed_gate::{LessThanZeroFunction, PreprocessedGate};
+use crate::mpc::public_params::PublicParameters;
+
+// Test that the noise e of a ciphertext lies in [-max_noise, max_noise), without revealing e.
+//
+// After the mod L step the parties hold e0 = z mod L = e + L/2 with z' = e0 + r mod L public. With the margin
+// M = L/2 - max_noise, the noise is out of bounds iff e0 lies in the cyclic interval [-M, M), i.e. iff r lies in the
+// cyclic interval (z' - M, z' + M] mod L. For x_1 = z' - M and x_2 = z' + M mod L that is
+//     [x_1 < r] - [x_2 < r] + [x_1 > x_2],
+// two comparisons of public values with r as in the decryption, each with a fresh LTZ gate so that the opened y'
+// stays masked. Only the resulting bit is opened.
+//
+// Noise beyond L/2 + M wraps into the margin of another message and cannot be told apart from a valid ciphertext,
+// the check catches noise that left the margin before it got that far.
+
+/// Preprocessing of one noise check, two LTZ gates
+#[derive(Clone, Debug, PartialEq)]
+pub struct NoiseCheck {
+    pub max_noise: BigInt,
+
+    /// masks[j][i] is the share of party i of the mask s of comparison j
+    masks: Vec<DVector<BigInt>>,
+
+    /// tables[j][i] is the share of party i of the LTZ table of comparison j
+    tables: Vec<Vec<DVector<BigInt>>>,
+}
+
+impl NoiseCheck {
+    /// Deals the gates of a check that accepts noise in [-max_noise, max_noise)
+    pub fn deal(params: &PublicParameters, max_noise: &BigInt) -> NoiseCheck {
+        assert!(max_noise > &BigInt::zero() && max_noise <= &(&params.big_l >> 1),
+                "The noise bound must lie in (0, L/2], got {}", max_noise);
+
+        let mut rng = rand::thread_rng();
+        let big_d = BigInt::from(params.big_d);
+
+        let (masks, tables) = (0..2)
+            .map(|_| {
+                let s = UniformBigInt::new(BigInt::zero(), &big_d).sample(&mut rng);
+                let s_shares = AdditiveSecretSharing::share(&s, params.n, params.d + 1);
+
+                let ltz_gate = PreprocessedGate::build(LessThanZeroFunction { modulo: big_d.clone() },
+                    s, params.n, params.big_d, params.m);
+
+                (s_shares, (0..params.n).map(|i| ltz_gate.get_party_shares(i)).collect())
+            })
+            .unzip();
+
+        NoiseCheck {
+            max_noise: max_noise.clone(),
+            masks,
+            tables,
+        }
+    }
+
+    /// s and LTZ table shares of party i for comparison j
+    pub fn gate(&self, j: usize, i: usize) -> (BigInt, DVector<BigInt>) {
+        (self.masks[j][i].clone(), self.tables[j][i].clone())
+    }
+
+    /// The public x_1, x_2 compared with r, and the correction [x_1 > x_2]
+    pub fn comparison_points(&self, params: &PublicParameters, z_prime: &BigInt) -> (BigInt, BigInt, BigInt) {
+        let margin: BigInt = (&params.big_l >> 1) - &self.max_noise;
+
+        let x_1 = (z_prime - &margin).mod_floor(&params.big_l);
+        let x_2 = (z_prime + &margin).mod_floor(&params.big_l);
+        let wraps = if x_1 > x_2 { BigInt::one() } else { BigInt::zero() };
+
+        (x_1, x_2, wraps)
+    }
+}
@@ -0,0 +1,221 @@
+use nalgebra::DVector;
+use num_bigint::{BigInt, UniformBigInt};
+use num_integer::Integer;
+use num_traits::One;
+use rand::distributions::uniform::UniformSampler;
+use crate::mpc::additive_sharing::AdditiveSecretSharing;
+use crate::mpc::party::{DecryptionParty, ProtocolAbort};
+use crate::mpc::public_params::PublicParameters;
+use crate::mpc::utils::round_div;
+use crate::network::ProtocolTransferredData;
+
+use bitcode::{serialize, deserialize};
+use crate::generate_getters_and_setters;
+use paste::paste;
+
+// Classical threshold decryption with noise flooding, for comparison with the protocol of `Party`.
+//
+// Every party publishes the partial decryption <a, sk_i> + e_i, where e_i is uniform in [-2^w, 2^w] and hides the
+// key share as long as 2^w is far above the noise of the ciphertext. Then b + sum_i (<a, sk_i> + e_i) is the phase
+// with the extra noise sum_i e_i, which is rounded like a plain LWE decryption. One round, no preprocessing, but the
+// noise budget must leave room for n * 2^w.
+
+/// Widest smudging noise, in bits, with which ciphertexts of noise below `noise_bound` still decrypt
+pub fn max_smudging_bits(params: &PublicParameters, noise_bound: &BigInt) -> Option<usize> {
+    let budget = (&params.big_l >> 1) - noise_bound;
+
+    (0..params.l)
+        .rev()
+        .find(|&bits| (BigInt::one() << bits) * params.n < budget)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NoiseFloodingParty {
+    pub party_number: usize,
+    pub params: PublicParameters,
+
+    /// e_i is uniform in [-2^smudging_bits, 2^smudging_bits]
+    pub smudging_bits: usize,
+
+    sk: Option<DVector<BigInt>>,
+    a: Option<DVector<BigInt>>,
+    b: Option<BigInt>,
+
+    partial_decryption: Option<BigInt>,
+    msg: Option<BigInt>,
+}
+
+generate_getters_and_setters! {
+    NoiseFloodingParty,
+    sk: DVector<BigInt>,
+    a: DVector<BigInt>,
+    b: BigInt,
+    partial_decryption: BigInt,
+    msg: BigInt
+}
+
+impl NoiseFloodingParty {
+    /// Steps of a decryption, the partial decryption and the rounding
+    pub const STEP_COUNT: usize = 2;
+
+    pub fn new(party_number: usize, params: &PublicParameters, smudging_bits: usize) -> NoiseFloodingParty {
+        NoiseFloodingParty {
+            party_number,
+            params: params.clone(),
+            smudging_bits,
+            sk: None,
+            a: None,
+            b: None,
+            partial_decryption: None,
+            msg: None,
+        }
+    }
+
+    /// <a, sk_i> + e_i mod q
+    pub fn partial_decrypt(&mut self) -> BigInt {
+        let bound = BigInt::one() << self.smudging_bits;
+        let e = UniformBigInt::new_inclusive(-&bound, &bound).sample(&mut rand::thread_rng());
+
+        let partial_decryption = (self.get_a().dot(self.get_sk()) + e).mod_floor(&self.params.q);
+        self.set_partial_decryption(partial_decryption.clone());
+
+        partial_decryption
+    }
+
+    /// Rounds b plus the partial decryptions of all parties, this party's own included
+    pub fn combine(&mut self, partial_decryptions: &[BigInt]) -> BigInt {
+        assert_eq!(partial_decryptions.len(), self.params.n);
+
+        let phase = partial_decryptions.iter()
+            .fold(self.get_b().clone(), |acc, partial_decryption| acc + partial_decryption)
+            .mod_floor(&self.params.q);
+
+        let msg = round_div(&phase, &self.params.big_l).mod_floor(&self.params.p);
+        self.set_msg(msg.clone());
+
+        msg
+    }
+}
+
+impl DecryptionParty for NoiseFloodingParty {
+    fn step_count(&self) -> usize {
+        NoiseFloodingParty::STEP_COUNT
+    }
+
+    fn execute_step(&mut self, step_number: usize, input: Vec<ProtocolTransferredData>) -> Result<ProtocolTransferredData, ProtocolAbort> {
+        match step_number {
+            0 => Ok(ProtocolTransferredData {
+                partial_decryption: Some(serialize(&self.partial_decrypt()).unwrap()),
+                ..ProtocolTransferredData::empty()
+            }),
+            1 => {
+                let mut partial_decryptions = vec![self.get_partial_decryption().clone()];
+                for data in input {
+                    partial_decryptions.push(deserialize(&data.partial_decryption.unwrap()).unwrap());
+                }
+
+                self.combine(&partial_decryptions);
+                Ok(ProtocolTransferredData::empty())
+            },
+            _ => unreachable!()
+        }
+    }
+
+    fn msg(&self) -> &BigInt {
+        self.get_msg()
+    }
+}
+
+/// All parties of a noise flooding decryption in one process, the counterpart of `Protocol`
+#[derive(Clone)]
+pub struct NoiseFlooding {
+    pub parties: Vec<NoiseFloodingParty>,
+    pub params: PublicParameters,
+}
+
+impl NoiseFlooding {
+    pub fn new(params: &PublicParameters, smudging_bits: usize) -> NoiseFlooding {
+        NoiseFlooding {
+            parties: (0..params.n).map(|i| NoiseFloodingParty::new(i, params, smudging_bits)).collect(),
+            params: params.clone(),
+        }
+    }
+
+    pub fn share_sk(&mut self, sk: &DVector<BigInt>) {
+        let columns: Vec<DVector<BigInt>> = sk.iter()
+            .map(|sk_digit| AdditiveSecretSharing::share(sk_digit, self.params.n, self.params.k))
+            .collect();
+
+        for (i, party) in self.parties.iter_mut().enumerate() {
+            party.set_sk(DVector::from_fn(sk.nrows(), |j, _| columns[j][i].clone()));
+        }
+    }
+
+    pub fn decrypt(&mut self, a: &DVector<BigInt>, b: &BigInt) -> BigInt {
+        let partial_decryptions: Vec<BigInt> = self.parties.iter_mut()
+            .map(|party| {
+                party.set_a(a.clone());
+                party.set_b(b.clone());
+                party.partial_decrypt()
+            })
+            .collect();
+
+        let msgs: Vec<BigInt> = self.parties.iter_mut().map(|party| party.combine(&partial_decryptions)).collect();
+        assert!(msgs.iter().all(|msg| msg == &msgs[0]));
+
+        msgs[0].clone()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use num_bigint::BigInt;
+    use num_traits::Zero;
+    use crate::mpc::lwe_scheme::LweScheme;
+    use crate::mpc::noise_flooding::{max_smudging_bits, NoiseFlooding, NoiseFloodingParty};
+    use crate::mpc::party::DecryptionParty;
+    use crate::mpc::public_params::PublicParameters;
+    use crate::network::ProtocolTransferredData;
+
+    #[test]
+    fn test_noise_flooding_decrypts_like_the_scheme() {
+        let params = PublicParameters::init(4, 64, 2, 6, 32, 40);
+        let lwe_scheme = LweScheme::new(params.m, params.k, params.lwe_dimension, 0);
+
+        let noise_bound = params.error_distribution.bound();
+        let smudging_bits = max_smudging_bits(&params, &noise_bound).unwrap();
+        assert!((BigInt::from(1) << smudging_bits) * params.n + &noise_bound < &params.big_l >> 1);
+
+        let mut noise_flooding = NoiseFlooding::new(&params, smudging_bits);
+        noise_flooding.share_sk(&lwe_scheme.sk);
+
+        for m in 0..4 {
+            let (a, b) = lwe_scheme.encrypt(&BigInt::from(m));
+            assert_eq!(noise_flooding.decrypt(&a, &b), lwe_scheme.decrypt(&a, &b));
+        }
+
+        // The same through the steps the network runs
+        let (a, b) = lwe_scheme.encrypt(&BigInt::from(3));
+        let mut parties: Vec<NoiseFloodingParty> = noise_flooding.parties.clone();
+        for party in parties.iter_mut() {
+            party.set_a(a.clone());
+            party.set_b(b.clone());
+        }
+
+        let mut messages = vec![ProtocolTransferredData::empty(); params.n];
+        for step in 0..NoiseFloodingParty::STEP_COUNT {
+            messages = parties.iter_mut().enumerate()
+                .map(|(i, party)| {
+                    let input = (0..params.n).filter(|&j| j != i).map(|j| messages[j].clone()).collect();
+                    party.execute_step(step, input).unwrap()
+                })
+                .collect();
+        }
+        assert!(parties.iter().all(|party| party.msg() == &BigInt::from(3)));
+
+        // No room for any smudging noise
+        assert_eq!(max_smudging_bits(&params, &((&params.big_l >> 1) - params.n)), None);
+        assert!(max_smudging_bits(&params, &BigInt::zero()).is_some());
+    }
+}
@@ -0,0 +1,94 @@
+use num_bigint::{BigInt, UniformBigInt};
+use num_integer::Integer;
+use num_traits::Zero;
+use rand::distributions::uniform::UniformSampler;
+use serde::{Deserialize, Serialize};
+use crate::mpc::party::ProtocolAbort;
+use crate::mpc::public_params::PublicParameters;
+use crate::mpc::ring::RingElement;
+use crate::mpc::utils::round_div;
+
+// Output to a designated recipient, e.g. the client that asked for the decryption.
+//
+// The committee opens z' and y' as usual, but o' = L * m only masked as epsilon = o' - a, for a uniform a mod
+// 2^(k+s) dealt with shares of the recipient's key beta and of beta * a. Once epsilon passed the MAC check, each
+// party holds a share beta_i * epsilon + (beta * a)_i of beta * o', which it sends with its share of o' to the
+// recipient only. The recipient alone opens o' and checks sum(mac_i) = beta * o' mod 2^(k+s). The parties never
+// learn beta, so a party that changes its share of o' cannot make up for it in its MAC.
+
+/// What a party sends to the recipient in place of broadcasting o'
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(bound = "R: RingElement")]
+pub struct RecipientShare<R: RingElement = BigInt> {
+    pub party_number: usize,
+    pub o_prime: R,
+    pub mac: R,
+}
+
+/// A party's preprocessing for one output to a recipient, all mod 2^(k+s)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(bound = "R: RingElement")]
+pub struct RecipientMask<R: RingElement = BigInt> {
+    /// Share of the recipient's key beta
+    pub key: R,
+    /// Share of the mask a and of its MAC under the global MAC key
+    pub mask: R,
+    pub mask_mac: R,
+    /// Share of beta * a
+    pub product: R,
+}
+
+impl RecipientMask {
+    /// The same shares in the ring backend of a party
+    pub fn into_ring<R: RingElement>(self) -> RecipientMask<R> {
+        RecipientMask {
+            key: R::from_bigint(&self.key),
+            mask: R::from_bigint(&self.mask),
+            mask_mac: R::from_bigint(&self.mask_mac),
+            product: R::from_bigint(&self.product),
+        }
+    }
+}
+
+/// The receiving side, holding the key the parties MAC their shares with
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutputRecipient {
+    pub params: PublicParameters,
+    key: BigInt,
+}
+
+impl OutputRecipient {
+    /// Recipient with a fresh key mod 2^(k+s)
+    pub fn new(params: &PublicParameters) -> OutputRecipient {
+        let key = UniformBigInt::new(BigInt::zero(), &params.mac_big_ks).sample(&mut rand::thread_rng());
+
+        OutputRecipient {
+            params: params.clone(),
+            key,
+        }
+    }
+
+    /// Key the dealer shares among the parties, see `Preprocessing::recipient_masks`
+    pub fn key(&self) -> &BigInt {
+        &self.key
+    }
+
+    /// The plaintext from the shares of all parties
+    pub fn reconstruct<R: RingElement>(&self, shares: &[RecipientShare<R>]) -> Result<BigInt, ProtocolAbort> {
+        assert_eq!(shares.len(), self.params.n);
+
+        // Opened in the MAC ring, the shares mod q would lose the wrap-arounds the MACs include
+        let o_prime = shares.iter()
+            .fold(BigInt::zero(), |acc, share| acc + share.o_prime.to_bigint())
+            .mod_floor(&self.params.mac_big_ks);
+        let mac = shares.iter()
+            .fold(BigInt::zero(), |acc, share| acc + share.mac.to_bigint())
+            .mod_floor(&self.params.mac_big_ks);
+
+        if mac != (&self.key * &o_prime).mod_floor(&self.params.mac_big_ks) {
+            return Err(ProtocolAbort::OutputMacCheckFailed);
+        }
+
+        Ok(round_div(&o_prime.mod_floor(&self.params.q), &self.params.big_l).mod_floor(&self.params.p))
+    }
+}
@@ -1,60 +1,161 @@
 use std::fmt;
 use std::fmt::{Debug, Display};
-use std::ops::{Add, AddAssign, Mul, Neg, SubAssign};
 use std::time::Instant;
 use log::debug;
 use nalgebra::{DMatrix, DVector};
 use num_bigint::BigInt;
 use num_integer::Integer;
-use num_traits::{ToPrimitive, Zero};
-use crate::mpc::additive_sharing::AdditiveSecretSharing;
-use crate::mpc::base_decomposition::BaseDecomposition;
+use rand::Rng;
+use crate::mpc::mac_scheme::{check_value, commit_check_values};
+use crate::mpc::output_recipient::{RecipientMask, RecipientShare};
+use crate::mpc::preprocessing::{MacShares, OpeningMask, PreprocessedShare};
+use crate::mpc::prss::PrssKeys;
 use crate::mpc::public_params::PublicParameters;
+use crate::mpc::ring::{dot, RingElement};
 use crate::mpc::utils::round_div;
 use crate::network::ProtocolTransferredData;
+use crate::network::common::STEP_COUNT;
 
 use bitcode::{serialize, deserialize};
+use serde::{Deserialize, Serialize};
 use crate::generate_getters_and_setters;
 use paste::paste;
 
 #[derive(Debug, Clone, PartialEq)]
-pub struct Party {
+pub enum ProtocolAbort {
+    /// The MAC check over the opened z', y' and o' did not sum to zero, or a party opened other check values than it
+    /// committed to
+    MacCheckFailed { party_number: usize },
+    /// The MACs the designated recipient received do not match the shares of o'
+    OutputMacCheckFailed,
+    /// The noise of the ciphertext left the bounds of the noise check, the rounded message would be unreliable
+    NoiseOverflow,
+}
+
+impl Display for ProtocolAbort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProtocolAbort::MacCheckFailed { party_number } =>
+                write!(f, "MAC check failed at party {}", party_number),
+            ProtocolAbort::OutputMacCheckFailed =>
+                write!(f, "MAC check of the output failed at the recipient"),
+            ProtocolAbort::NoiseOverflow =>
+                write!(f, "Ciphertext noise exceeds the bound of the noise check"),
+        }
+    }
+}
+
+impl std::error::Error for ProtocolAbort {}
+
+/// A party of a decryption protocol that runs in rounds of one message to every other party
+pub trait DecryptionParty: Send + Sync {
+    /// Steps of one decryption, the output of the last one stays with the party
+    fn step_count(&self) -> usize;
+
+    fn execute_step(&mut self, step_number: usize, input: Vec<ProtocolTransferredData>) -> Result<ProtocolTransferredData, ProtocolAbort>;
+
+    /// The plaintext, once the last step ran
+    fn msg(&self) -> &BigInt;
+}
+
+/// A party's additive share mod 2^(m+s) of a value whose remainder mod p is the plaintext, and its share of the MAC
+/// of that value under the global MAC key
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(bound = "R: RingElement")]
+pub struct PlaintextShare<R: RingElement = BigInt> {
+    pub value: R,
+    pub mac: R,
+}
+
+impl<R: RingElement> PlaintextShare<R> {
+    /// The plaintext, or None when the MACs do not match the shares. `mac_key_shares[i]` is the share of the MAC key
+    /// of the party `shares[i]` belongs to, each contributes mac_i - alpha_i * value to the check
+    pub fn open(shares: &[PlaintextShare<R>], mac_key_shares: &[R], params: &PublicParameters) -> Option<BigInt> {
+        assert_eq!(shares.len(), mac_key_shares.len());
+        let bits = params.mac_ltz_bits();
+
+        let value = shares.iter().fold(R::zero(), |acc, share| acc + share.value.clone()).reduce(bits);
+        let check = shares.iter().zip(mac_key_shares)
+            .fold(R::zero(), |acc, (share, alpha)| acc + check_value(&value, &share.mac, alpha, &R::zero(), bits));
+
+        if !check.reduce(bits).is_zero() {
+            return None;
+        }
+
+        Some(value.to_bigint().mod_floor(&params.p))
+    }
+}
+
+/// A value opened during the decryption, with this party's share of its MAC and the bits of its ring
+#[derive(Debug, Clone, PartialEq)]
+struct Opening<R: RingElement> {
+    value: R,
+    mac: R,
+    bits: usize,
+}
+
+/// A party of the decryption protocol, computing in the ring backend R. Every value lives in Z_2^(k+s) or in a
+/// smaller power of two, so R must compute mod 2^(k+s); BigInt always does and is the reference backend
+#[derive(Debug, Clone, PartialEq)]
+pub struct Party<R: RingElement = BigInt> {
     pub party_number: usize,
 
     pub params: PublicParameters,
 
-    a: Option<DVector<BigInt>>,
-    b: Option<BigInt>,
+    a: Option<DVector<R>>,
+    b: Option<R>,
+
+    s: Option<R>,  // from preprocessing, mod 2^(d+1+s)
+    z: Option<R>,  // z = b + <a,sk> + 2^(l-1) mod 2^(k+s)
+    r: Option<R>,  // from preprocessing, mod 2^(k+s)
 
-    s: Option<BigInt>,  // from preprocessing
-    z: Option<BigInt>,  // z = b - <a,sk> + 2^(l)
-    r: Option<BigInt>,  // from preprocessing
+    y: Option<R>,  // from get_weighted_signs
+    u: Option<R>,  // from LT_r_l
+    e: Option<R>,  // from Mod_l
 
-    y: Option<BigInt>,  // from get_weighted_signs
-    u: Option<BigInt>,  // from LT_r_l
-    e: Option<BigInt>,  // from Mod_l
+    sk: Option<DVector<R>>,    // mod 2^(k+s)
+    ltz: Option<DVector<R>>,
+    signs: Option<DMatrix<R>>,     // rows = B =  2^b = 2^(Digit bit length);   columns = d = Number of digits = ceil(l/b)
 
-    sk: Option<DVector<BigInt>>,
-    ltz: Option<DVector<BigInt>>,
-    signs: Option<DMatrix<BigInt>>,     // rows = B =  2^b = 2^(Digit bit length);   columns = d = Number of digits = ceil(l/b)
+    // Shares of the masks of `OpeningMask::ALL`
+    masks: Option<DVector<R>>,
 
+    z_prime: Option<R>,    // share of z + r + L * rho, z' is its remainder mod L
+    y_prime: Option<R>,    // share of y + s + D * rho, y' is its remainder mod D
+    o_prime: Option<R>,    // share of o + 2^k * rho
 
-    z_prime: Option<BigInt>,
-    y_prime: Option<BigInt>,
-    o_prime: Option<BigInt>,
+    z_prime_opened: Option<R>,     // z' revealed in step two
 
-    z_prime_all_parties: Option<DVector<BigInt>>,
-    y_prime_all_parties: Option<DVector<BigInt>>,
-    o_prime_all_parties: Option<DVector<BigInt>>,
+    msg: Option<BigInt>,    // released only after the MAC check passed
 
-    // MAC key share
-    // alpha: Option<BigInt>,
-    mac_alpha: Option<BigInt>,
+    keep_plaintext_shared: bool,
+    plaintext_share: Option<PlaintextShare<R>>,    // output instead of msg when the plaintext stays shared
 
-    mac_r: Option<DVector<BigInt>>,
-    mac_m_tilde:Option<BigInt>,
-    mac_z: Option<BigInt>,
-    mac_chi_values: Option<DVector<BigInt>>,
+    // Set when o' goes to a designated recipient only, see output_recipient
+    recipient_mask: Option<RecipientMask<R>>,      // shares of the recipient's key, of the mask a and of their product
+    output_pad: Option<R>,                         // share of zero mod 2^(k+s)
+    recipient_share: Option<RecipientShare<R>>,    // sent to the recipient instead of broadcasting o'
+
+    // This party's share of the global MAC key, which no party knows, and its shares of the MACs of its inputs
+    mac_alpha: Option<R>,
+    sk_mac: Option<DVector<R>>,
+    macs: Option<MacShares<R>>,
+    ltz_macs: Option<DVector<R>>,
+    sign_macs: Option<DMatrix<R>>,
+    mac_pads: Option<DVector<R>>,  // shares of zero, one per opened value
+
+    // MAC shares of z and of the shares sent in the first three steps
+    z_mac: Option<R>,
+    z_prime_mac: Option<R>,
+    y_prime_mac: Option<R>,
+    o_prime_mac: Option<R>,
+
+    openings: Vec<Opening<R>>,
+
+    // Check values of the openings, committed to in step four and opened in step five
+    mac_check: Option<DVector<R>>,
+    mac_check_nonce: Option<[u8; 32]>,
+    mac_commitments: Option<Vec<[u8; 32]>>,     // of the other parties
 
     start_time: Option<Instant>,
 
@@ -63,32 +164,45 @@ pub struct Party {
 
 
 generate_getters_and_setters! {
-    Party,
-    a: DVector<BigInt>,
-    b: BigInt,
-    s: BigInt,
-    z: BigInt,
-    r: BigInt,
-    y: BigInt,
-    u: BigInt,
-    e: BigInt,
-    sk: DVector<BigInt>,
-    ltz: DVector<BigInt>,
-    signs: DMatrix<BigInt>,
-    z_prime: BigInt,
-    y_prime: BigInt,
-    o_prime: BigInt,
-    z_prime_all_parties: DVector<BigInt>,
-    y_prime_all_parties: DVector<BigInt>,
-    o_prime_all_parties: DVector<BigInt>,
-    mac_alpha: BigInt,
-    mac_r: DVector<BigInt>,
-    mac_z: BigInt,
-    mac_chi_values: DVector<BigInt>
+    Party<R: RingElement>,
+    a: DVector<R>,
+    b: R,
+    s: R,
+    z: R,
+    r: R,
+    y: R,
+    u: R,
+    e: R,
+    sk: DVector<R>,
+    ltz: DVector<R>,
+    signs: DMatrix<R>,
+    masks: DVector<R>,
+    z_prime: R,
+    y_prime: R,
+    o_prime: R,
+    z_prime_opened: R,
+    msg: BigInt,
+    plaintext_share: PlaintextShare<R>,
+    recipient_mask: RecipientMask<R>,
+    output_pad: R,
+    recipient_share: RecipientShare<R>,
+    mac_alpha: R,
+    sk_mac: DVector<R>,
+    macs: MacShares<R>,
+    ltz_macs: DVector<R>,
+    sign_macs: DMatrix<R>,
+    mac_pads: DVector<R>,
+    z_mac: R,
+    z_prime_mac: R,
+    y_prime_mac: R,
+    o_prime_mac: R,
+    mac_check: DVector<R>,
+    mac_check_nonce: [u8; 32],
+    mac_commitments: Vec<[u8; 32]>
 }
 
 
-impl Party {
+impl<R: RingElement> Party<R> {
 
     pub fn get_params(&self) -> &PublicParameters {
         &self.params
@@ -96,6 +210,8 @@ impl Party {
 
     /// Initialization method
     pub fn new(party_number: usize, params: &PublicParameters) -> Self {
+        assert!(R::fits(params.mac_ks), "The ring backend does not compute mod 2^{}", params.mac_ks);
+
         Party {
             party_number,
             params: params.clone(),
@@ -110,69 +226,140 @@ impl Party {
             sk: None,
             signs: None,
             ltz: None,
+            masks: None,
 
             z_prime: None,
             y_prime: None,
             o_prime: None,
 
+            z_prime_opened: None,
+
+            msg: None,
+
+            keep_plaintext_shared: false,
+            plaintext_share: None,
 
-            z_prime_all_parties: None,
-            y_prime_all_parties: None,
-            o_prime_all_parties: None,
+            recipient_mask: None,
+            output_pad: None,
+            recipient_share: None,
 
-            // alpha: None,
             mac_alpha: None,
-            mac_r: None,
-            mac_m_tilde: None,
-            mac_z: None,
-            mac_chi_values: None,
+            sk_mac: None,
+            macs: None,
+            ltz_macs: None,
+            sign_macs: None,
+            mac_pads: None,
+
+            z_mac: None,
+            z_prime_mac: None,
+            y_prime_mac: None,
+            o_prime_mac: None,
+
+            openings: Vec::new(),
+
+            mac_check: None,
+            mac_check_nonce: None,
+            mac_commitments: None,
 
             start_time: None
 
         }
     }
 
+    /// Takes the tables, masks and MAC shares of a preprocessing instance. The party derives its shares of s, r and
+    /// the masks from its PRSS keys with the instance index as nonce unless the instance brings them, the pads of the
+    /// MAC check always come from PRSS
+    pub fn set_preprocessing(&mut self, share: PreprocessedShare, prss: &PrssKeys, nonce: u64) -> &mut Self {
+        let tables = share.tables.expand(&self.params).into_ring();
+        let lift = |x: BigInt| R::from_bigint(&x);
+
+        self.set_s(lift(share.s.unwrap_or_else(|| prss.s_share(nonce, &self.params))));
+        self.set_r(lift(share.r.unwrap_or_else(|| prss.r_share(nonce, &self.params))));
+        self.set_masks(share.masks.unwrap_or_else(|| prss.mask_shares(nonce, &self.params)).map(lift));
+        self.set_macs(share.macs.into_ring());
+        self.set_ltz(tables.ltz);
+        self.set_signs(tables.signs);
+        self.set_ltz_macs(tables.ltz_macs);
+        self.set_sign_macs(tables.sign_macs);
+        self.set_mac_pads(prss.mac_pads(nonce, &self.params).map(lift))
+    }
+
+    /// Keeps the plaintext shared among the parties instead of opening it, see `PlaintextShare`
+    pub fn keep_plaintext_shared(&mut self) -> &mut Self {
+        self.keep_plaintext_shared = true;
+        self
+    }
 
-    fn get_sign(&self, digit_index: usize, digit_value: usize) -> BigInt {
+
+    fn get_sign(&self, digit_index: usize, digit_value: usize) -> R {
         assert!(digit_index < self.get_signs().ncols() && digit_value < self.get_signs().nrows());
 
         self.get_signs()[(digit_value, digit_index)].clone()
     }
 
 
-    pub fn calc_weighted_sum(&self, z_prime_digits: DVector<BigInt>) -> BigInt {
+    pub fn calc_weighted_sum(&self, z_prime_digits: DVector<BigInt>) -> R {
         assert_eq!(z_prime_digits.nrows(), self.get_signs().ncols());
 
-        let mut lin_comb = BigInt::zero();
+        let mut lin_comb = R::zero();
 
         for (i, digit) in z_prime_digits.iter().enumerate() {
 
             // get to digit num i and retrieve the share at the digit value
-            let digit_sign = self.get_sign(i, digit.to_usize().unwrap());
+            let digit_sign = self.get_sign(i, RingElement::to_usize(digit));
 
             // print!("DEBUG: ({digit_sign} * 2^{i}) + ");
 
-            let two_pow = BigInt::from(2u32).pow(i as u32);
-            lin_comb.add_assign(digit_sign.clone() * &two_pow);
+            lin_comb = lin_comb + (digit_sign << i);
 
         }
         lin_comb
     }
 
+    /// The MAC of the weighted sum, from the MACs of the same entries of the sign tables
+    fn calc_weighted_mac_sum(&self, z_prime_digits: &DVector<BigInt>) -> R {
+        z_prime_digits.iter().enumerate()
+            .fold(R::zero(), |acc, (i, digit)| {
+                acc + (self.get_sign_macs()[(RingElement::to_usize(digit), i)].clone() << i)
+            })
+    }
+
+    fn mask(&self, mask: OpeningMask) -> R {
+        self.get_masks()[mask as usize].clone() << mask.weight_bits(&self.params)
+    }
+
+    fn mask_mac(&self, mask: OpeningMask) -> R {
+        self.get_macs().masks[mask as usize].clone() << mask.weight_bits(&self.params)
+    }
+
+    /// Opens the value of this party's `share` and the shares in `field` of the input, and keeps it with this party's
+    /// MAC share for the check
+    fn open(&mut self, share: &R, mac: &R, input: &[ProtocolTransferredData], field: fn(&ProtocolTransferredData) -> &Option<Vec<u8>>, bits: usize) -> R {
+        let value = input.iter()
+            .fold(share.clone(), |acc, data| {
+                let share: R = deserialize(field(data).as_ref().unwrap()).unwrap();
+                acc + share
+            })
+            .reduce(bits);
+
+        self.openings.push(Opening { value: value.clone(), mac: mac.clone().reduce(bits), bits });
+        value
+    }
+
 
-    pub fn execute_step(&mut self, step_number: usize, input: Vec<ProtocolTransferredData>)  -> ProtocolTransferredData {
+    pub fn execute_step(&mut self, step_number: usize, input: Vec<ProtocolTransferredData>)  -> Result<ProtocolTransferredData, ProtocolAbort> {
         let output = match step_number {
             0 => {
                 self.start_time = Some(Instant::now());
 
-                let out = self.execute_step_one(input);
-                out
+                Ok(self.execute_step_one(input))
             },
-            1 => self.execute_step_two(input),
-            2 => self.execute_step_three(input),
-            3 => self.execute_step_four(input),
-            4 => {
-                let out = self.execute_step_five(input);
+            1 => Ok(self.execute_step_two(input)),
+            2 => Ok(self.execute_step_three(input)),
+            3 => Ok(self.execute_step_four(input)),
+            4 => Ok(self.execute_step_five(input)),
+            5 => {
+                let out = self.execute_step_six(input);
 
                 let elapsed = self.start_time.unwrap().elapsed();
 
@@ -205,349 +392,235 @@ impl Party {
 
         //debug!("execute_step_one {:?}", self);
 
+        let ks = self.params.mac_ks;
 
-        // MPC decryption protocol
-        let mut z = BigInt::zero();
-        // -<a,sk>
-        let neg_a_dot_sk = self.get_a().dot(&self.get_sk())
-            .neg()
-            .mod_floor(&self.params.q);
+        // MPC decryption protocol, z = b + 2^(l-1) + <a,sk>. Party 0 adds the public part to its share, every party
+        // adds alpha_i times it to its MAC share
+        let public_part = self.get_b().clone() + (R::one() << (self.params.l - 1));
 
+        let mut z = dot(self.get_a().as_slice(), self.get_sk().as_slice());
         if self.party_number == 0 {
-
-            let add_term = BigInt::from(2u32).pow(self.params.l as u32 - 1);
-            z.add_assign(self.get_b());
-            z.add_assign(&add_term);
+            z = z + public_part.clone();
         }
+        let z_mac = dot(self.get_a().as_slice(), self.get_sk_mac().as_slice()) + self.get_mac_alpha().clone() * public_part;
 
-        z.sub_assign(&neg_a_dot_sk);
-
-        self.set_z(z);
+        self.set_z(z.reduce(ks));
+        self.set_z_mac(z_mac.reduce(ks));
 
-        let z_prime = (self.get_z() + self.get_r()).mod_floor(&self.params.big_l);
+        let z_prime = (self.get_z().clone() + self.get_r().clone() + self.mask(OpeningMask::Z)).reduce(ks);
+        let z_prime_mac = self.get_z_mac().clone() + self.get_macs().r.clone() + self.mask_mac(OpeningMask::Z);
         self.set_z_prime(z_prime.clone());
+        self.set_z_prime_mac(z_prime_mac);
 
-        // let start_time = std::time::Instant::now();
-
-        let output = ProtocolTransferredData {
-            preprocessed: None,
-            a: None,
-            b: None,
+        ProtocolTransferredData {
             z_prime: Some(serialize(&z_prime).unwrap()),
-            y_prime: None,
-            o_prime: None,
-            // alpha: None,
-            mac_alpha: None,
-            mac_r: None,
-            // mac_x_tilde_collection: None,
-            // mac_m_tilde_collection: None,
-            mac_chi_vals: None,
-            mac_z: None
-        };
-
-        // let elapsed = start_time.elapsed();
-
-        // let _microseconds = elapsed.as_micros();
-
-        //debug!("Serialize traffic from 'participant {}' to other participants' = {_microseconds} microseconds", self.party_number);
-
-        output
-
+            ..ProtocolTransferredData::empty()
+        }
     }
 
     pub fn execute_step_two(&mut self, input: Vec<ProtocolTransferredData>) -> ProtocolTransferredData {
         //debug!("execute_step_two {:?}", self);
-        let mut z_prime_shares = Vec::new();
-        z_prime_shares.push(self.get_z_prime().clone());
-        for share in input {
-            let z_prime_share: BigInt = deserialize(&share.z_prime.unwrap()).unwrap();
-            z_prime_shares.push(z_prime_share);
-        }
-
-        z_prime_shares.sort();
-        self.set_z_prime_all_parties(DVector::from_vec(z_prime_shares));
+        let (share, mac) = (self.get_z_prime().clone(), self.get_z_prime_mac().clone());
+        let opened = self.open(&share, &mac, &input, |data| &data.z_prime, self.params.mac_ks);
 
-        let z_prime = AdditiveSecretSharing::reveal(&self.get_z_prime_all_parties(), self.params.l);
-
-
-        let base_decomposition = BaseDecomposition {
-            base: self.params.big_b
-        };
+        let z_prime = opened.reduce(self.params.l);
+        self.set_z_prime_opened(z_prime.clone());
 
-        let decomposition = base_decomposition.decompose(&z_prime);
 
+        let z_prime_digits = self.params.digits(&z_prime.to_bigint());
 
-        let z_prime_digits = DVector::<BigInt>::from_fn(self.params.d,|i, _| {
-            if i < decomposition.nrows() {
-                decomposition[i].clone()
-            }
-            else {
-                BigInt::zero()
-            }
-        });
+        let sign_bits = self.params.mac_sign_bits();
 
-        let y = self.calc_weighted_sum(z_prime_digits.clone());
+        let y = self.calc_weighted_sum(z_prime_digits.clone()).reduce(sign_bits);
+        let y_mac = self.calc_weighted_mac_sum(&z_prime_digits);
         self.set_y(y);
 
-        let y_prime = (self.get_y() + self.get_s())
-            .mod_floor(&BigInt::from(self.params.big_d));
+        let y_prime = (self.get_y().clone() + self.get_s().clone() + self.mask(OpeningMask::Y))
+            .reduce(sign_bits);
+        let y_prime_mac = y_mac + self.get_macs().s.clone() + self.mask_mac(OpeningMask::Y);
 
         self.set_y_prime(y_prime.clone());
+        self.set_y_prime_mac(y_prime_mac);
 
-        let start_time = std::time::Instant::now();
-
-        let output = ProtocolTransferredData {
-            preprocessed: None,
-            a: None,
-            b: None,
-            z_prime: None,
+        ProtocolTransferredData {
             y_prime: Some(serialize(&y_prime).unwrap()),
-            o_prime: None,
-
-            // alpha: None,
-            mac_alpha: None,
-            mac_r: None,
-            // mac_x_tilde_collection: None,
-            // mac_m_tilde_collection: None,
-            mac_chi_vals: None,
-            mac_z: None,
-        };
-
-        let elapsed = start_time.elapsed();
-
-        let _microseconds = elapsed.as_micros();
-
-        //debug!("Serialize traffic from 'participant {}' to other participants' = {_microseconds} microseconds", self.party_number);
-
-
-        output
+            ..ProtocolTransferredData::empty()
+        }
     }
 
     pub fn execute_step_three(&mut self, input: Vec<ProtocolTransferredData>) -> ProtocolTransferredData {
 
         //debug!("execute_step_three {:?}", self);
-        let mut y_prime_shares = Vec::new();
-        y_prime_shares.push(self.get_y_prime().clone());
-        for share in input {
-            let y_prime_share: BigInt = deserialize(&share.y_prime.unwrap()).unwrap();
-            y_prime_shares.push(y_prime_share);
-        }
+        let (share, mac) = (self.get_y_prime().clone(), self.get_y_prime_mac().clone());
+        let opened = self.open(&share, &mac, &input, |data| &data.y_prime, self.params.mac_sign_bits());
 
-        y_prime_shares.sort();
-        self.set_y_prime_all_parties(DVector::from_vec(y_prime_shares));
-
-        let y_prime = AdditiveSecretSharing::reveal(&self.get_y_prime_all_parties(), self.params.d + 1);
-
-        let y_prime = y_prime.to_usize().unwrap();
+        let y_prime = opened.reduce(self.params.d + 1).to_usize();
         let u = self.get_ltz()[y_prime].clone();
+        let u_mac = self.get_ltz_macs()[y_prime].clone();
 
         self.set_u(u);
 
-        let mut e = BigInt::zero();
-
-        let neg_r = self.get_r()
-            .neg()
-            .mod_floor(&self.params.q);
-
+        // e = z' - r + L * u
+        let l = self.params.l;
+        let mut e = (self.get_u().clone() << l) - self.get_r().clone();
         if self.party_number == 0 {
-            e.add_assign(self.get_z_prime());
+            e = e + self.get_z_prime_opened().clone();
         }
+        let e_mac = (u_mac << l) - self.get_macs().r.clone() + self.get_mac_alpha().clone() * self.get_z_prime_opened().clone();
 
-        e.add_assign(&neg_r);
-
-        let big_l_mul_u = self.get_u()
-            .mul(&self.params.big_l); //.mod_floor(&self.params.p);
-
-        e.add_assign(&big_l_mul_u);
+        let ks = self.params.mac_ks;
+        self.set_e(e.reduce(ks));
 
-        self.set_e(e.clone());
+        // o' = L * m is opened as o + 2^k * rho, or as o + L * rho, which reveals m + rho only
+        let mask = if self.keep_plaintext_shared { OpeningMask::Plaintext } else { OpeningMask::O };
+        let o_prime = (self.get_z().clone() - self.get_e().clone() + self.mask(mask)).reduce(ks);
+        let o_prime_mac = self.get_z_mac().clone() - e_mac + self.mask_mac(mask);
 
-
-        let neg_e = self.get_e()
-            .neg()
-            .mod_floor(&self.params.q);
-
-        let o_prime = self.get_z().add(&neg_e).mod_floor(&self.params.q);
+        // With a designated recipient the committee opens o' - a only, which hides o'
+        let (o_prime, o_prime_mac) = match &self.recipient_mask {
+            Some(recipient_mask) => ((o_prime - recipient_mask.mask.clone()).reduce(ks), o_prime_mac - recipient_mask.mask_mac.clone()),
+            None => (o_prime, o_prime_mac),
+        };
 
         self.set_o_prime(o_prime.clone());
+        self.set_o_prime_mac(o_prime_mac);
 
-
-        let start_time = std::time::Instant::now();
-
-
-        let output = ProtocolTransferredData {
-            preprocessed: None,
-            a: None,
-            b: None,
-            z_prime: None,
-            y_prime: None,
+        ProtocolTransferredData {
             o_prime: Some(serialize(&o_prime).unwrap()),
-
-            // alpha: None,
-            mac_alpha: None,
-            mac_r: None,
-            // mac_x_tilde_collection: None,
-            // mac_m_tilde_collection: None,
-            mac_chi_vals: None,
-            mac_z: None
-        };
-
-        let elapsed = start_time.elapsed();
-
-        let _microseconds = elapsed.as_micros();
-
-        //debug!("Serialize traffic from 'participant {}' to other participants' = {_microseconds} microseconds", self.party_number);
-
-
-        output
+            ..ProtocolTransferredData::empty()
+        }
     }
 
+    /// Opens o' and commits to the check values of the opened values
     pub fn execute_step_four(&mut self, input: Vec<ProtocolTransferredData>) -> ProtocolTransferredData {
 
         //debug!("execute_step_four {:?}", self);
 
-        let mut o_prime_shares = Vec::new();
-        o_prime_shares.push(self.get_o_prime().clone());
-        for share in input {
-            let o_prime_share: BigInt = deserialize(&share.o_prime.unwrap()).unwrap();
-            o_prime_shares.push(o_prime_share);
-        }
-
-        o_prime_shares.sort();
-        self.set_o_prime_all_parties(DVector::from_vec(o_prime_shares));
-
-        let o_prime = AdditiveSecretSharing::reveal(self.get_o_prime_all_parties(), self.params.k);
-
-        //debug!("o_prime = {o_prime}");
-
-        let msg = round_div(&o_prime, &self.params.big_l);
-
-        debug!("Party {} msg = {msg}", self.party_number);
-
-        let t =  3;
-
-        // MAC scheme
-
-        let x_shares_collection = DMatrix::from_fn(self.params.n, t,|i, j| {
-            match j {
-                0 => self.get_z_prime_all_parties()[i].clone(),
-                1 => self.get_y_prime_all_parties()[i].clone(),
-                2 => self.get_o_prime_all_parties()[i].clone(),
-                _ => BigInt::zero() // error
-            }
-        });
-
-
-        // x_tilde_shares_collection [n rows, t columns]
+        let (share, mac) = (self.get_o_prime().clone(), self.get_o_prime_mac().clone());
+        self.open(&share, &mac, &input, |data| &data.o_prime, self.params.mac_ks);
 
-        let x_tilde_shares_collection = DMatrix::from_fn(self.params.n, t,|i, j| {
-            &x_shares_collection[(i,j)] + (&self.get_mac_r()[j] * self.params.mac_big_k.clone())
+        let pads = self.get_mac_pads();
+        let check_values = DVector::from_fn(self.openings.len(), |j, _| {
+            let opening = &self.openings[j];
+            check_value(&opening.value, &opening.mac, self.get_mac_alpha(), &pads[j], opening.bits)
         });
 
+        let nonce: [u8; 32] = rand::thread_rng().gen();
+        let commitment = commit_check_values(self.party_number, &check_values, &nonce);
 
-        // let m_tilde_collection = DMatrix::from_fn(self.params.n, t, |i ,j|{
-        //     let val = self.get_alpha() * &x_tilde_shares_collection[(i, j)];
-        //     val.mod_floor(&self.params.mac_big_ks)
-        // });
-
-        let x_tilde_shares = x_tilde_shares_collection.row_sum()
-            .map(|val| val.mod_floor(&self.params.mac_big_ks));
-
-
-        let y_tilde = self.get_mac_chi_values().dot(&x_tilde_shares.transpose())
-            .mod_floor(&self.params.mac_big_ks);
-
-
-        let party_x_tilde_macs = x_tilde_shares_collection.row(self.party_number)
-            .map(|x| {
-                let val = self.get_mac_alpha() * x;
-                val.mod_floor(&self.params.mac_big_ks)
-            });
-
-        let m_tilde = self.get_mac_chi_values().dot(&party_x_tilde_macs.transpose()).mod_floor(&self.params.mac_big_ks);
-
-        let z = (m_tilde - self.get_mac_alpha() * y_tilde).mod_floor(&self.params.mac_big_ks);
-
-
-        self.set_mac_z(z.clone());
+        self.set_mac_check(check_values);
+        self.set_mac_check_nonce(nonce);
 
+        ProtocolTransferredData {
+            mac_commitment: Some(serialize(&commitment).unwrap()),
+            ..ProtocolTransferredData::empty()
+        }
+    }
 
-        // let combined_mac_shares = DVector::from_fn(self.params.n, |i,_| {
-        //     let player_mac_shares = m_tilde_collection.row(i).transpose();
-        //
-        //     self.get_mac_chi_values().dot(&player_mac_shares).mod_floor(&self.params.mac_big_ks)
-        // });
-        //
+    /// Keeps the commitments of the others and opens the own check values
+    pub fn execute_step_five(&mut self, input: Vec<ProtocolTransferredData>) -> ProtocolTransferredData {
+        let commitments = input.iter()
+            .map(|data| deserialize(data.mac_commitment.as_ref().unwrap()).unwrap())
+            .collect();
+        self.set_mac_commitments(commitments);
 
+        let opening = (self.party_number, self.get_mac_check().clone(), *self.get_mac_check_nonce());
 
+        ProtocolTransferredData {
+            mac_check: Some(serialize(&opening).unwrap()),
+            ..ProtocolTransferredData::empty()
+        }
+    }
 
+    /// Checks that the check values of every party match its commitment and add up to zero, then outputs
+    pub fn execute_step_six(&mut self, input: Vec<ProtocolTransferredData>) -> Result<ProtocolTransferredData, ProtocolAbort> {
+        let abort = ProtocolAbort::MacCheckFailed { party_number: self.party_number };
+
+        let mut senders = Vec::new();
+        let mut opened_commitments = Vec::new();
+        let mut sums = self.get_mac_check().clone();
+        for data in input {
+            let (party_number, check_values, nonce): (usize, DVector<R>, [u8; 32]) = deserialize(data.mac_check.as_ref().unwrap()).unwrap();
+            if check_values.nrows() != sums.nrows() {
+                return Err(abort);
+            }
 
-        // let z = &combined_mac_shares - (&self.mac_alpha.clone().unwrap() * &y_tilde).mod_floor(&self.params.mac_big_ks);
+            opened_commitments.push(commit_check_values(party_number, &check_values, &nonce));
+            senders.push(party_number);
+            sums = sums.zip_map(&check_values, |sum, check_value| sum + check_value);
+        }
 
+        let mut commitments = self.get_mac_commitments().clone();
+        commitments.sort();
+        opened_commitments.sort();
+        senders.sort();
+        let others: Vec<usize> = (0..self.params.n).filter(|&i| i != self.party_number).collect();
+        if commitments != opened_commitments || senders != others {
+            return Err(abort);
+        }
 
-        // let final_sum = z_shares.sum().mod_floor(&self.params.mac_big_ks);
+        let passed = sums.iter().zip(&self.openings)
+            .all(|(sum, opening)| sum.clone().reduce(opening.bits).is_zero());
+        if !passed {
+            return Err(abort);
+        }
 
+        let o_prime = self.openings.last().unwrap().value.clone();
 
-        // let result = if z_shares.sum().mod_floor(&self.params.mac_big_ks) == BigInt::zero() {
-        //     let values = DVector::from_fn(t, |i, _| {
-        //         x_tilde_shares[i].mod_floor(&self.params.mac_big_k)
-        //     });
+        // o' - a passed the check, this party's share of beta * o' is beta_i * (o' - a) + (beta * a)_i. The recipient
+        // gets it with the share of o'
+        if let Some(recipient_mask) = &self.recipient_mask {
+            let ks = self.params.mac_ks;
+            let mac = (recipient_mask.key.clone() * o_prime + recipient_mask.product.clone() + self.get_output_pad().clone()).reduce(ks);
+            let share = (self.get_o_prime().clone() + recipient_mask.mask.clone()).reduce(ks);
+            self.set_recipient_share(RecipientShare { party_number: self.party_number, o_prime: share, mac });
+            return Ok(ProtocolTransferredData::empty());
+        }
 
-            // Some(values)
-        // }
-        // else {
-        //     None
-        // };
+        let o_prime = o_prime.reduce(self.params.k).to_bigint();
 
-        let output = ProtocolTransferredData {
-            preprocessed: None,
-            a: None,
-            b: None,
-            z_prime: None,
-            y_prime: None,
-            o_prime: None,
-            // alpha: None,
-            mac_alpha: None,
-            mac_r: None,
-            // mac_x_tilde_collection: None,
-            // mac_m_tilde_collection: None,
-            mac_chi_vals: None,
-            mac_z: Some(serialize(&z).unwrap()),
-        };
+        let msg = round_div(&o_prime, &self.params.big_l).mod_floor(&self.params.p);
 
-        //debug!("Serialize traffic from 'participant {}' to other participants' = {_microseconds} microseconds", self.party_number);
+        if self.keep_plaintext_shared {
+            // msg is m + rho mod p; the shares of (m + rho) - rho and their MACs live in Z_2^(m+s)
+            let bits = self.params.mac_ltz_bits();
+            let msg = R::from_bigint(&msg);
+            let rho = self.get_masks()[OpeningMask::Plaintext as usize].clone();
+            let rho_mac = self.get_macs().masks[OpeningMask::Plaintext as usize].clone();
 
+            let value = if self.party_number == 0 { msg.clone() - rho } else { -rho }.reduce(bits);
+            let mac = (self.get_mac_alpha().clone() * msg - rho_mac).reduce(bits);
 
-        output
+            self.set_plaintext_share(PlaintextShare { value, mac });
+        } else {
+            debug!("Party {} msg = {msg}", self.party_number);
 
+            self.set_msg(msg);
+        }
 
+        Ok(ProtocolTransferredData::empty())
     }
 
-
-    pub fn execute_step_five(&mut self, input: Vec<ProtocolTransferredData>) -> ProtocolTransferredData {
-        let mut z_sum = BigInt::zero();
-        for i in input {
-
-            let z: BigInt = deserialize(&i.mac_z.unwrap()).unwrap();
-
-            z_sum.add_assign(z);
-        }
-
-        z_sum.add_assign(self.get_mac_z());
+}
 
 
 
 
+impl<R: RingElement> DecryptionParty for Party<R> {
+    fn step_count(&self) -> usize {
+        STEP_COUNT
+    }
 
-        ProtocolTransferredData::empty()
+    fn execute_step(&mut self, step_number: usize, input: Vec<ProtocolTransferredData>) -> Result<ProtocolTransferredData, ProtocolAbort> {
+        Party::execute_step(self, step_number, input)
     }
 
+    fn msg(&self) -> &BigInt {
+        self.get_msg()
+    }
 }
 
-
-
-
-impl Display for Party {
+impl<R: RingElement + Display> Display for Party<R> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
@@ -561,7 +634,7 @@ impl Display for Party {
             y: {}\n\
             u: {}\n\
             e: {}\n\
-            alpha: {}\n\
+            mac_alpha: {}\n\
             a: {}\n\
             b: {}\n\
             z_prime: {}\n\
@@ -589,3 +662,178 @@ impl Display for Party {
 }
 
 
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::DVector;
+    use num_bigint::BigInt;
+    use num_traits::One;
+    use crate::mpc::additive_sharing::AdditiveSecretSharing;
+    use crate::mpc::lwe_scheme::init_lwe_with_random_ptxt;
+    use crate::mpc::party::{Party, PlaintextShare, ProtocolAbort};
+    use crate::mpc::preprocessing::Preprocessing;
+    use crate::mpc::prss::PrssKeys;
+    use crate::mpc::public_params::PublicParameters;
+    use crate::network::ProtocolTransferredData;
+    use crate::network::common::STEP_COUNT;
+
+    use bitcode::{serialize, deserialize};
+
+    const NONCE: u64 = 7;
+
+    fn setup_parties(params: &PublicParameters) -> (Vec<Party>, BigInt) {
+        let (lwe_scheme, ptxt, a, b) = init_lwe_with_random_ptxt(params.m, params.k, params.lwe_dimension, 0);
+
+        let keys = PrssKeys::deal(params.n);
+        let preprocessing = Preprocessing::new(params).with_prss(keys.clone());
+        let shares = preprocessing.run_prss(NONCE);
+        let mac_key_shares = preprocessing.mac_key_shares();
+
+        let sk_shares: Vec<DVector<BigInt>> = lwe_scheme.sk.iter().map(|x| AdditiveSecretSharing::share(x, params.n, params.mac_ks)).collect();
+        let party_sk: Vec<DVector<BigInt>> = (0..params.n)
+            .map(|i| DVector::from_fn(params.lwe_dimension, |j, _| sk_shares[j][i].clone()))
+            .collect();
+        let sk_macs = preprocessing.key_macs(&party_sk);
+
+        let parties = shares.into_iter().zip(party_sk).zip(sk_macs).enumerate()
+            .map(|(i, ((share, sk), sk_mac))| {
+                let mut party = Party::new(i, params);
+                party.set_preprocessing(share, &keys[i], NONCE);
+                party.set_sk(sk);
+                party.set_sk_mac(sk_mac);
+                party.set_mac_alpha(mac_key_shares[i].clone());
+
+                party.set_a(a.clone());
+                party.set_b(b.clone());
+                party
+            })
+            .collect();
+
+        (parties, ptxt)
+    }
+
+    /// Runs all steps, `tamper(step, from, to, data)` may alter a message in transit
+    fn run_parties<F>(parties: &mut [Party], tamper: F) -> Vec<Result<(), ProtocolAbort>>
+    where
+        F: Fn(usize, usize, usize, &mut ProtocolTransferredData),
+    {
+        let n = parties.len();
+        let mut outputs = vec![ProtocolTransferredData::empty(); n];
+        let last_step = STEP_COUNT - 1;
+
+        let inputs = |outputs: &[ProtocolTransferredData], step: usize| -> Vec<Vec<ProtocolTransferredData>> {
+            (0..n)
+                .map(|to| (0..n)
+                    .filter(|from| *from != to)
+                    .map(|from| {
+                        let mut data = outputs[from].clone();
+                        tamper(step, from, to, &mut data);
+                        data
+                    })
+                    .collect())
+                .collect()
+        };
+
+        for step in 0..last_step {
+            outputs = parties.iter_mut().zip(inputs(&outputs, step))
+                .map(|(party, input)| party.execute_step(step, input).unwrap())
+                .collect();
+        }
+
+        parties.iter_mut().zip(inputs(&outputs, last_step))
+            .map(|(party, input)| party.execute_step(last_step, input).map(|_| ()))
+            .collect()
+    }
+
+    type Corruption = fn(&mut ProtocolTransferredData);
+
+    fn add_one(field: &mut Option<Vec<u8>>) {
+        let value: BigInt = deserialize(field.as_ref().unwrap()).unwrap();
+        *field = Some(serialize(&(value + BigInt::one())).unwrap());
+    }
+
+    #[test]
+    fn test_mac_check_passes() {
+        let params = PublicParameters::init(4, 32, 1, 6, 64, 40);
+        let (mut parties, ptxt) = setup_parties(&params);
+
+        let results = run_parties(&mut parties, |_, _, _, _| {});
+
+        for (party, result) in parties.iter().zip(results) {
+            assert_eq!(result, Ok(()));
+            assert_eq!(party.get_msg(), &ptxt);
+        }
+    }
+
+    #[test]
+    fn test_mac_check_detects_corrupted_shares() {
+        let params = PublicParameters::init(4, 32, 1, 6, 64, 40);
+
+        // (step whose output is corrupted, field) for z', y' and o'
+        let corruptions: [(usize, Corruption); 3] = [
+            (0, |data| add_one(&mut data.z_prime)),
+            (1, |data| add_one(&mut data.y_prime)),
+            (2, |data| add_one(&mut data.o_prime)),
+        ];
+
+        for (corrupted_step, corrupt) in corruptions {
+            let (mut parties, _) = setup_parties(&params);
+
+            // Party 1 sends a wrong share to party 2 only
+            let results = run_parties(&mut parties, |step, from, to, data| {
+                if step == corrupted_step + 1 && from == 1 && to == 2 {
+                    corrupt(data);
+                }
+            });
+
+            for (i, result) in results.into_iter().enumerate() {
+                assert_eq!(result, Err(ProtocolAbort::MacCheckFailed { party_number: i }));
+                assert!(parties[i].msg.is_none());
+            }
+        }
+    }
+
+    #[test]
+    fn test_mac_check_detects_consistently_wrong_shares() {
+        let params = PublicParameters::init(4, 32, 1, 6, 64, 40);
+        let (mut parties, _) = setup_parties(&params);
+
+        // Party 1 computes with wrong inputs and broadcasts the same wrong shares to everyone, so all parties open
+        // the same values and only the MACs can tell
+        let corrupted = &mut parties[1];
+        let mut sk = corrupted.get_sk().clone();
+        sk[0] += BigInt::one();
+        let (r, s) = (corrupted.get_r() + 1, corrupted.get_s() + 1);
+        let ltz = corrupted.get_ltz().add_scalar(BigInt::one());
+        corrupted.set_sk(sk).set_r(r).set_s(s).set_ltz(ltz);
+
+        let results = run_parties(&mut parties, |_, _, _, _| {});
+
+        for (i, result) in results.into_iter().enumerate() {
+            assert_eq!(result, Err(ProtocolAbort::MacCheckFailed { party_number: i }));
+            assert!(parties[i].msg.is_none());
+        }
+    }
+
+    #[test]
+    fn test_plaintext_shares_open_with_the_mac_key_shares() {
+        let params = PublicParameters::init(4, 32, 1, 6, 64, 40);
+        let (mut parties, ptxt) = setup_parties(&params);
+        for party in parties.iter_mut() {
+            party.keep_plaintext_shared();
+        }
+
+        let results = run_parties(&mut parties, |_, _, _, _| {});
+        assert!(results.iter().all(|result| result.is_ok()));
+
+        let mut shares: Vec<PlaintextShare> = parties.iter().map(|party| party.get_plaintext_share().clone()).collect();
+        let mac_key_shares: Vec<BigInt> = parties.iter().map(|party| party.get_mac_alpha().clone()).collect();
+        assert!(parties.iter().all(|party| party.msg.is_none()));
+        assert!(shares.iter().all(|share| share.value.bits() <= params.mac_ltz_bits() as u64));
+        assert_eq!(PlaintextShare::open(&shares, &mac_key_shares, &params), Some(ptxt));
+
+        // The shares live in Z_2^(m+s), a share that is only right mod p is caught
+        shares[2].value += &params.p;
+        assert_eq!(PlaintextShare::open(&shares, &mac_key_shares, &params), None);
+    }
+}
@@ -39,16 +39,7 @@ impl ProcessingFunction for LessThanZeroFunction {
     }
 }
 
-// pub struct EqualsFunction;
-//
-// impl ProcessingFunction for EqualsFunction {
-//     fn apply(value: &BigInt, secret: &BigInt) -> BigInt {
-//         match (value - secret).sign() {
-//             Sign::NoSign => BigInt::from(1),
-//             Sign::Minus | Sign::Plus => BigInt::from(0),
-//         }
-//     }
-// }
+// Gates of other functions, e.g. equality, are built through `lookup_gate`
 
 
 
@@ -94,7 +85,7 @@ impl <F: ProcessingFunction> PreprocessedGate<F> {
     }
 
     pub fn get_table_index_shares(&self, table_index: usize) -> DVector<BigInt> {
-        let row: DVector<BigInt> = self.truth_table.row(table_index).transpose().into();  // Convert into DVector
+        let row: DVector<BigInt> = self.truth_table.row(table_index).transpose();  // Convert into DVector
         row
     }
 }
@@ -1,107 +1,614 @@
 use nalgebra::{DMatrix, DVector};
-use num_bigint::BigInt;
-use num_traits::{ToPrimitive, Zero};
+use num_bigint::{BigInt, UniformBigInt};
+use num_integer::Integer;
+use num_traits::{One, Zero};
+use rand::distributions::uniform::UniformSampler;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha20Rng;
 use serde::{Deserialize, Serialize};
 use crate::mpc::additive_sharing::AdditiveSecretSharing;
-use crate::mpc::base_decomposition::BaseDecomposition;
+use crate::mpc::mac_scheme::mac_shares;
+use crate::mpc::output_recipient::RecipientMask;
 use crate::mpc::preprocessed_gate::{LessThanZeroFunction, PreprocessedGate, SignFunction};
+use crate::mpc::prss::{open_masks, open_r, open_s, PrssKeys};
 use crate::mpc::public_params::PublicParameters;
+use crate::mpc::ring::RingElement;
+use crate::mpc::secret_sharing::SecretSharing;
+
+/// A party's shares of every coordinate of the key and of its MAC
+pub type KeyShares<T> = (Vec<T>, Vec<T>);
 
 #[derive(Clone, Default, PartialEq)]
 pub struct Preprocessing {
     pub params: PublicParameters,
+
+    /// Hand parties 0..n-1 a PRG seed for their table shares, party n-1 gets the correction tables
+    pub seed_compression: bool,
+
+    /// PRSS keys of all parties when the parties derive their shares of s, r and the masks themselves
+    pub prss: Option<Vec<PrssKeys>>,
+
+    /// Global MAC key alpha mod 2^(k+s). Only the dealer holds it, every committee gets fresh shares of it
+    pub mac_key: BigInt,
 }
 
 
 #[derive(Clone, Serialize, Deserialize)]
 pub struct PreprocessedShare {
-    pub s: BigInt,
-    pub r: BigInt,
-    pub sk: DVector<BigInt>,
-    pub ltz: DVector<BigInt>,
-    pub signs: DMatrix<BigInt>,     // rows = B =  2^b = 2^(Digit bit length);   columns = d = Number of digits = ceil(l/b)
+    /// None when the party derives its shares of s, r and the masks with PRSS
+    pub s: Option<BigInt>,
+    pub r: Option<BigInt>,
+    /// Shares of the masks in the order of `OpeningMask::ALL`
+    pub masks: Option<DVector<BigInt>>,
+
+    pub macs: MacShares,
+
+    pub tables: TableShares,
+}
+
+/// Masks that hide the bits of an opened value above those the protocol needs. The MACs are checked on the masked
+/// value, so its upper bits have to be uniform for the opening to reveal no more than the bits below them
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OpeningMask {
+    /// m + s bits, z + r + L * rho mod 2^(k+s) is opened and z' is its remainder mod L
+    Z,
+    /// s bits, y + s + D * rho mod 2^(d+1+s) is opened and y' is its remainder mod D
+    Y,
+    /// s bits, o + 2^k * rho mod 2^(k+s) is opened
+    O,
+    /// m + s bits, o + L * rho is opened instead to keep the plaintext shared, see `Party::keep_plaintext_shared`
+    Plaintext,
+}
+
+impl OpeningMask {
+    pub const ALL: [OpeningMask; 4] = [OpeningMask::Z, OpeningMask::Y, OpeningMask::O, OpeningMask::Plaintext];
+
+    pub fn bits(&self, params: &PublicParameters) -> usize {
+        match self {
+            OpeningMask::Z | OpeningMask::Plaintext => params.m + params.mac_s,
+            OpeningMask::Y | OpeningMask::O => params.mac_s,
+        }
+    }
+
+    /// Bits of the ring the mask and its MAC are shared in, the one of the value it hides
+    pub fn ring_bits(&self, params: &PublicParameters) -> usize {
+        match self {
+            OpeningMask::Y => params.mac_sign_bits(),
+            _ => params.mac_ks,
+        }
+    }
+
+    /// The mask is added shifted by ring bits - bits, which also cancels any carry above its bits
+    pub fn weight_bits(&self, params: &PublicParameters) -> usize {
+        self.ring_bits(params) - self.bits(params)
+    }
+}
+
+/// A party's shares of the MACs alpha * x of the values of an instance, each in the ring of the value
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(bound = "R: RingElement")]
+pub struct MacShares<R: RingElement = BigInt> {
+    /// mod 2^(d+1+s)
+    pub s: R,
+    /// mod 2^(k+s)
+    pub r: R,
+    /// In the order of `OpeningMask::ALL`
+    pub masks: DVector<R>,
+}
+
+impl MacShares {
+    /// The same shares in the ring backend of a party
+    pub fn into_ring<R: RingElement>(self) -> MacShares<R> {
+        MacShares {
+            s: R::from_bigint(&self.s),
+            r: R::from_bigint(&self.r),
+            masks: self.masks.map(|x| R::from_bigint(&x)),
+        }
+    }
+}
+
+/// A party's shares of the LTZ and sign tables and of the MACs of their entries
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(bound = "R: RingElement")]
+pub struct Tables<R: RingElement = BigInt> {
+    /// mod 2^(m+s)
+    pub ltz: DVector<R>,
+    /// mod 2^(d+1+s); rows = max(B, B') = Values of a digit;   columns = d = Number of digits
+    pub signs: DMatrix<R>,
+    pub ltz_macs: DVector<R>,
+    pub sign_macs: DMatrix<R>,
+}
+
+impl Tables {
+    /// The same shares in the ring backend of a party
+    pub fn into_ring<R: RingElement>(self) -> Tables<R> {
+        let lift = |x: BigInt| R::from_bigint(&x);
+
+        Tables {
+            ltz: self.ltz.map(lift),
+            signs: self.signs.map(lift),
+            ltz_macs: self.ltz_macs.map(lift),
+            sign_macs: self.sign_macs.map(lift),
+        }
+    }
+
+    fn reduce(self, params: &PublicParameters) -> Tables {
+        let ltz_modulo = BigInt::one() << params.mac_ltz_bits();
+        let signs_modulo = BigInt::one() << params.mac_sign_bits();
+
+        Tables {
+            ltz: self.ltz.map(|x| x.mod_floor(&ltz_modulo)),
+            signs: self.signs.map(|x| x.mod_floor(&signs_modulo)),
+            ltz_macs: self.ltz_macs.map(|x| x.mod_floor(&ltz_modulo)),
+            sign_macs: self.sign_macs.map(|x| x.mod_floor(&signs_modulo)),
+        }
+    }
+}
+
+/// A party's shares of the tables
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum TableShares {
+    /// Every entry, as held by the party that gets the correction tables
+    Explicit(Tables),
+
+    /// Seed of uniformly random entries, which the party expands itself
+    Seeded([u8; 32]),
+}
+
+impl TableShares {
+    /// Entries of a seeded share, the dealer and the party expand the same ones
+    fn expand_seed(seed: &[u8; 32], params: &PublicParameters) -> Tables {
+        let mut prg = ChaCha20Rng::from_seed(*seed);
+
+        let ltz_modulo = BigInt::one() << params.mac_ltz_bits();
+        let signs_modulo = BigInt::one() << params.mac_sign_bits();
+        let ltz_sampler = UniformBigInt::new(BigInt::zero(), &ltz_modulo);
+        let signs_sampler = UniformBigInt::new(BigInt::zero(), &signs_modulo);
+
+        let ltz = DVector::from_fn(params.big_d, |_, _| ltz_sampler.sample(&mut prg));
+        let signs = DMatrix::from_fn(params.sign_rows(), params.d, |_, _| signs_sampler.sample(&mut prg));
+        let ltz_macs = DVector::from_fn(params.big_d, |_, _| ltz_sampler.sample(&mut prg));
+        let sign_macs = DMatrix::from_fn(params.sign_rows(), params.d, |_, _| signs_sampler.sample(&mut prg));
+
+        Tables { ltz, signs, ltz_macs, sign_macs }
+    }
+
+    pub fn expand(&self, params: &PublicParameters) -> Tables {
+        match self {
+            TableShares::Explicit(tables) => tables.clone(),
+            TableShares::Seeded(seed) => TableShares::expand_seed(seed, params),
+        }
+    }
+}
+
+
+/// Independent preprocessing instances of one party, instance `j * ctxt_per_job + c` decrypts ciphertext `c` of job `j`.
+/// Masks must never be reused, so every instance can be taken only once
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PreprocessingPool {
+    instances: Vec<Option<PreprocessedShare>>,
+}
+
+impl PreprocessingPool {
+    pub fn new(instances: Vec<PreprocessedShare>) -> PreprocessingPool {
+        PreprocessingPool {
+            instances: instances.into_iter().map(Some).collect()
+        }
+    }
+
+    /// Number of instances not consumed yet
+    pub fn remaining(&self) -> usize {
+        self.instances.iter().filter(|instance| instance.is_some()).count()
+    }
+
+    /// Consumes the instances `first..first + count`, or none at all when any of them is missing or already used
+    pub fn take(&mut self, first: usize, count: usize) -> Option<Vec<PreprocessedShare>> {
+        let range = self.instances.get_mut(first..first + count)?;
+
+        if range.iter().any(|instance| instance.is_none()) {
+            return None;
+        }
+
+        Some(range.iter_mut().map(|instance| instance.take().unwrap()).collect())
+    }
 }
 
 impl Preprocessing {
     pub fn new(params: &PublicParameters) -> Preprocessing {
         Preprocessing {
-            params: params.clone()
+            params: params.clone(),
+            seed_compression: false,
+            prss: None,
+            mac_key: UniformBigInt::new(BigInt::zero(), &params.mac_big_ks).sample(&mut rand::thread_rng()),
         }
     }
 
-    pub fn run(&self, s: BigInt, r: BigInt, sk: DVector<BigInt>) -> Vec<PreprocessedShare> {
+    /// Instances are dealt for the s, r and masks the parties derive from `keys`, with the instance index as nonce
+    pub fn with_prss(mut self, keys: Vec<PrssKeys>) -> Preprocessing {
+        assert_eq!(keys.len(), self.params.n);
+        self.prss = Some(keys);
+        self
+    }
 
-        // Initialize empty Party structs
+    pub fn with_seed_compression(mut self) -> Preprocessing {
+        self.seed_compression = true;
+        self
+    }
 
-        // Additive secrete sharing of [s]_(d+1)
-        let s_shares = AdditiveSecretSharing::share(&s, self.params.n, self.params.d + 1);
+    /// Fresh additive shares mod 2^(k+s) of the MAC key, one per member of the committee
+    pub fn mac_key_shares(&self) -> DVector<BigInt> {
+        AdditiveSecretSharing::share(&self.mac_key, self.params.n, self.params.mac_ks)
+    }
 
-        // Build [LTZ(y)]_m gate
-        let ltz_function = LessThanZeroFunction {
-            modulo: BigInt::from(self.params.big_d)
-        };
+    /// Shares of the MAC of the key the shares `sk_shares` add up to in Z_2^(k+s), `sk_shares[i]` is the one of party i
+    pub fn key_macs(&self, sk_shares: &[DVector<BigInt>]) -> Vec<DVector<BigInt>> {
+        assert_eq!(sk_shares.len(), self.params.n);
 
-        let ltz_gate = PreprocessedGate::build(ltz_function,
-                                               s.clone(), self.params.n, self.params.big_d.to_usize().unwrap(), self.params.m);
+        // rows = parties; columns = LWE dimension
+        let macs = DMatrix::from_columns(&(0..sk_shares[0].nrows())
+            .map(|j| {
+                let sk = sk_shares.iter().fold(BigInt::zero(), |acc, share| acc + &share[j]);
+                mac_shares(&sk, &self.mac_key, self.params.n, self.params.mac_ks)
+            })
+            .collect::<Vec<_>>());
 
-        // Additive secrete sharing of [r]_k
-        let r_shares = AdditiveSecretSharing::share(&r, self.params.n, self.params.k);
+        (0..self.params.n).map(|i| macs.row(i).transpose()).collect()
+    }
 
-        let base_decomposition = BaseDecomposition {
-            base: self.params.big_b
-        };
+    /// Shares of a random mask rho of the key and of its MAC, per party. A party that generated its key share sk_i
+    /// itself publishes sk_i - rho_i, from which every party derives its MAC share of the key. The dealer knows every
+    /// rho_i, so it has to be trusted not to see the published values, from which it would recover every sk_i
+    pub fn key_input_masks(&self, dimension: usize) -> Vec<(DVector<BigInt>, DVector<BigInt>)> {
+        let masks: Vec<DVector<BigInt>> = (0..dimension)
+            .map(|_| {
+                let rho = UniformBigInt::new(BigInt::zero(), &self.params.mac_big_ks).sample(&mut rand::thread_rng());
+                AdditiveSecretSharing::share(&rho, self.params.n, self.params.mac_ks)
+            })
+            .collect();
+        let mask_shares: Vec<DVector<BigInt>> = (0..self.params.n)
+            .map(|i| DVector::from_fn(dimension, |j, _| masks[j][i].clone()))
+            .collect();
 
-        let r_digits = base_decomposition.decompose(&r);
+        mask_shares.iter().cloned().zip(self.key_macs(&mask_shares)).collect()
+    }
 
-        let r_digits = DVector::<BigInt>::from_fn(self.params.d,|i, _| {
-            if i < r_digits.nrows() {
-                r_digits[i].clone()
-            }
-            else {
-                BigInt::zero()
+    /// Shares with `scheme` of every coordinate of the key and of its MAC alpha * sk, per party. The shares of a
+    /// committee large enough to reconstruct turn into additive ones that match the MAC key shares dealt to it
+    pub fn threshold_key_shares<S: SecretSharing>(&self, scheme: &S, sk: &DVector<BigInt>) -> Vec<KeyShares<S::Share>> {
+        let mut shares_per_party = vec![(Vec::with_capacity(sk.nrows()), Vec::with_capacity(sk.nrows())); scheme.num_parties()];
+        for sk_digit in sk.iter() {
+            let digit_shares = scheme.share_secret(sk_digit);
+            let mac_shares = scheme.share_secret(&(&self.mac_key * sk_digit));
+
+            for ((sk_shares, sk_macs), (share, mac)) in shares_per_party.iter_mut().zip(digit_shares.into_iter().zip(mac_shares)) {
+                sk_shares.push(share);
+                sk_macs.push(mac);
             }
-        });
+        }
 
-        let mut sign_gates_shares = Vec::<PreprocessedGate<SignFunction>>::new();
+        shares_per_party
+    }
 
-        for r_digit in r_digits.iter() {
-            let sign_gate = PreprocessedGate::build( SignFunction,
-                                                     r_digit.clone(), self.params.n, self.params.big_b, self.params.d + 1);
-            sign_gates_shares.push(sign_gate);
-        }
+    /// Preprocessing of one output to the recipient with key `recipient_key`, per party
+    pub fn recipient_masks(&self, recipient_key: &BigInt) -> Vec<RecipientMask> {
+        let (n, bits) = (self.params.n, self.params.mac_ks);
 
-        // rows = 2^b = 2^(Digit bit length);   columns = d = Number of digits = ceil(l/b)
-        let mut sign_gates_per_party = vec![DMatrix::zeros(self.params.big_b, self.params.d); self.params.n];
+        let a = UniformBigInt::new(BigInt::zero(), &self.params.mac_big_ks).sample(&mut rand::thread_rng());
+        let key_shares = AdditiveSecretSharing::share(recipient_key, n, bits);
+        let mask_shares = AdditiveSecretSharing::share(&a, n, bits);
+        let mask_macs = mac_shares(&a, &self.mac_key, n, bits);
+        let products = AdditiveSecretSharing::share(&(recipient_key * &a), n, bits);
 
-        for (d, sign_gate) in sign_gates_shares.iter().enumerate() {
-            for i in 0..self.params.n {
-                let party_digit_share = sign_gate.get_party_shares(i);
-                sign_gates_per_party[i].set_column(d, &party_digit_share);
-            }
+        (0..n)
+            .map(|i| RecipientMask {
+                key: key_shares[i].clone(),
+                mask: mask_shares[i].clone(),
+                mask_mac: mask_macs[i].clone(),
+                product: products[i].clone(),
+            })
+            .collect()
+    }
+
+    /// Replaces the tables of parties 0..n-1 by seeded ones, and makes up the difference in the tables of party n-1
+    fn compress(&self, tables: Vec<Tables>) -> Vec<TableShares> {
+        let mut rng = rand::thread_rng();
+
+        let mut correction = tables[self.params.n - 1].clone();
+        let mut shares = Vec::new();
+
+        for party_tables in &tables[..self.params.n - 1] {
+            let seed: [u8; 32] = rng.gen();
+            let seeded = TableShares::expand_seed(&seed, &self.params);
+
+            correction = Tables {
+                ltz: correction.ltz + &party_tables.ltz - seeded.ltz,
+                signs: correction.signs + &party_tables.signs - seeded.signs,
+                ltz_macs: correction.ltz_macs + &party_tables.ltz_macs - seeded.ltz_macs,
+                sign_macs: correction.sign_macs + &party_tables.sign_macs - seeded.sign_macs,
+            }.reduce(&self.params);
+
+            shares.push(TableShares::Seeded(seed));
         }
 
-        let mut sk_shares_per_party = DMatrix::<BigInt>::zeros(sk.nrows(), self.params.n);
-        for (i, sk_digit) in sk.iter().enumerate() {
+        shares.push(TableShares::Explicit(correction));
+        shares
+    }
 
-            // Share each digit of secret key [sk]_k
-            let sk_digit_shares = AdditiveSecretSharing::share(sk_digit, self.params.n, self.params.k);
-            sk_shares_per_party.set_row(i, &sk_digit_shares.transpose());
+    pub fn run(&self, s: BigInt, r: BigInt) -> Vec<PreprocessedShare> {
+        let mut rng = rand::thread_rng();
+
+        // Additive secrete sharing of [s]_(d+1+s)
+        let s_shares = AdditiveSecretSharing::share(&s, self.params.n, self.params.mac_sign_bits());
+
+        // Additive secrete sharing of [r]_(k+s)
+        let r_shares = AdditiveSecretSharing::share(&r, self.params.n, self.params.mac_ks);
+
+        let masks: Vec<BigInt> = OpeningMask::ALL.iter()
+            .map(|mask| UniformBigInt::new(BigInt::zero(), BigInt::one() << mask.bits(&self.params)).sample(&mut rng))
+            .collect();
+        let mask_shares: Vec<DVector<BigInt>> = OpeningMask::ALL.iter().zip(&masks)
+            .map(|(mask, rho)| AdditiveSecretSharing::share(rho, self.params.n, mask.ring_bits(&self.params)))
+            .collect();
+
+        self.deal(&s, &r, &masks).into_iter().enumerate()
+            .map(|(i, mut share)| {
+                share.s = Some(s_shares[i].clone());
+                share.r = Some(r_shares[i].clone());
+                share.masks = Some(DVector::from_fn(mask_shares.len(), |j, _| mask_shares[j][i].clone()));
+                share
+            })
+            .collect()
+    }
+
+    /// Instance `nonce` for parties that derive their shares of s, r and the masks with PRSS
+    pub fn run_prss(&self, nonce: u64) -> Vec<PreprocessedShare> {
+        let keys = self.prss.as_ref().expect("PRSS preprocessing needs the keys of all parties");
+
+        let (s, s_carry) = open_s(keys, nonce, &self.params);
+        let (r, carry) = open_r(keys, nonce, &self.params);
+
+        self.deal(&(s + s_carry * self.params.big_d), &(r + carry * &self.params.big_l), &open_masks(keys, nonce, &self.params))
+    }
+
+    /// Tables and MACs for the values the shares of s, r and the masks add up to. With PRSS these exceed s < D and
+    /// r < L by a multiple of D and L, see `open_r`
+    fn deal(&self, s_sum: &BigInt, r_sum: &BigInt, mask_sums: &[BigInt]) -> Vec<PreprocessedShare> {
+        let params = &self.params;
+        let (ltz_bits, sign_bits) = (params.mac_ltz_bits(), params.mac_sign_bits());
+
+        let s = s_sum.mod_floor(&BigInt::from(params.big_d));
+        let (carry, r) = r_sum.div_mod_floor(&params.big_l);
+
+        // Build [LTZ(y)]_(m+s) gate; rows = entries; columns = parties
+        let ltz_function = LessThanZeroFunction {
+            modulo: BigInt::from(params.big_d)
+        };
+        let mut ltz = PreprocessedGate::build(ltz_function, s, params.n, params.big_d, ltz_bits).truth_table;
+
+        // e = z' - (r + c * L) + L * (u + c) for the carry c of the r shares
+        let ltz_modulo = BigInt::one() << ltz_bits;
+        for entry in ltz.column_mut(0).iter_mut() {
+            *entry = (&*entry + &carry).mod_floor(&ltz_modulo);
         }
+        let ltz_macs = self.table_macs(&ltz, ltz_bits);
 
-        let mut shares = Vec::new();
-        for i in 0..self.params.n {
-            let share = PreprocessedShare {
-                s: s_shares[i].clone(),
-                r: r_shares[i].clone(),
-                sk: sk_shares_per_party.column(i).into(),
-                ltz: ltz_gate.get_party_shares(i),
-                signs: sign_gates_per_party[i].clone(),
+        // One gate per digit of r, with the MACs of its entries
+        let sign_gates: Vec<(DMatrix<BigInt>, DMatrix<BigInt>)> = params.digits(&r).iter()
+            .map(|r_digit| {
+                let gate = PreprocessedGate::build(SignFunction, r_digit.clone(), params.n, params.sign_rows(), sign_bits).truth_table;
+                let macs = self.table_macs(&gate, sign_bits);
+                (gate, macs)
+            })
+            .collect();
+
+        let tables: Vec<Tables> = (0..params.n)
+            .map(|i| Tables {
+                ltz: ltz.column(i).into(),
+                signs: DMatrix::from_fn(params.sign_rows(), params.d, |x, t| sign_gates[t].0[(x, i)].clone()),
+                ltz_macs: ltz_macs.column(i).into(),
+                sign_macs: DMatrix::from_fn(params.sign_rows(), params.d, |x, t| sign_gates[t].1[(x, i)].clone()),
+            })
+            .collect();
+
+        let tables = if self.seed_compression {
+            self.compress(tables)
+        } else {
+            tables.into_iter().map(TableShares::Explicit).collect()
+        };
+
+        let s_macs = mac_shares(s_sum, &self.mac_key, params.n, sign_bits);
+        let r_macs = mac_shares(r_sum, &self.mac_key, params.n, params.mac_ks);
+        let mask_macs: Vec<DVector<BigInt>> = OpeningMask::ALL.iter().zip(mask_sums)
+            .map(|(mask, rho)| mac_shares(rho, &self.mac_key, params.n, mask.ring_bits(params)))
+            .collect();
+
+        tables.into_iter().enumerate()
+            .map(|(i, tables)| PreprocessedShare {
+                s: None,
+                r: None,
+                masks: None,
+                macs: MacShares {
+                    s: s_macs[i].clone(),
+                    r: r_macs[i].clone(),
+                    masks: DVector::from_fn(mask_macs.len(), |j, _| mask_macs[j][i].clone()),
+                },
+                tables,
+            })
+            .collect()
+    }
+
+    /// Shares of the MACs of the entries of a table; rows = entries; columns = parties
+    fn table_macs(&self, table: &DMatrix<BigInt>, bits: usize) -> DMatrix<BigInt> {
+        DMatrix::from_rows(&table.row_iter()
+            .map(|shares| {
+                let value = shares.iter().fold(BigInt::zero(), |acc, share| acc + share);
+                mac_shares(&value, &self.mac_key, self.params.n, bits).transpose()
+            })
+            .collect::<Vec<_>>())
+    }
+
+    /// Runs the preprocessing `instances` times with fresh s and r, returns the instances of every party. With PRSS
+    /// instance j is the one of nonce j
+    pub fn run_instances(&self, instances: usize) -> Vec<Vec<PreprocessedShare>> {
+        self.run_instances_from(0, instances)
+    }
+
+    /// `run_instances` for the instances `first..first + instances`, which only matters for the PRSS nonces
+    pub fn run_instances_from(&self, first: usize, instances: usize) -> Vec<Vec<PreprocessedShare>> {
+        let mut rng = rand::thread_rng();
+
+        let mut shares_per_party: Vec<Vec<PreprocessedShare>> = (0..self.params.n).map(|_| Vec::new()).collect();
+
+        for instance in first..first + instances {
+            let shares = if self.prss.is_some() {
+                self.run_prss(instance as u64)
+            } else {
+                let s = UniformBigInt::new(&BigInt::zero(), &BigInt::from(self.params.big_d)).sample(&mut rng);
+                let r = UniformBigInt::new(&BigInt::zero(), &self.params.big_l).sample(&mut rng);
+                self.run(s, r)
             };
 
-            shares.push(share)
+            for (party_shares, share) in shares_per_party.iter_mut().zip(shares) {
+                party_shares.push(share);
+            }
         }
 
-        shares
+        shares_per_party
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use bitcode::serialize;
+    use nalgebra::{DMatrix, DVector};
+    use num_bigint::BigInt;
+    use num_integer::Integer;
+    use num_traits::One;
+    use crate::mpc::additive_sharing::AdditiveSecretSharing;
+    use crate::mpc::preprocessing::{OpeningMask, PreprocessedShare, Preprocessing, PreprocessingPool, TableShares};
+    use crate::mpc::prss::PrssKeys;
+    use crate::mpc::public_params::PublicParameters;
+    use crate::mpc::shamir_sharing::ShamirSecretSharing;
+
+    #[test]
+    fn test_run_instances_uses_fresh_masks() {
+        let params = PublicParameters::init(3, 32, 1, 5, 8, 0);
+        let shares = Preprocessing::new(&params).run_instances(4);
+
+        assert_eq!(shares.len(), params.n);
+        assert!(shares.iter().all(|instances| instances.len() == 4));
+
+        let masks: Vec<_> = (0..4)
+            .map(|j| AdditiveSecretSharing::reveal(&DVector::from_fn(params.n, |i, _| shares[i][j].r.clone().unwrap()), params.k))
+            .collect();
+        assert!(masks.iter().any(|r| r != &masks[0]));
+    }
+
+    #[test]
+    fn test_pool_hands_out_instances_once() {
+        let params = PublicParameters::init(3, 32, 1, 5, 8, 0);
+        let mut shares = Preprocessing::new(&params).run_instances(4);
+        let mut pool = PreprocessingPool::new(shares.remove(0));
+
+        assert_eq!(pool.remaining(), 4);
+        assert_eq!(pool.take(2, 2).map(|instances| instances.len()), Some(2));
+        assert_eq!(pool.remaining(), 2);
+
+        // Already consumed or out of range
+        assert!(pool.take(2, 2).is_none());
+        assert!(pool.take(1, 2).is_none());
+        assert!(pool.take(4, 1).is_none());
+        assert_eq!(pool.remaining(), 2);
+
+        assert!(pool.take(0, 2).is_some());
+        assert_eq!(pool.remaining(), 0);
+    }
+
+    #[test]
+    fn test_seeded_tables_open_to_the_same_values() {
+        let params = PublicParameters::init(4, 64, 2, 6, 16, 32);
+        let s = BigInt::from(77);
+        let r = BigInt::from(123456789);
+
+        let preprocessing = Preprocessing::new(&params);
+        let explicit = preprocessing.run(s.clone(), r.clone());
+        let seeded = preprocessing.clone().with_seed_compression().run(s, r);
+
+        assert!(seeded[..params.n - 1].iter().all(|share| matches!(share.tables, TableShares::Seeded(_))));
+        assert!(matches!(seeded[params.n - 1].tables, TableShares::Explicit(_)));
+
+        // The tables and their MACs are functions of s, r and the MAC key, both sharings open to them
+        let open = |shares: &[PreprocessedShare]| {
+            let tables: Vec<_> = shares.iter().map(|share| share.tables.expand(&params)).collect();
+            let ltz = DMatrix::from_fn(params.big_d, 2, |x, j| {
+                let column = DVector::from_fn(params.n, |i, _| if j == 0 { tables[i].ltz[x].clone() } else { tables[i].ltz_macs[x].clone() });
+                AdditiveSecretSharing::reveal(&column, params.mac_ltz_bits())
+            });
+            let signs = DMatrix::from_fn(params.big_b, 2 * params.d, |x, t| {
+                let column = DVector::from_fn(params.n, |i, _| if t < params.d { tables[i].signs[(x, t)].clone() } else { tables[i].sign_macs[(x, t - params.d)].clone() });
+                AdditiveSecretSharing::reveal(&column, params.mac_sign_bits())
+            });
+            (ltz, signs)
+        };
+        assert_eq!(open(&seeded), open(&explicit));
+
+        // A seeded party stores 32 bytes instead of its tables
+        let size = |share: &PreprocessedShare| serialize(&share.tables).unwrap().len();
+        assert!(size(&seeded[0]) * 10 < size(&explicit[0]));
+    }
+
+    #[test]
+    fn test_macs_open_to_the_mac_key_times_the_values() {
+        let params = PublicParameters::init(4, 32, 2, 5, 16, 32);
+        let keys = PrssKeys::deal(params.n);
+        let preprocessing = Preprocessing::new(&params).with_prss(keys.clone());
+        let shares = preprocessing.run_prss(3);
+
+        let check = |values: Vec<BigInt>, macs: Vec<BigInt>, bits: usize| {
+            let value = AdditiveSecretSharing::reveal(&DVector::from_vec(values), bits);
+            let mac = AdditiveSecretSharing::reveal(&DVector::from_vec(macs), bits);
+            assert_eq!(mac, (&preprocessing.mac_key * value).mod_floor(&(BigInt::one() << bits)));
+        };
+
+        // The PRSS shares of s, r and the masks, whose carries the MACs include
+        check(keys.iter().map(|k| k.s_share(3, &params)).collect(), shares.iter().map(|x| x.macs.s.clone()).collect(), params.mac_sign_bits());
+        check(keys.iter().map(|k| k.r_share(3, &params)).collect(), shares.iter().map(|x| x.macs.r.clone()).collect(), params.mac_ks);
+        for (j, mask) in OpeningMask::ALL.iter().enumerate() {
+            check(keys.iter().map(|k| k.mask_shares(3, &params)[j].clone()).collect(),
+                  shares.iter().map(|x| x.macs.masks[j].clone()).collect(), mask.ring_bits(&params));
+        }
+
+        let tables: Vec<_> = shares.iter().map(|share| share.tables.expand(&params)).collect();
+        for x in [0, params.big_d - 1] {
+            check(tables.iter().map(|t| t.ltz[x].clone()).collect(), tables.iter().map(|t| t.ltz_macs[x].clone()).collect(), params.mac_ltz_bits());
+        }
+        check(tables.iter().map(|t| t.signs[(1, 0)].clone()).collect(), tables.iter().map(|t| t.sign_macs[(1, 0)].clone()).collect(), params.mac_sign_bits());
+
+        // Every committee gets fresh shares of the same key
+        assert_ne!(preprocessing.mac_key_shares(), preprocessing.mac_key_shares());
+        assert_eq!(AdditiveSecretSharing::reveal(&preprocessing.mac_key_shares(), params.mac_ks), preprocessing.mac_key);
+    }
+
+    #[test]
+    fn test_threshold_key_shares_carry_macs_under_any_committee() {
+        let params = PublicParameters::init(5, 32, 2, 5, 6, 32);
+        let preprocessing = Preprocessing::new(&params);
+        let scheme = ShamirSecretSharing::new(params.n, 2, params.mac_ks);
+        let sk = DVector::from_fn(params.lwe_dimension, |j, _| BigInt::from(j * 1000 + 7));
+
+        let key_shares = preprocessing.threshold_key_shares(&scheme, &sk);
+        for live in [vec![0, 1, 2], vec![4, 1, 3], vec![0, 1, 2, 3, 4]] {
+            let additive: Vec<_> = live.iter()
+                .map(|&i| (scheme.to_additive_all(i, &key_shares[i].0, &live), scheme.to_additive_all(i, &key_shares[i].1, &live)))
+                .collect();
+
+            for j in 0..params.lwe_dimension {
+                let open = |values: Vec<BigInt>| AdditiveSecretSharing::reveal(&DVector::from_vec(values), params.mac_ks);
+                assert_eq!(open(additive.iter().map(|(sk, _)| sk[j].clone()).collect()), sk[j]);
+                assert_eq!(open(additive.iter().map(|(_, mac)| mac[j].clone()).collect()),
+                           (&preprocessing.mac_key * &sk[j]).mod_floor(&params.mac_big_ks));
+            }
+        }
+    }
+}
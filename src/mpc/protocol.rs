@@ -2,12 +2,21 @@ use std::ops::{Add, AddAssign, Mul, Neg, SubAssign};
 use nalgebra::{DMatrix, DVector};
 use num_bigint::{BigInt};
 use num_integer::Integer;
-use num_traits::{ToPrimitive, Zero};
+use num_traits::{One, ToPrimitive, Zero};
 use crate::mpc::additive_sharing::AdditiveSecretSharing;
-use crate::mpc::base_decomposition::BaseDecomposition;
-use crate::mpc::party::Party;
+use crate::mpc::batch_decryption::BatchDecryption;
+use crate::mpc::distributed_preprocessing::DistributedPreprocessing;
+use crate::mpc::glwe_scheme::GlweCiphertext;
+use crate::mpc::key_generation::DistributedKeyGeneration;
+use crate::mpc::key_refresh::KeyRefresh;
+use crate::mpc::lwe_scheme::LwePublicKey;
+use crate::mpc::noise_check::NoiseCheck;
+use crate::mpc::resharing::Resharing;
+use crate::mpc::party::{Party, ProtocolAbort};
+use crate::mpc::preprocessing::Preprocessing;
 use crate::mpc::preprocessed_gate::{LessThanZeroFunction, PreprocessedGate, SignFunction};
 use crate::mpc::public_params::PublicParameters;
+use crate::mpc::secret_sharing::SecretSharing;
 use crate::mpc::utils::round_div;
 
 #[derive(Clone)]
@@ -48,36 +57,23 @@ impl Protocol {
         // Additive secrete sharing of [r]_k
         let r_shares = AdditiveSecretSharing::share(&r, self.params.n, self.params.k);
 
-        let base_decomposition = BaseDecomposition {
-            base: self.params.big_b
-        };
-
-        let r_digits = base_decomposition.decompose(&r);
-
-        let r_digits = DVector::<BigInt>::from_fn(self.params.d,|i, _| {
-            if i < r_digits.nrows() {
-                r_digits[i].clone()
-            }
-            else {
-                BigInt::zero()
-            }
-        });
+        let r_digits = self.params.digits(&r);
 
         let mut sign_gates_shares = Vec::<PreprocessedGate<SignFunction>>::new();
 
         for r_digit in r_digits.iter() {
             let sign_gate = PreprocessedGate::build( SignFunction,
-                r_digit.clone(), self.params.n, self.params.big_b, self.params.d + 1);
+                r_digit.clone(), self.params.n, self.params.sign_rows(), self.params.d + 1);
             sign_gates_shares.push(sign_gate);
         }
 
-        // rows = 2^b = 2^(Digit bit length);   columns = d = Number of digits = ceil(l/b)
-        let mut sign_gates_per_party = vec![DMatrix::zeros(self.params.big_b, self.params.d); self.params.n];
+        // rows = max(2^b, 2^b') = Values of a digit;   columns = d = Number of digits
+        let mut sign_gates_per_party = vec![DMatrix::zeros(self.params.sign_rows(), self.params.d); self.params.n];
 
         for (d, sign_gate) in sign_gates_shares.iter().enumerate() {
-            for i in 0..self.params.n {
+            for (i, party_signs) in sign_gates_per_party.iter_mut().enumerate() {
                 let party_digit_share = sign_gate.get_party_shares(i);
-                sign_gates_per_party[i].set_column(d, &party_digit_share);
+                party_signs.set_column(d, &party_digit_share);
             }
         }
 
@@ -90,6 +86,24 @@ impl Protocol {
 
     }
 
+    /// Dealer-free alternative to `preprocess`, hides s, r and the tables from any t parties
+    pub fn preprocess_distributed(&mut self, privacy_threshold: usize) {
+        let setup = DistributedPreprocessing::new(&self.params, privacy_threshold);
+
+        // The MACs the setup computes along are not checked by these parties
+        let mac_key = Preprocessing::new(&self.params).mac_key_shares();
+        let shares = setup.run(&mac_key);
+
+        for (party, mut instances) in self.parties.iter_mut().zip(shares) {
+            let share = instances.remove(0);
+            let tables = share.tables.expand(&self.params);
+            party.set_s(share.s.unwrap());
+            party.set_r(share.r.unwrap());
+            party.set_ltz(tables.ltz);
+            party.set_signs(tables.signs);
+        }
+    }
+
     pub fn share_sk(&mut self, sk: DVector<BigInt>) {
         let mut sk_shares_per_party = DMatrix::<BigInt>::zeros(sk.nrows(), self.params.n);
         for (i, sk_digit) in sk.iter().enumerate() {
@@ -107,6 +121,90 @@ impl Protocol {
         }
     }
 
+    /// Lets the parties generate the key without a dealer, returns the public key
+    pub fn generate_sk(&mut self, pk_rows: usize) -> LwePublicKey {
+        let key_generation = DistributedKeyGeneration::new(&self.params, pk_rows);
+
+        let mut b_shares = Vec::new();
+        for party in self.parties.iter_mut() {
+            let contribution = key_generation.contribute();
+            party.set_sk(contribution.sk_share);
+            b_shares.push(contribution.b_share);
+        }
+
+        key_generation.public_key(&key_generation.combine(&b_shares))
+    }
+
+    /// Moves every party to a fresh additive sharing of the same key
+    pub fn refresh_sk(&mut self) {
+        let key_refresh = KeyRefresh::new(&self.params);
+        let dimension = self.parties[0].get_sk().nrows();
+
+        // pieces_per_sender[j][i] is sent from party j to party i
+        let pieces_per_sender: Vec<Vec<DVector<BigInt>>> = (0..self.params.n)
+            .map(|_| key_refresh.zero_sharing(dimension))
+            .collect();
+
+        for (i, party) in self.parties.iter_mut().enumerate() {
+            let received: Vec<DVector<BigInt>> = pieces_per_sender.iter().map(|pieces| pieces[i].clone()).collect();
+            let party_sk = key_refresh.refresh(party.get_sk(), &received);
+
+            party.set_sk(party_sk);
+        }
+    }
+
+    /// Protocol of a new committee of `new_committee_size` parties holding fresh shares of the same key.
+    /// The new committee needs its own preprocessing
+    pub fn reshare(&self, new_committee_size: usize) -> Protocol {
+        let resharing = Resharing::new(&self.params, new_committee_size);
+
+        // pieces_per_sender[i][j] is sent from old member i to new member j
+        let pieces_per_sender: Vec<Vec<DVector<BigInt>>> = self.parties.iter()
+            .map(|party| resharing.split(party.get_sk()))
+            .collect();
+
+        let mut protocol = Protocol::new(&resharing.new_params);
+        for (j, party) in protocol.parties.iter_mut().enumerate() {
+            let received: Vec<DVector<BigInt>> = pieces_per_sender.iter().map(|pieces| pieces[j].clone()).collect();
+
+            party.set_sk(resharing.combine(&received));
+        }
+
+        protocol
+    }
+
+    /// Shares every coordinate of sk with `scheme`, returns the key shares of every party
+    pub fn share_sk_with<S: SecretSharing>(&self, scheme: &S, sk: &DVector<BigInt>) -> Vec<Vec<S::Share>> {
+        assert_eq!(scheme.num_parties(), self.params.n);
+
+        let mut shares_per_party = vec![Vec::with_capacity(sk.nrows()); self.params.n];
+        for sk_digit in sk.iter() {
+            for (party_shares, share) in shares_per_party.iter_mut().zip(scheme.share_secret(sk_digit)) {
+                party_shares.push(share);
+            }
+        }
+
+        shares_per_party
+    }
+
+    /// Protocol run by the `live` parties alone, their key shares become additive shares of sk among them.
+    /// The smaller committee needs its own preprocessing
+    pub fn restrict_to<S: SecretSharing>(&self, scheme: &S, sk_shares: &[Vec<S::Share>], live: &[usize]) -> Protocol {
+        assert!(live.len() >= scheme.reconstruction_threshold(),
+                "{} live parties cannot decrypt, {} are needed", live.len(), scheme.reconstruction_threshold());
+
+        let mut protocol = Protocol::new(&self.params.with_committee_size(live.len()));
+
+        for (party, &i) in protocol.parties.iter_mut().zip(live) {
+            let party_sk = DVector::from_iterator(sk_shares[i].len(), sk_shares[i].iter()
+                .map(|share| scheme.to_additive(i, share, live)));
+
+            party.set_sk(party_sk);
+        }
+
+        protocol
+    }
+
     pub fn noisy_decrypt(&mut self, z: BigInt) -> BigInt{
         let z_shares = AdditiveSecretSharing::share(&z, self.params.n, self.params.k);
 
@@ -133,6 +231,29 @@ impl Protocol {
 
 
     pub fn decrypt(&mut self, a: DVector<BigInt>, b: BigInt) -> BigInt {
+        self.phase_protocol(&a, &b);
+
+        // All parties have a share of z, and LTZ + Sign gates
+        self.mod_l_protocol();
+
+        self.open_message()
+    }
+
+    /// `decrypt` that first tests the noise against the bound of `noise_check`, without revealing it
+    pub fn decrypt_checked(&mut self, a: DVector<BigInt>, b: BigInt, noise_check: NoiseCheck) -> Result<BigInt, ProtocolAbort> {
+        self.phase_protocol(&a, &b);
+
+        self.mod_l_protocol();
+
+        if self.noise_overflow_protocol(&noise_check) {
+            return Err(ProtocolAbort::NoiseOverflow);
+        }
+
+        Ok(self.open_message())
+    }
+
+    // Each party sets its share of z
+    fn phase_protocol(&mut self, a: &DVector<BigInt>, b: &BigInt) {
         //--------------------------------------------------------------------------------------------------------------------------------
         //                         Start Step 1
         //--------------------------------------------------------------------------------------------------------------------------------
@@ -142,13 +263,13 @@ impl Protocol {
         for (i, party) in self.parties.iter_mut().enumerate() {
             let mut z = BigInt::zero();
             // -<a,sk>
-            let neg_a_dot_sk = a.dot(&party.get_sk())
+            let neg_a_dot_sk = a.dot(party.get_sk())
                 .neg()
                 .mod_floor(&self.params.q);
 
             if i == 0 {
                 let add_term = BigInt::from(2u32).pow(self.params.l as u32 - 1);
-                z.add_assign(&b);
+                z.add_assign(b);
                 z.add_assign(&add_term);
             }
 
@@ -156,10 +277,10 @@ impl Protocol {
 
             party.set_z(z);
         }
+    }
 
-        // All parties have a share of z, and LTZ + Sign gates
-        self.mod_l_protocol();
-
+    // Reveal(z - e) rounded, once the parties hold shares of e
+    fn open_message(&mut self) -> BigInt {
         let o_prime_shares = DVector::<BigInt>::from_fn(self.params.n, |i, _| {
             let neg_e = self.parties[i].get_e()
                 .neg()
@@ -178,9 +299,52 @@ impl Protocol {
         // Reveal(z - e)
         let o_prime = AdditiveSecretSharing::reveal(&o_prime_shares, self.params.k);
 
-        let msg = round_div(&o_prime, &self.params.big_l);
+        round_div(&o_prime, &self.params.big_l).mod_floor(&self.params.p)
+    }
+
+    /// Decrypts every slot of a GLWE ciphertext in the rounds of a single decryption, under the key whose shares
+    /// the parties hold as the concatenated coefficients of s_1, ..., s_k
+    pub fn decrypt_glwe(&self, ciphertext: &GlweCiphertext) -> Result<DVector<BigInt>, ProtocolAbort> {
+        let sk_shares: Vec<DVector<BigInt>> = self.parties.iter().map(|party| party.get_sk().clone()).collect();
 
-        msg
+        let mut batch: BatchDecryption = BatchDecryption::new(&self.params, &sk_shares, &ciphertext.sample_extract_all());
+        Ok(DVector::from_vec(batch.run()?))
+    }
+
+    /// Whether the noise is out of the bounds of `noise_check`, once the parties hold shares of e; only this bit is
+    /// opened
+    pub fn noise_overflow_protocol(&mut self, noise_check: &NoiseCheck) -> bool {
+        let z_prime_shares = DVector::from_fn(self.params.n, |i, _| self.parties[i].get_z_prime().clone());
+        let z_prime = AdditiveSecretSharing::reveal(&z_prime_shares, self.params.l);
+
+        let (x_1, x_2, wraps) = noise_check.comparison_points(&self.params, &z_prime);
+
+        // The gates of the decryption are used up, each comparison runs with its own
+        let gates: Vec<(BigInt, DVector<BigInt>)> = self.parties.iter().map(|party| (party.get_s().clone(), party.get_ltz().clone())).collect();
+
+        let comparisons: Vec<DVector<BigInt>> = [x_1, x_2].iter().enumerate()
+            .map(|(j, x)| {
+                for (i, party) in self.parties.iter_mut().enumerate() {
+                    let (s, ltz) = noise_check.gate(j, i);
+                    party.set_s(s);
+                    party.set_ltz(ltz);
+                }
+
+                // [x < r]
+                self.lt_r_l_protocol(x)
+            })
+            .collect();
+
+        for (party, (s, ltz)) in self.parties.iter_mut().zip(gates) {
+            party.set_s(s);
+            party.set_ltz(ltz);
+        }
+
+        // [x_1 < r] - [x_2 < r] + [x_1 > x_2]
+        let mut overflow_shares = &comparisons[0] - &comparisons[1];
+        overflow_shares[0] += wraps;
+
+        AdditiveSecretSharing::reveal(&overflow_shares, self.params.m).is_one()
     }
 
     // returns sharing [e] where:
@@ -292,30 +456,13 @@ impl Protocol {
 
 
     pub fn weighted_signs_protocol(&self, z_prime: &BigInt) -> DVector<BigInt>{
-        // Base decomposition in base B of public z_prime
-        let base_decomposition = BaseDecomposition {
-            base: self.params.big_b
-        };
-
-        let decomposition = base_decomposition.decompose(z_prime);
-
-
-        let z_prime_digits = DVector::<BigInt>::from_fn(self.params.d,|i, _| {
-            if i < decomposition.nrows() {
-                decomposition[i].clone()
-            }
-            else {
-                BigInt::zero()
-            }
-        });
+        // Digits of the public z_prime, d - 1 in base B and the top one of b' bits
+        let z_prime_digits = self.params.digits(z_prime);
 
         // Each party executes locally WeightedSigns function and the result is assigned into [y] share
-        let y_shares = DVector::<BigInt>::from_fn(self.parties.len(), |i, _| {
-
+        DVector::<BigInt>::from_fn(self.parties.len(), |i, _| {
             self.parties[i].calc_weighted_sum(z_prime_digits.clone())
-        });
-
-        y_shares
+        })
     }
 }
 
@@ -325,14 +472,19 @@ mod tests {
     use std::ops::{Div, Neg};
     use nalgebra::DMatrix;
     use num_bigint::{BigInt, UniformBigInt};
-    
-    use num_traits::Zero;
+    use num_integer::Integer;
+    use num_traits::{One, Zero};
     use rand::distributions::uniform::UniformSampler;
     use crate::mpc::additive_sharing::AdditiveSecretSharing;
-    use crate::mpc::lwe_scheme::init_lwe_with_random_ptxt;
+    use crate::mpc::distributions::{ErrorDistribution, KeyDistribution};
+    use crate::mpc::glwe_scheme::GlweScheme;
+    use crate::mpc::lwe_scheme::{init_lwe_with_random_ptxt, LweParameters, LweScheme};
     use crate::mpc::mac_scheme::{AuthenticatedSharingScheme, MACSchemeParams};
+    use crate::mpc::noise_check::NoiseCheck;
+    use crate::mpc::party::ProtocolAbort;
     use crate::mpc::protocol::Protocol;
     use crate::mpc::public_params::PublicParameters;
+    use crate::mpc::shamir_sharing::ShamirSecretSharing;
 
     #[test]
     fn test_decrypt_mac() {
@@ -350,7 +502,7 @@ mod tests {
 
         let mut rng = rand::thread_rng();
 
-        let (lwe_scheme, ptxt, a, b, ) = init_lwe_with_random_ptxt(params.m, params.k, params.lwe_dimension, 1);
+        let (lwe_scheme, ptxt, a, b, ) = init_lwe_with_random_ptxt(params.m, params.k, params.lwe_dimension, 0);
 
         let s = UniformBigInt::new(&BigInt::zero(), &BigInt::from(params.big_d)).sample(&mut rng);
 
@@ -406,8 +558,8 @@ mod tests {
 
         // loop {
             let k = 64;     // Ciphertext bit length
-            let mac_s = 80; // MAC Security parameter bit length
-            let mac_t = 3;  // MAC batch count
+            let _mac_s = 80; // MAC Security parameter bit length
+            let _mac_t = 3;  // MAC batch count
             let n = 4;      // Number of parties
             let m = 1;      // Plaintext bit length
             let b = 7;      // "Digit" bit length
@@ -419,13 +571,13 @@ mod tests {
 
             let mut rng = rand::thread_rng();
 
-            let (lwe_scheme, ptxt, a, b, ) = init_lwe_with_random_ptxt(params.m, params.k, params.lwe_dimension, 1);
+            let (lwe_scheme, ptxt, a, b, ) = init_lwe_with_random_ptxt(params.m, params.k, params.lwe_dimension, 0);
 
             let s = UniformBigInt::new(&BigInt::zero(), &BigInt::from(params.big_d)).sample(&mut rng);
 
             let r = UniformBigInt::new(&BigInt::zero(), &params.big_l).sample(&mut rng);
 
-            let global_mac_key = UniformBigInt::new(&BigInt::zero(), &params.big_l).sample(&mut rng);
+            let _global_mac_key = UniformBigInt::new(&BigInt::zero(), &params.big_l).sample(&mut rng);
 
 
             let mut protocol = Protocol::new(&params);
@@ -442,12 +594,32 @@ mod tests {
         // }
     }
 
+    #[test]
+    fn test_decrypt_with_a_wide_top_digit() {
+        // l = 63 bits in 9 digits of 6 bits under a top digit of 9 bits
+        let params = PublicParameters::init(4, 64, 1, 6, 256, 0).with_top_digit_bits(9);
+        assert_eq!(params.d, 10);
+        let mut rng = rand::thread_rng();
+
+        for _ in 0..4 {
+            let (lwe_scheme, ptxt, a, b, ) = init_lwe_with_random_ptxt(params.m, params.k, params.lwe_dimension, 0);
+
+            let mut protocol = Protocol::new(&params);
+            let s = UniformBigInt::new(&BigInt::zero(), &BigInt::from(params.big_d)).sample(&mut rng);
+            let r = UniformBigInt::new(&BigInt::zero(), &params.big_l).sample(&mut rng);
+            protocol.preprocess(s, r);
+            protocol.share_sk(lwe_scheme.sk);
+
+            assert_eq!(protocol.decrypt(a, b), ptxt);
+        }
+    }
+
     #[test]
     fn debug_template() {
         // loop {
             let k = 64;         // Ciphertext bit length
-            let mac_s = 16;     // MAC Security parameter bit length
-            let mac_t = 3;      // MAC batch count
+            let _mac_s = 16;     // MAC Security parameter bit length
+            let _mac_t = 3;      // MAC batch count
             let n = 4;          // Number of parties
             let m = 4;          // Plaintext bit length
             let b = 8;          // "Digit" bit length
@@ -479,10 +651,217 @@ mod tests {
             let mut protocol = Protocol::new(&params);
             protocol.preprocess(s, r);
 
-            let z_sub_e = protocol.noisy_decrypt(z);
+            let _z_sub_e = protocol.noisy_decrypt(z);
 
 
 
         // }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_decrypt_with_any_t_plus_one_parties() {
+        let params = PublicParameters::init(5, 64, 1, 7, 256, 0);
+        let mut rng = rand::thread_rng();
+
+        let (lwe_scheme, ptxt, a, b, ) = init_lwe_with_random_ptxt(params.m, params.k, params.lwe_dimension, 0);
+
+        let protocol = Protocol::new(&params);
+        let scheme = ShamirSecretSharing::new(params.n, 2, params.k);
+        let sk_shares = protocol.share_sk_with(&scheme, &lwe_scheme.sk);
+
+        // Parties not listed are offline
+        for live in [vec![0, 1, 2], vec![4, 1, 3], vec![0, 2, 3, 4]] {
+            let mut committee = protocol.restrict_to(&scheme, &sk_shares, &live);
+
+            let s = UniformBigInt::new(&BigInt::zero(), &BigInt::from(params.big_d)).sample(&mut rng);
+            let r = UniformBigInt::new(&BigInt::zero(), &params.big_l).sample(&mut rng);
+            committee.preprocess(s, r);
+
+            assert_eq!(committee.decrypt(a.clone(), b.clone()), ptxt);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "live parties cannot decrypt")]
+    fn test_decrypt_with_t_parties_fails() {
+        let params = PublicParameters::init(5, 64, 1, 7, 16, 0);
+        let (lwe_scheme, _, _, _, ) = init_lwe_with_random_ptxt(params.m, params.k, params.lwe_dimension, 0);
+
+        let protocol = Protocol::new(&params);
+        let scheme = ShamirSecretSharing::new(params.n, 2, params.k);
+        let sk_shares = protocol.share_sk_with(&scheme, &lwe_scheme.sk);
+
+        protocol.restrict_to(&scheme, &sk_shares, &[0, 3]);
+    }
+
+    #[test]
+    fn test_decrypt_after_key_refresh() {
+        let params = PublicParameters::init(4, 64, 1, 7, 64, 0);
+        let mut rng = rand::thread_rng();
+
+        let (lwe_scheme, ptxt, a, b, ) = init_lwe_with_random_ptxt(params.m, params.k, params.lwe_dimension, 0);
+
+        let mut protocol = Protocol::new(&params);
+        protocol.share_sk(lwe_scheme.sk.clone());
+
+        for _ in 0..3 {
+            let old_shares: Vec<_> = protocol.parties.iter().map(|party| party.get_sk().clone()).collect();
+            protocol.refresh_sk();
+
+            for (party, old_share) in protocol.parties.iter().zip(&old_shares) {
+                assert_ne!(party.get_sk(), old_share);
+            }
+
+            let s = UniformBigInt::new(&BigInt::zero(), &BigInt::from(params.big_d)).sample(&mut rng);
+            let r = UniformBigInt::new(&BigInt::zero(), &params.big_l).sample(&mut rng);
+            protocol.preprocess(s, r);
+
+            assert_eq!(protocol.decrypt(a.clone(), b.clone()), ptxt);
+        }
+    }
+
+    #[test]
+    fn test_decrypt_after_committee_change() {
+        let params = PublicParameters::init(4, 64, 1, 7, 64, 0);
+        let mut rng = rand::thread_rng();
+
+        let (lwe_scheme, ptxt, a, b, ) = init_lwe_with_random_ptxt(params.m, params.k, params.lwe_dimension, 0);
+
+        let mut protocol = Protocol::new(&params);
+        protocol.share_sk(lwe_scheme.sk);
+
+        for new_committee_size in [8, 3, 5] {
+            protocol = protocol.reshare(new_committee_size);
+            assert_eq!(protocol.parties.len(), new_committee_size);
+
+            let s = UniformBigInt::new(&BigInt::zero(), &BigInt::from(params.big_d)).sample(&mut rng);
+            let r = UniformBigInt::new(&BigInt::zero(), &params.big_l).sample(&mut rng);
+            protocol.preprocess(s, r);
+
+            assert_eq!(protocol.decrypt(a.clone(), b.clone()), ptxt);
+        }
+    }
+
+    #[test]
+    fn test_decrypt_public_key_ciphertexts() {
+        let params = PublicParameters::init(4, 64, 2, 7, 64, 0);
+        let mut rng = rand::thread_rng();
+
+        let pk_rows = LweParameters::from_public_parameters(&params, 0).min_public_key_rows();
+        let lwe_scheme = LweScheme::new(params.m, params.k, params.lwe_dimension, pk_rows);
+        let mut protocol = Protocol::new(&params);
+        protocol.share_sk(lwe_scheme.sk.clone());
+
+        for m in 0..4 {
+            let ptxt = BigInt::from(m);
+            let (a, b) = lwe_scheme.encrypt_with_public_key(&ptxt);
+            assert_eq!(lwe_scheme.decrypt(&a, &b), ptxt);
+
+            let s = UniformBigInt::new(&BigInt::zero(), &BigInt::from(params.big_d)).sample(&mut rng);
+            let r = UniformBigInt::new(&BigInt::zero(), &params.big_l).sample(&mut rng);
+            protocol.preprocess(s, r);
+
+            assert_eq!(protocol.decrypt(a, b), ptxt);
+        }
+    }
+
+    #[test]
+    fn test_decrypt_with_distributed_key() {
+        let params = PublicParameters::init(4, 64, 2, 7, 64, 0);
+        let mut rng = rand::thread_rng();
+
+        let mut protocol = Protocol::new(&params);
+        let pk = protocol.generate_sk(LweParameters::from_public_parameters(&params, 0).min_public_key_rows());
+
+        for m in 0..4 {
+            let (a, b) = pk.encrypt(&BigInt::from(m));
+
+            let s = UniformBigInt::new(&BigInt::zero(), &BigInt::from(params.big_d)).sample(&mut rng);
+            let r = UniformBigInt::new(&BigInt::zero(), &params.big_l).sample(&mut rng);
+            protocol.preprocess(s, r);
+
+            assert_eq!(protocol.decrypt(a, b), BigInt::from(m));
+        }
+    }
+
+    #[test]
+    fn test_decrypt_with_recorded_distributions() {
+        let params = PublicParameters::init(4, 64, 2, 7, 64, 0)
+            .with_distributions(ErrorDistribution::CenteredBinomial { eta: 8 }, KeyDistribution::Ternary);
+        let mut rng = rand::thread_rng();
+
+        let pk_rows = LweParameters::from_public_parameters(&params, 0).min_public_key_rows();
+        let lwe_scheme = LweScheme::with_parameters(&LweParameters::from_public_parameters(&params, pk_rows));
+        assert_eq!(lwe_scheme.pk.error_distribution, params.error_distribution);
+
+        let mut protocol = Protocol::new(&params);
+        protocol.share_sk(lwe_scheme.sk.clone());
+
+        for m in 0..4 {
+            let ptxt = BigInt::from(m);
+            let (a, b) = lwe_scheme.encrypt_with_public_key(&ptxt);
+
+            let s = UniformBigInt::new(&BigInt::zero(), &BigInt::from(params.big_d)).sample(&mut rng);
+            let r = UniformBigInt::new(&BigInt::zero(), &params.big_l).sample(&mut rng);
+            protocol.preprocess(s, r);
+
+            assert_eq!(protocol.decrypt(a, b), ptxt);
+        }
+    }
+
+    #[test]
+    fn test_decrypt_glwe() {
+        // RLWE with one mask polynomial, GLWE with two
+        for (glwe_dimension, polynomial_size) in [(1, 32), (2, 16)] {
+            let params = PublicParameters::init(4, 32, 2, 6, 32, 0);
+            let scheme = GlweScheme::new(&params, glwe_dimension, polynomial_size);
+
+            let mut protocol = Protocol::new(&params);
+            protocol.share_sk(scheme.flattened_sk());
+
+            let m = scheme.random_plaintext();
+            assert_eq!(protocol.decrypt_glwe(&scheme.encrypt(&m)), Ok(m));
+        }
+    }
+
+    #[test]
+    fn test_decrypt_checked_reports_noise_overflow() {
+        let params = PublicParameters::init(4, 32, 2, 6, 32, 0);
+        let lwe_scheme = LweScheme::new(params.m, params.k, params.lwe_dimension, 0);
+        let mut rng = rand::thread_rng();
+
+        let max_noise = &params.big_l >> 3;
+        let half_l = &params.big_l >> 1;
+
+        // Extra noise added to a fresh ciphertext, and whether it leaves [-max_noise, max_noise)
+        let cases = [
+            (BigInt::zero(), false),
+            (&max_noise - (BigInt::one() << 20), false),
+            (-&max_noise + (BigInt::one() << 20), false),
+            (&max_noise * 2, true),
+            (-&max_noise * 2, true),
+            // The plain rounding returns a wrong message here
+            (&half_l + (BigInt::one() << 20), true),
+        ];
+
+        for (extra_noise, overflows) in cases {
+            let ptxt = BigInt::from(2);
+            let (a, b) = lwe_scheme.encrypt(&ptxt);
+            let b = (b + extra_noise).mod_floor(&params.q);
+
+            let mut protocol = Protocol::new(&params);
+            protocol.share_sk(lwe_scheme.sk.clone());
+
+            let s = UniformBigInt::new(&BigInt::zero(), &BigInt::from(params.big_d)).sample(&mut rng);
+            let r = UniformBigInt::new(&BigInt::zero(), &params.big_l).sample(&mut rng);
+            protocol.preprocess(s, r);
+
+            let result = protocol.decrypt_checked(a, b, NoiseCheck::deal(&params, &max_noise));
+            if overflows {
+                assert_eq!(result, Err(ProtocolAbort::NoiseOverflow));
+            } else {
+                assert_eq!(result, Ok(ptxt));
+            }
+        }
+    }
+}
@@ -0,0 +1,198 @@
+use nalgebra::DVector;
+use num_bigint::{BigInt, RandBigInt};
+use num_integer::Integer;
+use num_traits::{One, Zero};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use serde::{Deserialize, Serialize};
+use crate::mpc::preprocessing::OpeningMask;
+use crate::mpc::public_params::PublicParameters;
+
+// Pseudorandom secret sharing from keys that are set up once.
+//
+// Party i holds a key k_i of its own and a key k_ij for every other party j. For a nonce, here the index of the
+// preprocessing instance, party i derives without any communication
+//  - its share F(k_i, nonce) of a random value, which only the holder of all keys can open,
+//  - its share sum_{j > i} F(k_ij, nonce) - sum_{j < i} F(k_ij, nonce) of zero.
+// F is ChaCha20 keyed with the key, on the stream of the nonce. Every label owns a window of the stream, so one
+// nonce gives independent r, s, opening masks and pads.
+
+/// Words of the ChaCha20 stream a label may use, enough for values of 512 bits
+const WORDS_PER_LABEL: u128 = 16;
+
+/// What a value is derived for
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PrssLabel {
+    R,
+    S,
+    OutputPad,
+    MacPad(usize),
+    Mask(OpeningMask),
+}
+
+impl PrssLabel {
+    fn window(&self) -> u128 {
+        match self {
+            PrssLabel::R => 0,
+            PrssLabel::S => 1,
+            PrssLabel::OutputPad => 2,
+            PrssLabel::MacPad(j) => 3 + *j as u128,
+            PrssLabel::Mask(mask) => 6 + *mask as u128,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PrssKeys {
+    pub party_number: usize,
+
+    own_key: [u8; 32],
+
+    /// Key shared with party j at index j, the entry of the party itself is unused
+    pairwise_keys: Vec<[u8; 32]>,
+}
+
+impl PrssKeys {
+    /// Keys of all parties
+    pub fn deal(num_parties: usize) -> Vec<PrssKeys> {
+        let mut rng = rand::thread_rng();
+
+        let mut pairwise_keys = vec![vec![[0u8; 32]; num_parties]; num_parties];
+        for (i, j) in (0..num_parties).flat_map(|i| (i + 1..num_parties).map(move |j| (i, j))) {
+            let key: [u8; 32] = rng.gen();
+            pairwise_keys[i][j] = key;
+            pairwise_keys[j][i] = key;
+        }
+
+        pairwise_keys.into_iter().enumerate()
+            .map(|(party_number, pairwise_keys)| PrssKeys {
+                party_number,
+                own_key: rng.gen(),
+                pairwise_keys,
+            })
+            .collect()
+    }
+
+    fn prf(key: &[u8; 32], nonce: u64, label: PrssLabel, bits: usize) -> BigInt {
+        assert!(bits <= 32 * WORDS_PER_LABEL as usize);
+
+        let mut prg = ChaCha20Rng::from_seed(*key);
+        prg.set_stream(nonce);
+        prg.set_word_pos(label.window() * WORDS_PER_LABEL);
+
+        BigInt::from(prg.gen_biguint(bits as u64))
+    }
+
+    /// Share in [0, 2^bits) of a random value
+    pub fn random_share(&self, nonce: u64, label: PrssLabel, bits: usize) -> BigInt {
+        PrssKeys::prf(&self.own_key, nonce, label, bits)
+    }
+
+    /// Share mod 2^bits of zero
+    pub fn zero_share(&self, nonce: u64, label: PrssLabel, bits: usize) -> BigInt {
+        let modulus = BigInt::one() << bits;
+
+        self.pairwise_keys.iter().enumerate()
+            .filter(|&(j, _)| j != self.party_number)
+            .fold(BigInt::zero(), |acc, (j, key)| {
+                let value = PrssKeys::prf(key, nonce, label, bits);
+                if j > self.party_number { acc + value } else { acc - value }
+            })
+            .mod_floor(&modulus)
+    }
+
+    /// Share of the mask s in [0, D), see `open_s` for the value it belongs to
+    pub fn s_share(&self, nonce: u64, params: &PublicParameters) -> BigInt {
+        self.random_share(nonce, PrssLabel::S, params.d + 1)
+    }
+
+    /// Share of the mask r in [0, L), see `open_r` for the value it belongs to
+    pub fn r_share(&self, nonce: u64, params: &PublicParameters) -> BigInt {
+        self.random_share(nonce, PrssLabel::R, params.l)
+    }
+
+    /// Shares of the opening masks in the order of `OpeningMask::ALL`, each below 2^bits of the mask
+    pub fn mask_shares(&self, nonce: u64, params: &PublicParameters) -> DVector<BigInt> {
+        DVector::from_fn(OpeningMask::ALL.len(), |j, _| {
+            let mask = OpeningMask::ALL[j];
+            self.random_share(nonce, PrssLabel::Mask(mask), mask.bits(params))
+        })
+    }
+
+    /// Pad of the MAC on the share sent to a designated recipient, a share of zero mod 2^(k+s)
+    pub fn output_pad(&self, nonce: u64, params: &PublicParameters) -> BigInt {
+        self.zero_share(nonce, PrssLabel::OutputPad, params.mac_ks)
+    }
+
+    /// This party's three MAC pads, shares of zero mod 2^(k+s)
+    pub fn mac_pads(&self, nonce: u64, params: &PublicParameters) -> DVector<BigInt> {
+        DVector::from_fn(3, |j, _| self.zero_share(nonce, PrssLabel::MacPad(j), params.mac_ks))
+    }
+}
+
+/// s in [0, D) and the carry c for the keys of all parties. The s shares add up to s + c * D, which y' = y + s mod D
+/// does not see
+pub fn open_s(keys: &[PrssKeys], nonce: u64, params: &PublicParameters) -> (BigInt, BigInt) {
+    let sum = keys.iter().fold(BigInt::zero(), |acc, party_keys| acc + party_keys.s_share(nonce, params));
+
+    let (carry, s) = sum.div_mod_floor(&BigInt::from(params.big_d));
+    (s, carry)
+}
+
+/// r in [0, L) and the carry c for the keys of all parties.
+///
+/// The r shares add up to r + c * L rather than r. The parties use them as shares mod 2^k, so the dealer adds c to
+/// the LTZ table: e = z' - (r + c * L) + L * (u + c) is then what it would be for shares of r.
+pub fn open_r(keys: &[PrssKeys], nonce: u64, params: &PublicParameters) -> (BigInt, BigInt) {
+    let sum = keys.iter().fold(BigInt::zero(), |acc, party_keys| acc + party_keys.r_share(nonce, params));
+
+    let (carry, r) = sum.div_mod_floor(&params.big_l);
+    (r, carry)
+}
+
+/// What the mask shares of all parties add up to, in the order of `OpeningMask::ALL`. The carry above the bits of a
+/// mask vanishes once it is weighted, see `OpeningMask::weight`
+pub fn open_masks(keys: &[PrssKeys], nonce: u64, params: &PublicParameters) -> Vec<BigInt> {
+    keys.iter()
+        .map(|party_keys| party_keys.mask_shares(nonce, params))
+        .fold(vec![BigInt::zero(); OpeningMask::ALL.len()], |acc, shares| {
+            acc.into_iter().zip(shares.iter()).map(|(sum, share)| sum + share).collect()
+        })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{DMatrix, DVector};
+    use num_bigint::BigInt;
+    use num_integer::Integer;
+    use num_traits::Zero;
+    use crate::mpc::additive_sharing::AdditiveSecretSharing;
+    use crate::mpc::prss::{open_r, PrssKeys, PrssLabel};
+    use crate::mpc::public_params::PublicParameters;
+
+    #[test]
+    fn test_shares_are_consistent_and_fresh() {
+        let params = PublicParameters::init(5, 64, 2, 6, 16, 80);
+        let keys = PrssKeys::deal(params.n);
+
+        for nonce in 0..4 {
+            // rows = pads; columns = parties
+            let pads = DMatrix::from_columns(&keys.iter().map(|party_keys| party_keys.mac_pads(nonce, &params)).collect::<Vec<_>>());
+            for pad_shares in pads.row_iter() {
+                assert!(pad_shares.iter().any(|pad| !pad.is_zero()));
+                assert!(AdditiveSecretSharing::reveal(&pad_shares.transpose(), params.mac_ks).is_zero());
+            }
+
+            let (r, carry) = open_r(&keys, nonce, &params);
+            let r_shares = DVector::from_fn(params.n, |i, _| keys[i].r_share(nonce, &params));
+            assert!(r < params.big_l && carry < BigInt::from(params.n));
+            assert_eq!(AdditiveSecretSharing::reveal(&r_shares, params.k), (r + carry * &params.big_l).mod_floor(&params.q));
+        }
+
+        // Labels and nonces select independent values
+        assert_ne!(keys[0].random_share(0, PrssLabel::R, 64), keys[0].random_share(0, PrssLabel::S, 64));
+        assert_ne!(keys[0].random_share(0, PrssLabel::R, 64), keys[0].random_share(1, PrssLabel::R, 64));
+        assert_eq!(keys[0].random_share(7, PrssLabel::R, 64), keys[0].clone().random_share(7, PrssLabel::R, 64));
+    }
+}
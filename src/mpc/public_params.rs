@@ -1,8 +1,11 @@
 use std::fmt;
+use nalgebra::DVector;
 use num_bigint::BigInt;
 use num_traits::One;
+use crate::mpc::distributions::{ErrorDistribution, KeyDistribution};
+use crate::mpc::lwe_security::{estimate, LweInstance, SecurityEstimate};
 
-#[derive(Debug, Clone, Default, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct PublicParameters {
     /// Number of parties
     pub n: usize,
@@ -31,7 +34,7 @@ pub struct PublicParameters {
     /// B = 2^b
     pub big_b: usize,
 
-    /// Number of digits = ceil(l/b)
+    /// Number of digits = ceil(l/b), or (l - b')/b + 1 with a wider top digit
     pub d: usize,
 
     /// 2^(d+1)
@@ -62,6 +65,12 @@ pub struct PublicParameters {
     /// 2 ^ (mac_ks)
     pub mac_big_ks: BigInt,
 
+    /// Distribution of the LWE errors, it bounds the noise a decryption must tolerate
+    pub error_distribution: ErrorDistribution,
+
+    /// Distribution of the LWE secret key
+    pub key_distribution: KeyDistribution,
+
 }
 
 
@@ -70,6 +79,7 @@ pub struct PublicParameters {
 impl PublicParameters {
 
 
+    /// Derives the parameters without checking them, `builder` reports combinations the protocol cannot run with
     pub fn init(n:usize, k:usize, m: usize, b: usize, lwe_dimension: usize, mac_s: usize) -> PublicParameters {
         let l = k - m;
         let d = f64::ceil(l as f64 / b as f64) as usize;
@@ -114,22 +124,317 @@ impl PublicParameters {
             mac_s,
             mac_ks,
             mac_big_s,
-            mac_big_ks
+            mac_big_ks,
+            error_distribution: ErrorDistribution::wide_uniform(l),
+            key_distribution: KeyDistribution::default(),
         }
     }
 
+    /// The same noise budget cut into d - 1 digits of b bits under a top digit of `b_prime` bits, which may be wider
+    /// than b, without checking that l - b' is a multiple of b
+    pub fn with_top_digit_bits(mut self, b_prime: usize) -> PublicParameters {
+        self.d = (self.l - b_prime) / self.b + 1;
+        self.big_d = 2usize.pow(self.d as u32 + 1);
+        self.b_prime = b_prime;
+        self.big_b_prime = BigInt::one() << b_prime;
+        self
+    }
+
+    /// Rows of the sign tables, every digit of both widths indexes into them
+    pub fn sign_rows(&self) -> usize {
+        self.big_b.max(1 << self.b_prime)
+    }
+
+    /// Bits of the ring s, the sign tables and the opening of y' are authenticated in, d + 1 + s
+    pub fn mac_sign_bits(&self) -> usize {
+        self.d + 1 + self.mac_s
+    }
+
+    /// Bits of the ring the LTZ table is authenticated in, m + s. L times such a value lives in Z_2^(k+s)
+    pub fn mac_ltz_bits(&self) -> usize {
+        self.m + self.mac_s
+    }
+
+    /// The d digits of a value below L, lowest first, the top one holds the b' bits above the others
+    pub fn digits(&self, value: &BigInt) -> DVector<BigInt> {
+        let digit_mask = BigInt::from(self.big_b - 1);
+
+        DVector::from_fn(self.d, |i, _| {
+            if i + 1 < self.d {
+                (value >> (i * self.b)) & &digit_mask
+            } else {
+                value >> (i * self.b)
+            }
+        })
+    }
+
+    pub fn with_distributions(mut self, error_distribution: ErrorDistribution, key_distribution: KeyDistribution) -> PublicParameters {
+        self.error_distribution = error_distribution;
+        self.key_distribution = key_distribution;
+        self
+    }
+
+    /// Same parameters for a committee of `n` parties
+    pub fn with_committee_size(&self, n: usize) -> PublicParameters {
+        PublicParameters {
+            n,
+            ..self.clone()
+        }
+    }
+
+    /// Bound on the sum of `samples` errors
+    pub fn noise_bound(&self, samples: usize) -> BigInt {
+        self.error_distribution.bound() * samples
+    }
+
+    /// Decryption rounds to the nearest multiple of L, so the noise must stay below L/2
+    pub fn tolerates_noise(&self, samples: usize) -> bool {
+        self.noise_bound(samples) < &self.big_l / 2
+    }
+
+    /// Cost of the best known lattice attacks on the LWE ciphertexts, see `lwe_security`
+    pub fn security_estimate(&self) -> SecurityEstimate {
+        estimate(&LweInstance::from_public_parameters(self))
+    }
+}
+
+/// Widest table index the preprocessing deals, a table of 2^20 entries per party and gate
+pub const MAX_TABLE_BITS: usize = 20;
+
+/// Why a combination of parameters cannot be used
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParameterError {
+    NoParties,
+    NoPlaintextBits,
+    /// l = k - m must be at least 1
+    NoNoiseBudget { k: usize, m: usize },
+    NoDigitBits,
+    /// A digit cannot be longer than the l bits it is taken from
+    DigitBitsExceedNoiseBudget { b: usize, l: usize },
+    /// The top digit b' = l - (d - 1) * b must have between 1 and b bits
+    TopDigitOutOfRange { b_prime: i64, b: usize },
+    /// A chosen top digit leaves l - b' bits, which digits of b bits must cut exactly
+    TopDigitMisaligned { b_prime: usize, b: usize, l: usize },
+    /// The sign tables have 2^b rows
+    SignTableTooLarge { b: usize },
+    /// The LTZ table has 2^(d+1) entries, enough for the weighted signs in (-2^d, 2^d)
+    LtzTableTooLarge { d: usize },
+    /// The MAC check is only sound with s >= 1 extra bits
+    NoMacSecurity,
+    /// Below k coordinates the key of an LWE sample mod 2^k is easy to recover
+    LweDimensionTooSmall { lwe_dimension: usize, k: usize },
+    /// The errors of the distribution reach L/2, where the rounding fails
+    NoiseExceedsBudget { bound: BigInt, budget: BigInt },
+    /// The cheaper of the primal and dual attacks costs fewer operations than requested
+    InsufficientSecurity { estimated: f64, required: usize },
+}
+
+impl fmt::Display for ParameterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParameterError::NoParties =>
+                write!(f, "At least one party is needed"),
+            ParameterError::NoPlaintextBits =>
+                write!(f, "The plaintext needs at least one bit"),
+            ParameterError::NoNoiseBudget { k, m } =>
+                write!(f, "Ciphertext bits k = {} leave no noise budget over the plaintext bits m = {}", k, m),
+            ParameterError::NoDigitBits =>
+                write!(f, "Digits need at least one bit"),
+            ParameterError::DigitBitsExceedNoiseBudget { b, l } =>
+                write!(f, "Digits of b = {} bits are longer than the noise budget l = {}", b, l),
+            ParameterError::TopDigitOutOfRange { b_prime, b } =>
+                write!(f, "Top digit of {} bits is outside [1, {}]", b_prime, b),
+            ParameterError::TopDigitMisaligned { b_prime, b, l } =>
+                write!(f, "A top digit of {} bits leaves {} bits of l = {}, not a multiple of b = {}", b_prime, l.saturating_sub(*b_prime), l, b),
+            ParameterError::SignTableTooLarge { b } =>
+                write!(f, "Sign tables of 2^{} rows exceed 2^{} entries", b, MAX_TABLE_BITS),
+            ParameterError::LtzTableTooLarge { d } =>
+                write!(f, "LTZ table of 2^{} entries for {} digits exceeds 2^{} entries", d + 1, d, MAX_TABLE_BITS),
+            ParameterError::NoMacSecurity =>
+                write!(f, "MAC security parameter s must be at least 1"),
+            ParameterError::LweDimensionTooSmall { lwe_dimension, k } =>
+                write!(f, "LWE dimension {} is below the {} bits of the modulus", lwe_dimension, k),
+            ParameterError::NoiseExceedsBudget { bound, budget } =>
+                write!(f, "Errors up to {} reach the noise budget L/2 = {}", bound, budget),
+            ParameterError::InsufficientSecurity { estimated, required } =>
+                write!(f, "LWE security of about {:.1} bits is below the required {} bits", estimated, required),
+        }
+    }
+}
+
+impl std::error::Error for ParameterError {}
+
+/// Checked construction of `PublicParameters`, starting from the defaults
+#[derive(Debug, Clone, PartialEq)]
+pub struct PublicParametersBuilder {
+    n: usize,
+    k: usize,
+    m: usize,
+    b: usize,
+    b_prime: Option<usize>,
+    lwe_dimension: usize,
+    mac_s: usize,
+    /// None for the default the noise budget of k and m gives
+    error_distribution: Option<ErrorDistribution>,
+    key_distribution: KeyDistribution,
+    min_security: Option<usize>,
+}
+
+impl PublicParameters {
+    pub fn builder() -> PublicParametersBuilder {
+        let params = PublicParameters::default();
+
+        PublicParametersBuilder {
+            n: params.n,
+            k: params.k,
+            m: params.m,
+            b: params.b,
+            b_prime: None,
+            lwe_dimension: params.lwe_dimension,
+            mac_s: params.mac_s,
+            error_distribution: None,
+            key_distribution: params.key_distribution,
+            min_security: None,
+        }
+    }
+}
+
+impl PublicParametersBuilder {
+    pub fn with_parties(mut self, n: usize) -> Self {
+        self.n = n;
+        self
+    }
+
+    pub fn with_ciphertext_bits(mut self, k: usize) -> Self {
+        self.k = k;
+        self
+    }
+
+    pub fn with_plaintext_bits(mut self, m: usize) -> Self {
+        self.m = m;
+        self
+    }
+
+    pub fn with_digit_bits(mut self, b: usize) -> Self {
+        self.b = b;
+        self
+    }
+
+    /// Cuts l into d - 1 digits of b bits under a top digit of `b_prime` bits, by default the top digit takes what
+    /// ceil(l/b) digits of b bits leave
+    pub fn with_top_digit_bits(mut self, b_prime: usize) -> Self {
+        self.b_prime = Some(b_prime);
+        self
+    }
+
+    pub fn with_lwe_dimension(mut self, lwe_dimension: usize) -> Self {
+        self.lwe_dimension = lwe_dimension;
+        self
+    }
+
+    pub fn with_mac_security(mut self, mac_s: usize) -> Self {
+        self.mac_s = mac_s;
+        self
+    }
+
+    pub fn with_distributions(mut self, error_distribution: ErrorDistribution, key_distribution: KeyDistribution) -> Self {
+        self.error_distribution = Some(error_distribution);
+        self.key_distribution = key_distribution;
+        self
+    }
+
+    /// Rejects parameters whose estimated LWE security is below `bits`, not checked by default
+    pub fn with_min_security(mut self, bits: usize) -> Self {
+        self.min_security = Some(bits);
+        self
+    }
 
+    /// Checks the constraints in the order the derived values depend on each other, before anything is derived
+    pub fn build(self) -> Result<PublicParameters, ParameterError> {
+        let (n, k, m, b) = (self.n, self.k, self.m, self.b);
+
+        if n == 0 {
+            return Err(ParameterError::NoParties);
+        }
+        if m == 0 {
+            return Err(ParameterError::NoPlaintextBits);
+        }
+        if k <= m {
+            return Err(ParameterError::NoNoiseBudget { k, m });
+        }
+        let l = k - m;
+
+        if b == 0 {
+            return Err(ParameterError::NoDigitBits);
+        }
+        if b > l {
+            return Err(ParameterError::DigitBitsExceedNoiseBudget { b, l });
+        }
+        if b > MAX_TABLE_BITS {
+            return Err(ParameterError::SignTableTooLarge { b });
+        }
+
+        let d = match self.b_prime {
+            Some(b_prime) => {
+                if b_prime == 0 || b_prime > l || (l - b_prime) % b != 0 {
+                    return Err(ParameterError::TopDigitMisaligned { b_prime, b, l });
+                }
+                if b_prime > MAX_TABLE_BITS {
+                    return Err(ParameterError::SignTableTooLarge { b: b_prime });
+                }
+                (l - b_prime) / b + 1
+            }
+            None => {
+                let d = l.div_ceil(b);
+                let b_prime = l as i64 - ((d - 1) * b) as i64;
+                if b_prime < 1 || b_prime > b as i64 {
+                    return Err(ParameterError::TopDigitOutOfRange { b_prime, b });
+                }
+                d
+            }
+        };
+        if d + 1 > MAX_TABLE_BITS {
+            return Err(ParameterError::LtzTableTooLarge { d });
+        }
+
+        if self.mac_s == 0 {
+            return Err(ParameterError::NoMacSecurity);
+        }
+        if self.lwe_dimension < k {
+            return Err(ParameterError::LweDimensionTooSmall { lwe_dimension: self.lwe_dimension, k });
+        }
+
+        let mut params = PublicParameters::init(n, k, m, b, self.lwe_dimension, self.mac_s);
+        let error_distribution = self.error_distribution.unwrap_or_else(|| params.error_distribution.clone());
+        params = params.with_distributions(error_distribution, self.key_distribution);
+        if let Some(b_prime) = self.b_prime {
+            params = params.with_top_digit_bits(b_prime);
+        }
+
+        if !params.tolerates_noise(1) {
+            return Err(ParameterError::NoiseExceedsBudget { bound: params.noise_bound(1), budget: &params.big_l / 2 });
+        }
+
+        if let Some(required) = self.min_security {
+            let estimated = params.security_estimate().bits();
+            if estimated < required as f64 {
+                return Err(ParameterError::InsufficientSecurity { estimated, required });
+            }
+        }
+
+        Ok(params)
+    }
+}
+
+impl Default for PublicParameters {
     // Experimented values:
     // k = 64
     // m = 1, 2, 4
     // b = 5, 6, 7, 8, 9
-    pub fn default() -> PublicParameters {
+    fn default() -> PublicParameters {
         let (n, k, m, b, lwe_dimension, mac_s) = (4, 64, 4, 7, 1024, 80);
         PublicParameters::init(n, k, m, b, lwe_dimension, mac_s)
     }
-
-
-
 }
 
 impl fmt::Display for PublicParameters {
@@ -146,12 +451,14 @@ impl fmt::Display for PublicParameters {
                 L: {}\t L = 2^l\n
                 b: {}\t 'Digit' bit length\n
                 B: {}\t B = 2^b\n
-                d: {}\t Number of digits d = ceil(l/b)\n
+                d: {}\t Number of digits d = (l - b')/b + 1\n
                 D: {}\t 2^(d+1)\n
                 b': {}\t Top digit bit length b' = l - (d - 1)*b\n
                 B': {}\t B' = 2^b'\n
                 lwe_dimension: {} LWE scheme key size\n
                 mac_ks: {}\t TODO + MAC scheme security parameter\n
+                error_distribution: {:?}\n
+                key_distribution: {:?}\n
             }}",
             self.n,
             self.k,
@@ -167,7 +474,67 @@ impl fmt::Display for PublicParameters {
             self.b_prime,
             self.big_b_prime,
             self.lwe_dimension,
-            self.mac_ks
+            self.mac_ks,
+            self.error_distribution,
+            self.key_distribution
         )
     }
 }
+
+
+#[cfg(test)]
+mod tests {
+    use num_bigint::BigInt;
+    use crate::mpc::distributions::{ErrorDistribution, KeyDistribution};
+    use crate::mpc::public_params::{ParameterError, PublicParameters};
+
+    #[test]
+    fn test_builder_reports_every_constraint() {
+        let builder = PublicParameters::builder();
+        assert_eq!(builder.clone().build(), Ok(PublicParameters::default()));
+
+        let params = builder.clone().with_parties(3).with_ciphertext_bits(32).with_plaintext_bits(2)
+            .with_digit_bits(6).with_lwe_dimension(64).with_mac_security(40).build().unwrap();
+        assert_eq!(params, PublicParameters::init(3, 32, 2, 6, 64, 40));
+
+        let cases = [
+            (builder.clone().with_parties(0), ParameterError::NoParties),
+            (builder.clone().with_plaintext_bits(0), ParameterError::NoPlaintextBits),
+            (builder.clone().with_plaintext_bits(64), ParameterError::NoNoiseBudget { k: 64, m: 64 }),
+            (builder.clone().with_digit_bits(0), ParameterError::NoDigitBits),
+            (builder.clone().with_plaintext_bits(60).with_digit_bits(5), ParameterError::DigitBitsExceedNoiseBudget { b: 5, l: 4 }),
+            (builder.clone().with_digit_bits(24), ParameterError::SignTableTooLarge { b: 24 }),
+            (builder.clone().with_digit_bits(1), ParameterError::LtzTableTooLarge { d: 60 }),
+            (builder.clone().with_mac_security(0), ParameterError::NoMacSecurity),
+            (builder.clone().with_lwe_dimension(16), ParameterError::LweDimensionTooSmall { lwe_dimension: 16, k: 64 }),
+            (builder.clone().with_top_digit_bits(5), ParameterError::TopDigitMisaligned { b_prime: 5, b: 7, l: 60 }),
+            (builder.clone().with_digit_bits(20).with_top_digit_bits(0), ParameterError::TopDigitMisaligned { b_prime: 0, b: 20, l: 60 }),
+            (builder.clone().with_digit_bits(2).with_top_digit_bits(22), ParameterError::SignTableTooLarge { b: 22 }),
+        ];
+        for (builder, error) in cases {
+            assert_eq!(builder.build(), Err(error));
+        }
+
+        // 48 bits in digits of 6 under a top digit of 12 bits
+        let mixed = builder.clone().with_digit_bits(6).with_top_digit_bits(12).build().unwrap();
+        assert_eq!((mixed.d, mixed.big_d, mixed.b_prime, mixed.sign_rows()), (9, 1 << 10, 12, 1 << 12));
+        let value = (BigInt::from(0xabc) << 48) + 0x3f;
+        let digits = mixed.digits(&value);
+        assert_eq!((digits[0].clone(), digits[1].clone(), digits[8].clone()), (BigInt::from(0x3f), BigInt::from(0), BigInt::from(0xabc)));
+
+        let too_wide = ErrorDistribution::Uniform { bound: BigInt::from(1) << 30 };
+        let result = builder.with_ciphertext_bits(32).with_lwe_dimension(64)
+            .with_distributions(too_wide, KeyDistribution::default())
+            .build();
+        assert!(matches!(result, Err(ParameterError::NoiseExceedsBudget { .. })));
+
+        // With the narrow error of the homomorphic encryption standard 2^10 coordinates mod 2^64 are far below 128
+        // bits, 2^12 are above, the default wide error is secure at 2^10
+        let narrow = PublicParameters::builder().with_min_security(128)
+            .with_distributions(ErrorDistribution::RoundedGaussian { sigma: 3.2 }, KeyDistribution::default());
+        let result = narrow.clone().build();
+        assert!(matches!(result, Err(ParameterError::InsufficientSecurity { required: 128, .. })));
+        assert!(narrow.with_lwe_dimension(4096).build().is_ok());
+        assert!(PublicParameters::builder().with_min_security(128).build().is_ok());
+    }
+}
@@ -0,0 +1,85 @@
+use nalgebra::{DMatrix, DVector};
+use num_bigint::BigInt;
+use num_integer::Integer;
+use crate::mpc::additive_sharing::AdditiveSecretSharing;
+use crate::mpc::public_params::PublicParameters;
+
+// Moves additive sk shares from an old committee to a new one of a different size.
+//
+// Every old member splits its share into additive pieces, one per new member. A new member adds up the pieces
+// it got from all old members. No one reconstructs the key, and the new shares are independent of the old ones.
+// Shares live mod 2^(k+s), so the MAC shares of sk move to the new committee the same way.
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Resharing {
+    pub old_params: PublicParameters,
+    pub new_params: PublicParameters,
+}
+
+impl Resharing {
+    pub fn new(old_params: &PublicParameters, new_committee_size: usize) -> Resharing {
+        assert!(new_committee_size >= 1);
+
+        Resharing {
+            old_params: old_params.clone(),
+            new_params: old_params.with_committee_size(new_committee_size),
+        }
+    }
+
+    /// Pieces of the share of an old member, piece j is meant for new member j
+    pub fn split(&self, sk: &DVector<BigInt>) -> Vec<DVector<BigInt>> {
+        // rows = key coordinates; columns = new members
+        let pieces = DMatrix::from_rows(&sk.iter()
+            .map(|sk_digit| AdditiveSecretSharing::share(sk_digit, self.new_params.n, self.new_params.mac_ks).transpose())
+            .collect::<Vec<_>>());
+
+        pieces.column_iter().map(|piece| piece.into()).collect()
+    }
+
+    /// Share of a new member from the pieces of every old member
+    pub fn combine(&self, pieces: &[DVector<BigInt>]) -> DVector<BigInt> {
+        assert_eq!(pieces.len(), self.old_params.n, "Resharing needs a piece from every old member");
+
+        let dimension = pieces[0].nrows();
+        pieces.iter()
+            .fold(DVector::zeros(dimension), |acc, piece| acc + piece)
+            .map(|x| x.mod_floor(&self.new_params.mac_big_ks))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::DVector;
+    use num_bigint::BigInt;
+    use num_integer::Integer;
+    use crate::mpc::additive_sharing::AdditiveSecretSharing;
+    use crate::mpc::public_params::PublicParameters;
+    use crate::mpc::resharing::Resharing;
+
+    #[test]
+    fn test_reshare_keeps_the_key() {
+        for (old_n, new_n) in [(4, 8), (8, 3), (3, 3), (2, 1)] {
+            let params = PublicParameters::init(old_n, 32, 1, 6, 8, 0);
+            let resharing = Resharing::new(&params, new_n);
+
+            let sk = DVector::from_fn(params.lwe_dimension, |i, _| BigInt::from(i) - 4);
+            let old_shares: Vec<DVector<BigInt>> = {
+                let columns: Vec<_> = sk.iter().map(|x| AdditiveSecretSharing::share(x, old_n, params.k)).collect();
+                (0..old_n).map(|i| DVector::from_fn(params.lwe_dimension, |j, _| columns[j][i].clone())).collect()
+            };
+
+            let pieces_per_sender: Vec<Vec<DVector<BigInt>>> = old_shares.iter().map(|share| resharing.split(share)).collect();
+            let new_shares: Vec<DVector<BigInt>> = (0..new_n)
+                .map(|j| resharing.combine(&pieces_per_sender.iter().map(|pieces| pieces[j].clone()).collect::<Vec<_>>()))
+                .collect();
+
+            let revealed = new_shares.iter()
+                .fold(DVector::zeros(params.lwe_dimension), |acc, share| acc + share)
+                .map(|x| x.mod_floor(&params.q));
+
+            assert_eq!(new_shares.len(), new_n);
+            assert_eq!(revealed, sk.map(|x| x.mod_floor(&params.q)));
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use std::fmt::Debug;
+use std::num::Wrapping;
+use std::ops::{Add, Mul, Neg, Shl, Shr, Sub};
+use num_bigint::BigInt;
+use num_integer::Integer;
+use num_traits::{One, ToPrimitive, Zero};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+// Elements of the rings Z_2^k the online phase computes in.
+//
+// Every modulus of the protocol is a power of two, so a u128 that wraps at 2^128 computes mod 2^k for any smaller k,
+// as long as values are cut to k bits before they are compared or opened. Parties run on Z128 whenever k + s fits,
+// BigInt does not wrap and is kept as the reference implementation and for wider MAC rings. `Party` is generic over
+// the ring element, so every backend runs the same online phase; the preprocessing is dealt in BigInt and lifted into
+// the backend when a party takes it.
+
+/// Z_2^128 in two machine words
+pub type Z128 = Wrapping<u128>;
+
+pub trait RingElement: Clone + Debug + PartialEq + Zero + One + Send + Sync + 'static + Serialize + DeserializeOwned
+    + Add<Output = Self> + Sub<Output = Self> + Mul<Output = Self> + Neg<Output = Self>
+    + Shl<usize, Output = Self> + Shr<usize, Output = Self>
+{
+    /// Bits of the widest ring the type computes in, None when it never wraps
+    const CAPACITY: Option<usize>;
+
+    /// x mod 2^CAPACITY
+    fn from_bigint(x: &BigInt) -> Self;
+
+    /// Representative in [0, 2^CAPACITY), or the value itself when the type does not wrap
+    fn to_bigint(&self) -> BigInt;
+
+    /// Representative in [0, 2^bits)
+    fn reduce(self, bits: usize) -> Self;
+
+    /// Value of a representative known to be small, e.g. a digit or a table index
+    fn to_usize(&self) -> usize;
+
+    /// Whether the type computes mod 2^bits
+    fn fits(bits: usize) -> bool {
+        Self::CAPACITY.is_none_or(|capacity| bits <= capacity)
+    }
+}
+
+impl RingElement for Z128 {
+    const CAPACITY: Option<usize> = Some(u128::BITS as usize);
+
+    fn from_bigint(x: &BigInt) -> Self {
+        let modulus = BigInt::one() << u128::BITS;
+        Wrapping(x.mod_floor(&modulus).to_u128().unwrap())
+    }
+
+    fn to_bigint(&self) -> BigInt {
+        BigInt::from(self.0)
+    }
+
+    fn reduce(self, bits: usize) -> Self {
+        if bits >= u128::BITS as usize {
+            self
+        } else {
+            Wrapping(self.0 & ((1 << bits) - 1))
+        }
+    }
+
+    fn to_usize(&self) -> usize {
+        self.0 as usize
+    }
+}
+
+impl RingElement for BigInt {
+    const CAPACITY: Option<usize> = None;
+
+    fn from_bigint(x: &BigInt) -> Self {
+        x.clone()
+    }
+
+    fn to_bigint(&self) -> BigInt {
+        self.clone()
+    }
+
+    fn reduce(self, bits: usize) -> Self {
+        self.mod_floor(&(BigInt::one() << bits))
+    }
+
+    fn to_usize(&self) -> usize {
+        ToPrimitive::to_usize(self).unwrap()
+    }
+}
+
+/// Moves a value of Z_2^k into a wider ring, keeping its representative in [0, 2^k)
+pub fn lift<R: RingElement, M: RingElement>(x: &R) -> M {
+    M::from_bigint(&x.to_bigint())
+}
+
+/// sum_i x_i * y_i, wrapping at the capacity of R
+pub fn dot<R: RingElement>(x: &[R], y: &[R]) -> R {
+    assert_eq!(x.len(), y.len());
+
+    x.iter().zip(y).fold(R::zero(), |acc, (x_i, y_i)| acc + x_i.clone() * y_i.clone())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use num_bigint::{BigInt, UniformBigInt};
+    use num_integer::Integer;
+    use num_traits::{One, Zero};
+    use rand::distributions::uniform::UniformSampler;
+    use crate::mpc::ring::{dot, RingElement, Z128};
+
+    fn agrees_with_bigint<R: RingElement>(bits: usize) {
+        let mut rng = rand::thread_rng();
+        let modulus = BigInt::one() << bits;
+        let sample = |rng: &mut _| UniformBigInt::new(-&modulus, &modulus).sample(rng);
+
+        let x: Vec<BigInt> = (0..50).map(|_| sample(&mut rng)).collect();
+        let y: Vec<BigInt> = (0..50).map(|_| sample(&mut rng)).collect();
+
+        let x_ring: Vec<R> = x.iter().map(R::from_bigint).collect();
+        let y_ring: Vec<R> = y.iter().map(R::from_bigint).collect();
+
+        let expected = x.iter().zip(&y).fold(BigInt::zero(), |acc, (x_i, y_i)| acc + x_i * y_i).mod_floor(&modulus);
+        assert_eq!(dot(&x_ring, &y_ring).reduce(bits).to_bigint(), expected);
+
+        let expected = (&x[0] - &y[0]).mod_floor(&modulus) >> (bits / 2);
+        assert_eq!((x_ring[0].clone() - y_ring[0].clone()).reduce(bits) >> (bits / 2), R::from_bigint(&expected));
+    }
+
+    #[test]
+    fn test_wrapping_rings_agree_with_bigint() {
+        for bits in [1, 17, 63, 64] {
+            agrees_with_bigint::<Z128>(bits);
+            agrees_with_bigint::<BigInt>(bits);
+        }
+        agrees_with_bigint::<Z128>(104);
+        agrees_with_bigint::<Z128>(128);
+
+        assert!(Z128::fits(128) && !Z128::fits(129));
+        assert!(BigInt::fits(144));
+    }
+}
@@ -0,0 +1,20 @@
+use num_bigint::BigInt;
+
+/// Linear secret sharing of values mod 2^k among `num_parties()` parties, numbered from 0
+pub trait SecretSharing {
+    type Share: Clone;
+
+    fn num_parties(&self) -> usize;
+
+    /// Smallest number of parties able to reconstruct, or to decrypt together
+    fn reconstruction_threshold(&self) -> usize;
+
+    fn share_secret(&self, secret: &BigInt) -> Vec<Self::Share>;
+
+    /// Reconstructs from (party number, share) pairs, None when the shares are too few or inconsistent
+    fn reconstruct(&self, shares: &[(usize, Self::Share)]) -> Option<BigInt>;
+
+    /// Turns the share of `party` into an additive share mod 2^k among `parties`, the additive shares of all
+    /// parties in `parties` sum up to the secret
+    fn to_additive(&self, party: usize, share: &Self::Share, parties: &[usize]) -> BigInt;
+}
@@ -0,0 +1,173 @@
+use nalgebra::DVector;
+use num_bigint::BigInt;
+use num_traits::Zero;
+use crate::mpc::galois_ring::GaloisRing;
+use crate::mpc::secret_sharing::SecretSharing;
+
+/// t-of-n Shamir sharing over GR(2^k, D): any t + 1 shares reconstruct, any t shares reveal nothing.
+/// Party i holds P(x_(i+1)) with x_j the exceptional points of the ring and P(0) the secret
+#[derive(Clone, Debug, PartialEq)]
+pub struct ShamirSecretSharing {
+    pub ring: GaloisRing,
+    pub num_parties: usize,
+
+    /// Degree t of the sharing polynomial
+    pub threshold: usize,
+}
+
+impl ShamirSecretSharing {
+    pub fn new(num_parties: usize, threshold: usize, ring_exponent: usize) -> ShamirSecretSharing {
+        assert!(threshold < num_parties, "Shamir sharing requires t < n");
+
+        ShamirSecretSharing {
+            ring: GaloisRing::for_parties(ring_exponent, num_parties),
+            num_parties,
+            threshold,
+        }
+    }
+
+    fn evaluation_point(&self, party: usize) -> DVector<BigInt> {
+        assert!(party < self.num_parties);
+        self.ring.exceptional_point(party + 1)
+    }
+
+    /// lambda_i = prod_(j != i) x_j / (x_j - x_i), so that P(0) = sum_i lambda_i * P(x_i)
+    pub fn lagrange_coefficient(&self, party: usize, parties: &[usize]) -> DVector<BigInt> {
+        let x_i = self.evaluation_point(party);
+
+        parties.iter()
+            .filter(|&&j| j != party)
+            .fold(self.ring.one(), |acc, &j| {
+                let x_j = self.evaluation_point(j);
+                let denominator = self.ring.inverse(&self.ring.sub(&x_j, &x_i)).unwrap();
+                self.ring.mul(&acc, &self.ring.mul(&x_j, &denominator))
+            })
+    }
+
+    /// `to_additive` of every share of `party` at once, with a single Lagrange coefficient
+    pub fn to_additive_all(&self, party: usize, shares: &[DVector<BigInt>], parties: &[usize]) -> DVector<BigInt> {
+        assert!(parties.len() >= self.reconstruction_threshold());
+        assert!(parties.contains(&party));
+
+        let lambda = self.lagrange_coefficient(party, parties);
+        DVector::from_iterator(shares.len(), shares.iter()
+            .map(|share| self.ring.constant_term(&self.ring.mul(&lambda, share))))
+    }
+}
+
+impl SecretSharing for ShamirSecretSharing {
+    type Share = DVector<BigInt>;
+
+    fn num_parties(&self) -> usize {
+        self.num_parties
+    }
+
+    fn reconstruction_threshold(&self) -> usize {
+        self.threshold + 1
+    }
+
+    fn share_secret(&self, secret: &BigInt) -> Vec<DVector<BigInt>> {
+        let mut rng = rand::thread_rng();
+
+        let mut coefficients = vec![self.ring.from_int(secret)];
+        coefficients.extend((0..self.threshold).map(|_| self.ring.random(&mut rng)));
+
+        (0..self.num_parties)
+            .map(|party| {
+                // Horner evaluation of P(x_i)
+                let x = self.evaluation_point(party);
+                coefficients.iter().rev().fold(self.ring.zero(), |acc, coefficient| {
+                    self.ring.add(&self.ring.mul(&acc, &x), coefficient)
+                })
+            })
+            .collect()
+    }
+
+    fn reconstruct(&self, shares: &[(usize, DVector<BigInt>)]) -> Option<BigInt> {
+        let mut parties: Vec<usize> = shares.iter().map(|(party, _)| *party).collect();
+        parties.sort();
+        parties.dedup();
+
+        if parties.len() != shares.len() || parties.len() < self.reconstruction_threshold() {
+            return None;
+        }
+
+        let secret = shares.iter().fold(self.ring.zero(), |acc, (party, share)| {
+            self.ring.add(&acc, &self.ring.mul(&self.lagrange_coefficient(*party, &parties), share))
+        });
+
+        // A consistent sharing interpolates to a constant
+        if secret.iter().skip(1).any(|coefficient| !coefficient.is_zero()) {
+            return None;
+        }
+
+        Some(self.ring.constant_term(&secret))
+    }
+
+    fn to_additive(&self, party: usize, share: &DVector<BigInt>, parties: &[usize]) -> BigInt {
+        assert!(parties.len() >= self.reconstruction_threshold());
+        assert!(parties.contains(&party));
+
+        // Ring addition works coefficient by coefficient, so the constant terms add up to the secret
+        self.ring.constant_term(&self.ring.mul(&self.lagrange_coefficient(party, parties), share))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use num_bigint::{BigInt, UniformBigInt};
+    use num_integer::Integer;
+    use num_traits::{One, Zero};
+    use rand::distributions::uniform::UniformSampler;
+    use crate::mpc::secret_sharing::SecretSharing;
+    use crate::mpc::shamir_sharing::ShamirSecretSharing;
+
+    #[test]
+    fn test_any_t_plus_one_shares_reconstruct() {
+        let scheme = ShamirSecretSharing::new(5, 2, 64);
+        assert_eq!(scheme.ring.degree, 3);
+
+        for s in [-257, 0, 5, 1i64 << 40] {
+            let secret = BigInt::from(s);
+            let expected = secret.mod_floor(&(BigInt::one() << 64));
+            let shares = scheme.share_secret(&secret);
+
+            for parties in [vec![0, 1, 2], vec![4, 2, 0], vec![1, 3, 4], vec![0, 1, 2, 3, 4]] {
+                let subset: Vec<_> = parties.iter().map(|&i| (i, shares[i].clone())).collect();
+                assert_eq!(scheme.reconstruct(&subset), Some(expected.clone()));
+            }
+
+            // Too few or repeated parties
+            assert_eq!(scheme.reconstruct(&[(0, shares[0].clone()), (3, shares[3].clone())]), None);
+            assert_eq!(scheme.reconstruct(&[(0, shares[0].clone()), (0, shares[0].clone()), (3, shares[3].clone())]), None);
+        }
+    }
+
+    #[test]
+    fn test_corrupted_share_is_inconsistent() {
+        let scheme = ShamirSecretSharing::new(4, 1, 32);
+        let mut shares = scheme.share_secret(&BigInt::from(1234));
+        shares[2][1] += 1;
+
+        let all: Vec<_> = shares.iter().cloned().enumerate().collect();
+        assert_eq!(scheme.reconstruct(&all), None);
+    }
+
+    #[test]
+    fn test_to_additive_sums_to_secret() {
+        let mut rng = rand::thread_rng();
+        let q = BigInt::one() << 32;
+        let scheme = ShamirSecretSharing::new(7, 3, 32);
+
+        let secret = UniformBigInt::new(BigInt::zero(), &q).sample(&mut rng);
+        let shares = scheme.share_secret(&secret);
+
+        for parties in [vec![0, 1, 2, 3], vec![6, 4, 2, 0], vec![1, 2, 3, 4, 5, 6]] {
+            let sum = parties.iter()
+                .fold(BigInt::zero(), |acc, &i| acc + scheme.to_additive(i, &shares[i], &parties))
+                .mod_floor(&q);
+            assert_eq!(sum, secret);
+        }
+    }
+}
@@ -6,8 +6,8 @@ use num_traits::{One, Signed};
 
 #[macro_export]
 macro_rules! generate_getters_and_setters {
-    ($struct_name:ident, $( $field_name:ident : $field_type:ty ),* ) => {
-        impl $struct_name {
+    ($struct_name:ident $(< $param:ident : $bound:path >)?, $( $field_name:ident : $field_type:ty ),* ) => {
+        impl $(< $param: $bound >)? $struct_name $(< $param >)? {
             paste! {
                 $(
                     // Setter
@@ -1,6 +1,9 @@
 use serde::{Serialize, Deserialize};
 
 use std::net::{SocketAddr};
+use nalgebra::{DMatrix, DVector};
+use num_bigint::BigInt;
+use crate::mpc::distributed_preprocessing::PreprocessingMessage;
 use crate::network::{ProtocolTransferredData};
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -8,22 +11,88 @@ pub enum Message {
     // To DiscoveryServer
     RegisterParticipant(String, SocketAddr),
     UnregisterParticipant(String),
+    /// Hands the key over to a committee of this many participants
+    ReconfigureCommittee(usize),
+    /// The participant stored its share of the key for the new committee
+    ReshareDone(usize),
+    /// Published part b_i of the public samples from the key generation
+    PublicKeyShare(usize, DVector<BigInt>),
+    /// The participant finished a job, after this many microseconds online
+    JobDone(usize, u64, u64),
+    /// The participant stored its key share of the refresh epoch
+    KeyRefreshDone(usize, u64),
 
     // From DiscoveryServer
     ParticipantList(Vec<(String, SocketAddr)>),
     ParticipantNotificationAdded(String, SocketAddr),
     ParticipantNotificationRemoved(String),
+    /// Privacy threshold and instances of the distributed preprocessing, and the jobs that consume them
+    PreprocessingStart(usize, usize, JobAssignment),
+    /// Old and new committee sizes, participants of the old committee send their pieces to the new one
+    ReshareStart(usize, usize),
+    /// Public matrix A, every participant generates its own key share
+    KeyGenerationStart(DMatrix<BigInt>),
+    /// Refresh epoch every participant sends its zero-sharing pieces for, no job is running meanwhile
+    KeyRefreshStart(u64),
+    /// Participants of a threshold committee in party order, each turns its Shamir share of the key into an additive
+    /// share among them
+    CommitteeMembers(Vec<usize>),
+
+    ProtocolStart(JobAssignment),
 
     // From Participant to Participant
-    ProtocolStart,
 
     ProtocolExecuteStep(usize, usize, Vec<ProtocolTransferredData>, u64),
 
+    PreprocessingStep(usize, usize, PreprocessingMessage),
+
+    /// Sender, refresh epoch and the sender's zero-sharing pieces of the key share and of its MACs
+    KeyRefreshPiece(usize, u64, DVector<BigInt>, DVector<BigInt>),
+
+    /// Sender and its pieces of the key share and of its MACs
+    ResharePiece(usize, DVector<BigInt>, DVector<BigInt>),
+
+    /// Sender and its generated key share minus its input mask, the sum over all senders turns the MACs of the masks
+    /// into MACs of the key
+    KeyInput(usize, DVector<BigInt>),
+
+
+}
+
+/// Jobs the discovery server starts, job `first_job + i` decrypts with the preprocessing instances from
+/// `first_instance + i * ctxt_per_job` on. Job ids keep increasing for as long as the server runs, instance indices
+/// start over with the stores of every new committee
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct JobAssignment {
+    pub first_job: u64,
+    pub jobs: usize,
+    pub first_instance: usize,
+}
 
+impl JobAssignment {
+    /// Job ids with the index of the first instance of each
+    pub fn jobs(&self, ctxt_per_job: usize) -> impl Iterator<Item = (u64, usize)> {
+        let (first_job, first_instance) = (self.first_job, self.first_instance);
+        (0..self.jobs).map(move |i| (first_job + i as u64, first_instance + i * ctxt_per_job))
+    }
+
+    /// Id of the job after the last one
+    pub fn end(&self) -> u64 {
+        self.first_job + self.jobs as u64
+    }
 }
 
 pub const DISCOVERY_SERVER: &str = "DISCOVERY_SERVER";
 
-pub const STEP_COUNT: usize = 5;
+pub const STEP_COUNT: usize = 6;
+
+/// Decryption protocol the participants run for their jobs
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DecryptionProtocol {
+    /// The protocol of `Party`, with preprocessing and MACs
+    Rounds,
+    /// Partial decryptions flooded with noise of this many bits, see `NoiseFloodingParty`
+    NoiseFlooding(usize),
+}
 
 
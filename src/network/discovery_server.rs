@@ -1,47 +1,121 @@
-use super::common::{Message};
+use super::common::{JobAssignment, Message};
 
 use message_io::network::{NetEvent, Transport, Endpoint};
-use message_io::node::{self, NodeHandler, NodeListener};
+use message_io::node::{self, NodeEvent, NodeHandler, NodeListener};
 
 use std::net::{SocketAddr};
-use std::collections::{HashMap};
+use std::collections::{HashMap, HashSet};
 use std::fs::{create_dir_all, File};
 use std::io::{self, Write};
+use std::ops::Range;
 use std::path::Path;
 use std::process::exit;
-use std::str::FromStr;
 use std::{thread};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use log::debug;
 use nalgebra::{DMatrix, DVector};
 use num_bigint::{BigInt, UniformBigInt};
 use num_traits::Zero;
 use rand::distributions::uniform::UniformSampler;
 use crate::mpc::additive_sharing::AdditiveSecretSharing;
-use crate::mpc::lwe_scheme::init_lwe_with_random_ptxt;
+use crate::mpc::key_generation::DistributedKeyGeneration;
+use crate::mpc::lwe_scheme::{init_lwe_with_parameters, LweParameters};
 use crate::mpc::preprocessing::Preprocessing;
+use crate::mpc::prss::PrssKeys;
 use crate::mpc::public_params::PublicParameters;
+use crate::mpc::resharing::Resharing;
+use crate::mpc::secret_sharing::SecretSharing;
+use crate::mpc::shamir_sharing::ShamirSecretSharing;
 use crate::network::ProtocolTransferredData;
+use crate::network::preprocessing_producer::{Dealer, POLL_INTERVAL};
+use crate::network::preprocessing_store::PreprocessingStore;
+use crate::network::storage::{encode_participant_data, encode_value, ContentType};
+use crate::network::worker::ThresholdKeyShare;
 
 use bitcode::{serialize, deserialize};
 
+/// Rows of the public key built by the distributed key generation, the fewest that hide the message of a ciphertext
+pub fn public_key_rows(params: &PublicParameters) -> usize {
+    LweParameters::from_public_parameters(params, 0).min_public_key_rows()
+}
+
+/// Where the key shares of a committee come from
+enum KeySource<'a> {
+    /// Dealt by the server, rows = parties; columns = LWE dimension
+    Dealt(&'a DMatrix<BigInt>),
+    /// Generated by the members, who input them with the masks in their data files
+    Generated,
+    /// Moved over from the old committee by the members themselves
+    Reshared,
+    /// Turned into additive shares among the committee by the members themselves, from their Shamir shares
+    Threshold,
+}
+
+/// Timers of the discovery server
+enum Signal {
+    /// Jobs wait for the producer to top up the stores
+    RetryWaitingJobs,
+    KeyRefreshDue,
+}
+
 struct ParticipantInfo {
     addr: SocketAddr,
     endpoint: Endpoint,
 }
 
 pub struct DiscoveryServer {
-    handler: NodeHandler<()>,
-    node_listener: Option<NodeListener<()>>,
+    handler: NodeHandler<Signal>,
+    node_listener: Option<NodeListener<Signal>>,
     participants: HashMap<String, ParticipantInfo>,
     params: PublicParameters,
     preprocessing: Preprocessing,
+    /// Set when the participants generate s, r and the tables themselves
+    privacy_threshold: Option<usize>,
+    /// Jobs started on a fresh store, their instances are dealt up front, one per ciphertext
+    jobs_per_worker: usize,
+    ctxt_per_job: usize,
+    /// Job ids are never reused, so that no step of an earlier job is taken for one of a later job
+    next_job: u64,
+    /// First instance of the next job in the stores of the committee
+    next_instance: usize,
+    /// Instances and the online time in microseconds of the members that finished, per running job
+    running_jobs: HashMap<u64, (Range<usize>, HashMap<usize, u64>)>,
+    /// Jobs to start once the producer topped up the stores
+    waiting_jobs: usize,
+    retry_scheduled: bool,
+    /// Time between the end of a key refresh and the next one, never refreshed when None
+    key_refresh_interval: Option<Duration>,
+    key_refresh_epoch: u64,
+    /// The interval passed, no job starts until the refresh ran
+    key_refresh_due: bool,
+    /// Members that stored their key share of the running refresh, None when none runs
+    key_refreshed: Option<HashSet<usize>>,
+    /// Set when the participants generate the key themselves
+    key_generation: Option<DistributedKeyGeneration>,
+    public_key_shares: HashMap<usize, DVector<BigInt>>,
+    distributed_key_generation: bool,
+    /// Serialized (a, b) of the ciphertext handed to every committee
+    ciphertext: Option<(Vec<u8>, Vec<u8>)>,
+    committee_set_up: bool,
+    /// Set when the key is dealt as Shamir shares, so that any t + 1 registered participants decrypt
+    threshold_scheme: Option<ShamirSecretSharing>,
+    /// Participants of the threshold committee in party order, None while too few are registered
+    committee: Option<Vec<usize>>,
+    /// Pending handover of the key to a committee of another size
+    committee_change: Option<Resharing>,
+    resharing_started: bool,
+    /// New committee members that stored their key share
+    reshared: HashSet<usize>,
+    /// Shared with a producer that tops up the stores of the committee
+    preprocessing_dealer: Option<Dealer>,
+    /// Preprocessing dealt to the staged stores, handed to the producer once the committee changed
+    staged_preprocessing: Option<Preprocessing>,
     // start_time: Option<Instant>,
 }
 
 impl DiscoveryServer {
     pub fn new(public_parameters: &PublicParameters, preprocessing: &Preprocessing) -> io::Result<DiscoveryServer> {
-        let (handler, node_listener) = node::split::<()>();
+        let (handler, node_listener) = node::split::<Signal>();
 
         let listen_addr = "127.0.0.1:5000";
         handler.network().listen(Transport::FramedTcp, listen_addr)?;
@@ -54,18 +128,95 @@ impl DiscoveryServer {
             participants: HashMap::new(),
             preprocessing: preprocessing.clone(),
             params: public_parameters.clone(),
+            privacy_threshold: None,
+            jobs_per_worker: 1,
+            ctxt_per_job: 1,
+            next_job: 0,
+            next_instance: 0,
+            running_jobs: HashMap::new(),
+            waiting_jobs: 0,
+            retry_scheduled: false,
+            key_refresh_interval: None,
+            key_refresh_epoch: 0,
+            key_refresh_due: false,
+            key_refreshed: None,
+            key_generation: None,
+            public_key_shares: HashMap::new(),
+            distributed_key_generation: false,
+            ciphertext: None,
+            committee_set_up: false,
+            threshold_scheme: None,
+            committee: None,
+            committee_change: None,
+            resharing_started: false,
+            reshared: HashSet::new(),
+            preprocessing_dealer: None,
+            staged_preprocessing: None,
             // start_time: None,
         })
     }
 
+    /// Hands out only the key shares and lets the participants run the dealer-free preprocessing
+    pub fn with_distributed_preprocessing(mut self, privacy_threshold: usize) -> DiscoveryServer {
+        self.privacy_threshold = Some(privacy_threshold);
+        self
+    }
+
+    /// Lets the participants generate the key, no process ever holds the whole key
+    pub fn with_distributed_key_generation(mut self) -> DiscoveryServer {
+        self.distributed_key_generation = true;
+        self
+    }
+
+    /// Deals the key as t-of-n Shamir shares. Participants that leave are dropped from the committee between jobs,
+    /// which keeps decrypting for as long as t + 1 of them are registered, and those that come back join it again
+    pub fn with_threshold(mut self, threshold: usize) -> DiscoveryServer {
+        self.threshold_scheme = Some(ShamirSecretSharing::new(self.params.n, threshold, self.params.mac_ks));
+        self
+    }
+
+    /// Lets every committee run `jobs_per_worker` jobs of `ctxt_per_job` ciphertexts at a time
+    pub fn with_jobs(mut self, jobs_per_worker: usize, ctxt_per_job: usize) -> DiscoveryServer {
+        self.jobs_per_worker = jobs_per_worker;
+        self.ctxt_per_job = ctxt_per_job;
+        self
+    }
+
+    /// Refreshes the key shares of the committee every `interval`, at a moment when no job is running, so that no job
+    /// combines shares of two epochs. Jobs that would start meanwhile wait for the refresh
+    pub fn with_key_refresh_interval(mut self, interval: Duration) -> DiscoveryServer {
+        self.key_refresh_interval = Some(interval);
+        self
+    }
+
+    /// Hands the preprocessing dealt to every committee to a `PreprocessingProducer`, which keeps dealing batches of
+    /// it while the committee decrypts. Every finished job is followed by another one on the instances of the next
+    /// batch. Must not be combined with distributed preprocessing, which leaves the producer nothing to deal
+    pub fn with_preprocessing_producer(mut self, dealer: Dealer) -> DiscoveryServer {
+        self.preprocessing_dealer = Some(dealer);
+        self
+    }
+
 
     pub fn run(mut self) {
         let node_listener = self.node_listener.take().unwrap();
-        node_listener.for_each(move |event| match event.network() {
-            NetEvent::Connected(_, _) => unreachable!(), // There is no connect() calls.
-            NetEvent::Accepted(_, _) => (),              // All endpoint accepted
-            NetEvent::Message(endpoint, input_data) => {
-                let message: Message = deserialize(&input_data).unwrap();
+        if let Some(interval) = self.key_refresh_interval {
+            self.handler.signals().send_with_timer(Signal::KeyRefreshDue, interval);
+        }
+
+        node_listener.for_each(move |event| match event {
+            NodeEvent::Signal(Signal::RetryWaitingJobs) => {
+                self.retry_scheduled = false;
+                self.start_waiting_jobs();
+            }
+            NodeEvent::Signal(Signal::KeyRefreshDue) => {
+                self.key_refresh_due = true;
+                self.try_start_key_refresh();
+            }
+            NodeEvent::Network(NetEvent::Connected(_, _)) => unreachable!(), // There is no connect() calls.
+            NodeEvent::Network(NetEvent::Accepted(_, _)) => (),              // All endpoint accepted
+            NodeEvent::Network(NetEvent::Message(endpoint, input_data)) => {
+                let message: Message = deserialize(input_data).unwrap();
                 match message {
                     Message::RegisterParticipant(name, addr) => {
                         self.register(&name, addr, endpoint);
@@ -73,10 +224,25 @@ impl DiscoveryServer {
                     Message::UnregisterParticipant(name) => {
                         self.unregister(&name);
                     }
+                    Message::ReconfigureCommittee(new_committee_size) => {
+                        self.reconfigure(new_committee_size);
+                    }
+                    Message::ReshareDone(participant) => {
+                        self.reshare_done(participant);
+                    }
+                    Message::PublicKeyShare(participant, b_share) => {
+                        self.public_key_share(participant, b_share);
+                    }
+                    Message::JobDone(participant, job_id, online_micros) => {
+                        self.job_done(participant, job_id, online_micros);
+                    }
+                    Message::KeyRefreshDone(participant, epoch) => {
+                        self.key_refresh_done(participant, epoch);
+                    }
                     _ => unreachable!(),
                 }
             }
-            NetEvent::Disconnected(endpoint) => {
+            NodeEvent::Network(NetEvent::Disconnected(endpoint)) => {
                 // Participant disconnection without explict unregistration.
                 // We must remove from the registry too.
                 let participant =
@@ -103,7 +269,7 @@ impl DiscoveryServer {
             // Notify other participants about this new participant
             let message : Message = Message::ParticipantNotificationAdded(name.to_string(), addr);
             let output_data = serialize(&message).unwrap();
-            for (_, info) in &mut self.participants {
+            for info in self.participants.values_mut() {
                 self.handler.network().send(info.endpoint, &output_data);
             }
 
@@ -111,91 +277,426 @@ impl DiscoveryServer {
             self.participants.insert(name.to_string(), ParticipantInfo { addr, endpoint });
             //debug!("Added participant '{}' with ip {}", name, addr);
 
-            if self.participants.len() == self.params.n {
+            if !self.committee_set_up && self.key_generation.is_none() && self.participants.len() == self.params.n {
                 thread::sleep(Duration::from_millis(100));
 
                 // self.start_time = Some(std::time::Instant::now());
+                self.set_up_committee();
+            }
+
+            self.try_start_resharing();
+
+            // A participant holding a Shamir share joins the committee again once it connected to the others
+            if self.committee_set_up && self.threshold_scheme.is_some() {
+                thread::sleep(Duration::from_millis(100));
+                self.form_committee();
+            }
+        }
+        else {
+            //debug!("Participant with name '{}' already exists, please registry with another name",name);
+        }
+    }
 
 
-                let (lwe_scheme, _ptxt, a, b) = init_lwe_with_random_ptxt(self.params.m, self.params.k, self.params.lwe_dimension, 1);
 
+    fn set_up_committee(&mut self) {
+        if self.distributed_key_generation {
+            let key_generation = DistributedKeyGeneration::new(&self.params, public_key_rows(&self.params));
+            for i in 0..self.params.n {
+                self.send_to(i, &Message::KeyGenerationStart(key_generation.public_a.clone()));
+            }
+            self.key_generation = Some(key_generation);
+            return;
+        }
 
-                println!("_ptxt = {_ptxt}");
-                let mut rng = rand::thread_rng();
-                let s = UniformBigInt::new(&BigInt::zero(), &BigInt::from(self.params.big_d)).sample(&mut rng);
-                let r = UniformBigInt::new(&BigInt::zero(), &self.params.big_l).sample(&mut rng);
+        let (lwe_scheme, _ptxt, a, b) = init_lwe_with_parameters(&LweParameters::from_public_parameters(&self.params, 0));
 
-                let preprocessing_shares = self.preprocessing.run(s, r, lwe_scheme.sk);
-                let a = serialize(&a).unwrap();
-                let b = serialize(&b).unwrap();
+        println!("_ptxt = {_ptxt}");
 
-                let alpha = UniformBigInt::new(&BigInt::zero(), &self.params.mac_big_ks).sample(&mut rng);
-                let mac_alpha_shares = AdditiveSecretSharing::share(&alpha, self.params.n, self.params.mac_ks);
+        self.ciphertext = Some((serialize(&a).unwrap(), serialize(&b).unwrap()));
+        if let Some(scheme) = self.threshold_scheme.clone() {
+            self.write_threshold_key_shares(&scheme, &lwe_scheme.sk);
+            self.committee_set_up = true;
+            self.form_committee();
+            return;
+        }
 
-                let mut mac_r_shares_collection = Vec::new();
-                let t = 3;
-                for _i in 0..t {
-                    let r = UniformBigInt::new(BigInt::zero(), &self.params.mac_big_s).sample(&mut rng);
-                    let r_shares = AdditiveSecretSharing::share(&r, self.params.n, self.params.mac_s);
+        // rows = parties; columns = LWE dimension
+        let sk_shares_per_party = DMatrix::from_columns(&lwe_scheme.sk.iter()
+            .map(|sk_digit| AdditiveSecretSharing::share(sk_digit, self.params.n, self.params.mac_ks))
+            .collect::<Vec<_>>());
 
-                    let _ = r_shares.push(r.clone());
+        let dealt = self.write_participant_data(KeySource::Dealt(&sk_shares_per_party), "bin");
+        self.set_dealer(dealt);
+        self.committee_set_up = true;
 
-                    mac_r_shares_collection.push(r_shares);
-                }
+        // Send a notification message to each participant to load data from the file
+        let message = self.start_message();
+        for i in 0..self.params.n {
+            self.send_to(i, &message);
+        }
+    }
 
-                let mac_r_shares_collection = DMatrix::from_columns(&mac_r_shares_collection);
-
-                let chi_vals = DVector::from_fn(t, |_i, _| {
-                    UniformBigInt::new(BigInt::zero(), &self.params.mac_big_s).sample(&mut rng)
-                });
-
-                // Set up a directory for participant data files
-                let dir_path = Path::new("/tmp/participant_data");
-                create_dir_all(dir_path).expect("Failed to create participant data directory");
-
-                // For each participant, prepare data and save it to a unique file
-                for (participant_name, info) in self.participants.iter() {
-                    let i = usize::from_str(participant_name.as_str()).unwrap();
-                    let mac_r_shares: DVector<BigInt> = mac_r_shares_collection.row(i).transpose().into();
-
-                    // Create participant-specific data
-                    let participant_data = ProtocolTransferredData {
-                        preprocessed: Some(serialize(&preprocessing_shares[i]).unwrap()),
-                        a: Some(a.clone()),
-                        b: Some(b.clone()),
-                        z_prime: None,
-                        y_prime: None,
-                        o_prime: None,
-                        // alpha: Some(serialize(&alpha).unwrap()),
-                        mac_alpha: Some(serialize(&mac_alpha_shares[i]).unwrap()),
-                        mac_r: Some(serialize(&mac_r_shares).unwrap()),
-                        // mac_x_tilde_collection: None,
-                        // mac_m_tilde_collection: None,
-                        mac_chi_vals: Some(serialize(&chi_vals).unwrap()),
-                        mac_z: None,
-                    };
-
-                    // Serialize and write the data to a file for this participant
-                    let file_path = dir_path.join(format!("{}.bin", participant_name));
-                    let mut file = File::create(&file_path).expect("Failed to create participant data file");
-                    file.write_all(&serialize(&participant_data).unwrap())
-                        .expect("Failed to write participant data to file");
-
-                   debug!("Data for participant '{}' written to file {:?}", participant_name, file_path);
-
-                    // Send a notification message to each participant to load data from the file
-                    let message: Message = Message::ProtocolStart;
-                    let output_data = serialize(&message).unwrap();
-                    self.handler.network().send(info.endpoint, &output_data);
-                }
+    fn public_key_share(&mut self, participant: usize, b_share: DVector<BigInt>) {
+        let Some(key_generation) = self.key_generation.clone() else {
+            return;
+        };
+        self.public_key_shares.insert(participant, b_share);
+        if self.public_key_shares.len() < self.params.n {
+            return;
+        }
+
+        let b_shares: Vec<DVector<BigInt>> = std::mem::take(&mut self.public_key_shares).into_values().collect();
+        let pk = key_generation.public_key(&key_generation.combine(&b_shares));
+
+        let ptxt = UniformBigInt::new(BigInt::zero(), &self.params.p).sample(&mut rand::thread_rng());
+        let (a, b) = pk.encrypt(&ptxt);
+        println!("_ptxt = {ptxt}");
+
+        self.ciphertext = Some((serialize(&a).unwrap(), serialize(&b).unwrap()));
+        let dealt = self.write_participant_data(KeySource::Generated, "bin");
+        self.set_dealer(dealt);
+        self.committee_set_up = true;
+
+        let message = self.start_message();
+        for i in 0..self.params.n {
+            self.send_to(i, &message);
+        }
+    }
+
+    /// Writes the data file of every committee member with fresh shares of the MAC key, and the key shares with their
+    /// MACs when the server deals them. Returns the preprocessing the stores were dealt with, None when the members
+    /// run it
+    fn write_participant_data(&self, key_source: KeySource, extension: &str) -> Option<Preprocessing> {
+        let (a, b) = self.ciphertext.clone().unwrap();
+
+        // Fresh PRSS keys for every pool, nonce j belongs to instance j
+        let prss_keys = PrssKeys::deal(self.params.n);
+
+        // The key share is stored once, apart from the preprocessing store, since key refreshes replace it
+        let (preprocessing, preprocessing_shares) = match self.privacy_threshold {
+            None => {
+                let started = Instant::now();
+                let preprocessing = self.preprocessing.clone().with_prss(prss_keys.clone());
+                let shares = preprocessing.run_instances(self.preprocessing_instances());
+                println!("Offline: preprocessing dealt. instances: {:?}, n: {}, microseconds: {}",
+                         0..self.preprocessing_instances(), self.params.n, started.elapsed().as_micros());
+                (Some(preprocessing), Some(shares))
+            },
+            Some(_) => (None, None),
+        };
+
+        // The MAC key stays the same across committees, the MACs of the key share move over with it
+        let mac_alpha_shares = self.preprocessing.mac_key_shares();
+
+        let (sk_shares, sk_macs, sk_input_masks) = match key_source {
+            KeySource::Dealt(sk_shares_per_party) => {
+                let sk_shares: Vec<DVector<BigInt>> = sk_shares_per_party.row_iter().map(|row| row.transpose()).collect();
+                let sk_macs = self.preprocessing.key_macs(&sk_shares);
+                (Some(sk_shares), Some(sk_macs), None)
+            },
+            KeySource::Generated => (None, None, Some(self.preprocessing.key_input_masks(self.params.lwe_dimension))),
+            KeySource::Reshared | KeySource::Threshold => (None, None, None),
+        };
+
+        // Set up a directory for participant data files
+        let dir_path = Path::new("/tmp/participant_data");
+        create_dir_all(dir_path).expect("Failed to create participant data directory");
+
+        // For each participant, prepare data and save it to a unique file, party i of the committee is participant
+        // `members[i]`
+        for (i, &participant) in self.members().iter().enumerate() {
+            // Create participant-specific data
+            let participant_data = ProtocolTransferredData {
+                preprocessed: None,
+                sk: sk_shares.as_ref().map(|sk_shares| serialize(&sk_shares[i]).unwrap()),
+                a: Some(a.clone()),
+                b: Some(b.clone()),
+                z_prime: None,
+                y_prime: None,
+                o_prime: None,
+                sk_mac: sk_macs.as_ref().map(|sk_macs| serialize(&sk_macs[i]).unwrap()),
+                sk_input_mask: sk_input_masks.as_ref().map(|masks| serialize(&masks[i]).unwrap()),
+                mac_alpha: Some(serialize(&mac_alpha_shares[i]).unwrap()),
+                prss: Some(serialize(&prss_keys[i]).unwrap()),
+                // mac_x_tilde_collection: None,
+                // mac_m_tilde_collection: None,
+                mac_commitment: None,
+                mac_check: None,
+                partial_decryption: None,
+            };
+
+            // Serialize and write the data to a file for this participant
+            let file_path = dir_path.join(format!("{}.{}", participant, extension));
+            let mut file = File::create(&file_path).expect("Failed to create participant data file");
+            file.write_all(&encode_participant_data(&self.params, participant, &participant_data))
+                .expect("Failed to write participant data to file");
+
+            debug!("Data for participant '{}' written to file {:?}", participant, file_path);
+
+            // The staged data of a committee change comes with a staged store, put in place with the key share
+            let store_dir = if extension == "bin" { PreprocessingStore::directory(participant) } else { PreprocessingStore::staged_directory(participant) };
+            let mut store = PreprocessingStore::create(&store_dir, &self.params, participant).expect("Failed to create the preprocessing store");
+            if let Some(shares) = &preprocessing_shares {
+                store.append(0, &shares[i]).expect("Failed to write the preprocessing batch");
             }
         }
-        else {
-            //debug!("Participant with name '{}' already exists, please registry with another name",name);
+
+        preprocessing
+    }
+
+    /// Writes the Shamir shares of the key and of its MACs of every participant, they outlive every committee
+    fn write_threshold_key_shares(&self, scheme: &ShamirSecretSharing, sk: &DVector<BigInt>) {
+        let dir_path = Path::new("/tmp/participant_data");
+        create_dir_all(dir_path).expect("Failed to create participant data directory");
+
+        for (i, (sk, sk_mac)) in self.preprocessing.threshold_key_shares(scheme, sk).into_iter().enumerate() {
+            let key_share = ThresholdKeyShare { threshold: scheme.threshold, sk, sk_mac };
+            let file_path = dir_path.join(format!("{}.key.bin", i));
+            File::create(&file_path)
+                .and_then(|mut file| file.write_all(&encode_value(&self.params, i, ContentType::KeyShare, &key_share)))
+                .expect("Failed to write the Shamir key share");
+        }
+    }
+
+    /// Participants of the committee in party order
+    fn members(&self) -> Vec<usize> {
+        self.committee.clone().unwrap_or_else(|| (0..self.params.n).collect())
+    }
+
+    /// Restarts the jobs on a committee of the registered participants that hold a Shamir share, once these changed.
+    /// The data of the new committee is staged, its members turn their key shares into additive ones and put it in
+    /// place before they start
+    fn form_committee(&mut self) {
+        let Some(scheme) = self.threshold_scheme.clone() else {
+            return;
+        };
+        if !self.committee_set_up {
+            return;
+        }
+        let live: Vec<usize> = (0..scheme.num_parties())
+            .filter(|i| self.participants.contains_key(&i.to_string()))
+            .collect();
+        if self.committee.as_ref() == Some(&live) {
+            return;
+        }
+
+        // Jobs of the old committee wait for steps of the members that left
+        self.running_jobs.clear();
+        if live.len() < scheme.reconstruction_threshold() {
+            eprintln!("{} live parties cannot decrypt, {} are needed", live.len(), scheme.reconstruction_threshold());
+            self.committee = None;
+            return;
+        }
+
+        self.params = self.params.with_committee_size(live.len());
+        self.preprocessing.params = self.params.clone();
+        self.committee = Some(live.clone());
+        let dealt = self.write_participant_data(KeySource::Threshold, "next.bin");
+        self.set_dealer(dealt);
+        println!("Committee formed by participants {:?}", live);
+
+        let message = self.start_message();
+        for &i in &live {
+            self.send_to(i, &Message::CommitteeMembers(live.clone()));
+            self.send_to(i, &message);
+        }
+    }
+
+    /// Lets the producer top up the live stores with `preprocessing`, or stops it with None
+    fn set_dealer(&self, preprocessing: Option<Preprocessing>) {
+        if let Some(dealer) = &self.preprocessing_dealer {
+            // Waits for a batch being dealt to the current stores
+            *dealer.lock().unwrap() = preprocessing;
+        }
+    }
+
+    /// Instances dealt to a fresh store, those of the first jobs
+    fn preprocessing_instances(&self) -> usize {
+        self.jobs_per_worker * self.ctxt_per_job
+    }
+
+    /// Starts the first jobs of a committee on its fresh stores
+    fn start_message(&mut self) -> Message {
+        self.next_instance = 0;
+        self.running_jobs.clear();
+        self.waiting_jobs = 0;
+
+        let assignment = self.assign_jobs(self.jobs_per_worker);
+        match self.privacy_threshold {
+            None => Message::ProtocolStart(assignment),
+            Some(privacy_threshold) => Message::PreprocessingStart(privacy_threshold, self.preprocessing_instances(), assignment),
+        }
+    }
+
+    /// The next `jobs` jobs, on the instances that follow those of the jobs before
+    fn assign_jobs(&mut self, jobs: usize) -> JobAssignment {
+        let assignment = JobAssignment { first_job: self.next_job, jobs, first_instance: self.next_instance };
+        for (job_id, first_instance) in assignment.jobs(self.ctxt_per_job) {
+            self.running_jobs.insert(job_id, (first_instance..first_instance + self.ctxt_per_job, HashMap::new()));
+        }
+
+        self.next_job = assignment.end();
+        self.next_instance += jobs * self.ctxt_per_job;
+        assignment
+    }
+
+    fn job_done(&mut self, participant: usize, job_id: u64, online_micros: u64) {
+        let Some((_, finished)) = self.running_jobs.get_mut(&job_id) else {
+            return;
+        };
+        finished.insert(participant, online_micros);
+        if finished.len() < self.params.n {
+            return;
+        }
+
+        // The job took as long as its slowest member
+        let (instances, finished) = self.running_jobs.remove(&job_id).unwrap();
+        println!("Online: job decrypted. job: {}, instances: {:?}, n: {}, microseconds: {}",
+                 job_id, instances, self.params.n, finished.values().max().unwrap());
+
+        if self.preprocessing_dealer.is_some() {
+            self.waiting_jobs += 1;
+            self.start_waiting_jobs();
+        }
+        self.try_start_key_refresh();
+    }
+
+    // Starts the waiting jobs the stores of every member hold instances for, and checks again later for the rest
+    fn start_waiting_jobs(&mut self) {
+        if self.waiting_jobs == 0 || self.committee_change.is_some() || self.key_refresh_due || self.key_refreshed.is_some() {
+            return;
+        }
+
+        let available = self.members().into_iter()
+            .map(|i| PreprocessingStore::open(&PreprocessingStore::directory(i), &self.params, i).map_or(0, |store| store.end()))
+            .min()
+            .unwrap_or(0);
+        let jobs = (available.saturating_sub(self.next_instance) / self.ctxt_per_job).min(self.waiting_jobs);
+
+        if jobs > 0 {
+            self.waiting_jobs -= jobs;
+            let message = Message::ProtocolStart(self.assign_jobs(jobs));
+            for i in self.members() {
+                self.send_to(i, &message);
+            }
+        }
+
+        if self.waiting_jobs > 0 && !self.retry_scheduled {
+            self.retry_scheduled = true;
+            self.handler.signals().send_with_timer(Signal::RetryWaitingJobs, POLL_INTERVAL);
+        }
+    }
+
+    // A due refresh starts at the first job boundary where no job is running
+    fn try_start_key_refresh(&mut self) {
+        if !self.key_refresh_due || self.key_refreshed.is_some() || !self.committee_set_up || self.committee_change.is_some()
+            || !self.running_jobs.is_empty() {
+            return;
+        }
+
+        self.key_refresh_due = false;
+        self.key_refreshed = Some(HashSet::new());
+        for i in 0..self.params.n {
+            self.send_to(i, &Message::KeyRefreshStart(self.key_refresh_epoch));
+        }
+    }
+
+    fn key_refresh_done(&mut self, participant: usize, epoch: u64) {
+        let Some(refreshed) = self.key_refreshed.as_mut() else {
+            return;
+        };
+        if epoch != self.key_refresh_epoch {
+            return;
+        }
+        refreshed.insert(participant);
+        if refreshed.len() < self.params.n {
+            return;
+        }
+
+        println!("Key refreshed. epoch: {}, n: {}", epoch, self.params.n);
+        self.key_refreshed = None;
+        self.key_refresh_epoch += 1;
+        if let Some(interval) = self.key_refresh_interval {
+            self.handler.signals().send_with_timer(Signal::KeyRefreshDue, interval);
+        }
+
+        self.start_waiting_jobs();
+    }
+
+    fn send_to(&self, participant: usize, message: &Message) {
+        if let Some(info) = self.participants.get(&participant.to_string()) {
+            let output_data = serialize(message).unwrap();
+            self.handler.network().send(info.endpoint, &output_data);
+        }
+    }
+
+    fn reconfigure(&mut self, new_committee_size: usize) {
+        if !self.committee_set_up || self.committee_change.is_some() || self.key_refreshed.is_some() {
+            eprintln!("Committee change to {} participants refused, no committee is set up or a change or key refresh is running", new_committee_size);
+            return;
+        }
+        if self.threshold_scheme.is_some() {
+            eprintln!("Committee change to {} participants refused, the threshold committee is formed by the registered participants", new_committee_size);
+            return;
+        }
+
+        self.committee_change = Some(Resharing::new(&self.params, new_committee_size));
+        self.try_start_resharing();
+    }
+
+    // The handover starts once every member of the old and of the new committee registered
+    fn try_start_resharing(&mut self) {
+        let Some(resharing) = self.committee_change.clone() else {
+            return;
+        };
+        let (old_n, new_n) = (resharing.old_params.n, resharing.new_params.n);
+        if self.resharing_started || !(0..old_n.max(new_n)).all(|i| self.participants.contains_key(&i.to_string())) {
+            return;
+        }
+        self.resharing_started = true;
+
+        // Everything but the key share is dealt again for the new committee. The files are staged next to the
+        // current ones, which still hold the key shares of the old members until these sent their pieces
+        self.params = resharing.new_params.clone();
+        self.preprocessing.params = self.params.clone();
+        self.set_dealer(None);
+        self.staged_preprocessing = self.write_participant_data(KeySource::Reshared, "next.bin");
+
+        for i in 0..old_n.max(new_n) {
+            self.send_to(i, &Message::ReshareStart(old_n, new_n));
         }
     }
 
+    fn reshare_done(&mut self, participant: usize) {
+        let Some(resharing) = self.committee_change.clone() else {
+            return;
+        };
+        self.reshared.insert(participant);
+        if self.reshared.len() < resharing.new_params.n {
+            return;
+        }
+
+        // Members of the old committee only are let go, which also tells everyone else to forget them
+        for i in resharing.new_params.n..resharing.old_params.n {
+            if let Some(info) = self.participants.get(&i.to_string()) {
+                self.handler.network().remove(info.endpoint.resource_id());
+                self.unregister(&i.to_string());
+            }
+        }
 
+        println!("Committee changed from {} to {} participants", resharing.old_params.n, resharing.new_params.n);
+        self.committee_change = None;
+        self.resharing_started = false;
+        self.reshared.clear();
+        let staged_preprocessing = self.staged_preprocessing.take();
+        self.set_dealer(staged_preprocessing);
+
+        let message = self.start_message();
+        for i in 0..self.params.n {
+            self.send_to(i, &message);
+        }
+    }
 
     fn unregister(&mut self, name: &str) {
         if let Some(_info) = self.participants.remove(name) {
@@ -207,7 +708,7 @@ impl DiscoveryServer {
             }
             //debug!("Removed participant '{}' with ip {}", name, info.addr);
 
-            if self.participants.len() == 0 {
+            if self.participants.is_empty() {
                 // let elapsed = self.start_time.unwrap().elapsed();
 
                 // let _microseconds = elapsed.as_micros();
@@ -218,9 +719,33 @@ impl DiscoveryServer {
                 exit(0);
 
             }
+
+            // The other holders of a Shamir share go on without it
+            self.form_committee();
         }
         else {
             //debug!("Can not unregister an non-existent participant with name '{}'", name);
         }
     }
-}
\ No newline at end of file
+}
+
+
+/// Asks a running discovery server to hand the key over to a committee of `new_committee_size` participants
+pub fn request_committee_change(new_committee_size: usize) -> io::Result<()> {
+    let (handler, node_listener) = node::split::<()>();
+    let (server, _) = handler.network().connect(Transport::FramedTcp, "127.0.0.1:5000")?;
+
+    node_listener.for_each(move |event| {
+        if let NetEvent::Connected(_, established) = event.network() {
+            if established {
+                let output_data = serialize(&Message::ReconfigureCommittee(new_committee_size)).unwrap();
+                handler.network().send(server, &output_data);
+            } else {
+                eprintln!("Can not connect to the discovery server");
+            }
+            handler.stop();
+        }
+    });
+
+    Ok(())
+}
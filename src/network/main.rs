@@ -1,10 +1,18 @@
-use clap::{Parser, Subcommand,};
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser, Subcommand,};
+use std::time::Duration;
 
+use threshold_decryption::mpc::cost_model::{optimize, NetworkProfile, RankingTable};
+use threshold_decryption::mpc::decryption_failure::{monte_carlo, FailureEstimate};
+use threshold_decryption::mpc::lwe_scheme::LweParameters;
+use threshold_decryption::mpc::noise_flooding::max_smudging_bits;
 use threshold_decryption::mpc::preprocessing::Preprocessing;
-use threshold_decryption::mpc::public_params::PublicParameters;
+use threshold_decryption::mpc::public_params::{PublicParameters, PublicParametersBuilder};
 
-use threshold_decryption::network::discovery_server::DiscoveryServer;
-use threshold_decryption::network::participant::Participant;
+use threshold_decryption::network::common::DecryptionProtocol;
+use threshold_decryption::network::discovery_server::{request_committee_change, DiscoveryServer, public_key_rows};
+use threshold_decryption::network::participant::{load_config, Participant};
+use threshold_decryption::network::preprocessing_producer::PreprocessingProducer;
 
 
 #[derive(Parser, Debug)]
@@ -22,14 +30,47 @@ struct Cli {
     #[arg(short = 'm', long = "ctxt-bits")]
     m: usize,
 
+    /// Required by every command but `optimize` and `reconfigure`
     #[arg(short = 'b', long = "digit-bits")]
-    b: usize,
+    b: Option<usize>,
 
+    /// Top digit of this many bits instead of the l - (d - 1) * b that ceil(l / b) digits leave, it may exceed b
+    #[arg(long = "top-digit-bits")]
+    b_prime: Option<usize>,
+
+    /// Required by every command but `optimize` and `reconfigure`
     #[arg(long = "lwe-bits")]
-    lwe_dimension: usize,
+    lwe_dimension: Option<usize>,
 
+    /// Required by every command but `optimize` and `reconfigure`
     #[arg(long = "mac-s")]
-    mac_s: usize,
+    mac_s: Option<usize>,
+
+    /// Run the dealer-free preprocessing, hiding s, r and the tables from any `t` participants
+    #[arg(long = "distributed-preprocessing", value_name = "t")]
+    privacy_threshold: Option<usize>,
+
+    /// Deal the LWE key as t-of-n Shamir shares, any t + 1 registered participants keep decrypting
+    #[arg(long = "threshold", value_name = "t")]
+    threshold: Option<usize>,
+
+    /// Let the participants generate the LWE key, no participant ever holds the whole key. The discovery server deals
+    /// the masks the participants input their key shares with, it must be trusted not to observe the inputs the
+    /// participants send each other, from which it would recover the key
+    #[arg(long = "distributed-keygen")]
+    distributed_key_generation: bool,
+
+    /// Deal the tables of participants 0..n-1 as PRG seeds, which keeps their preprocessing small
+    #[arg(long = "seed-compression")]
+    seed_compression: bool,
+
+    /// Decrypt with partial decryptions flooded with noise of this many bits instead of the round-based protocol
+    #[arg(long = "noise-flooding", value_name = "smudging bits")]
+    smudging_bits: Option<usize>,
+
+    /// Refuse to run with an estimated LWE security below this many bits
+    #[arg(long = "min-security", value_name = "bits")]
+    min_security: Option<usize>,
 
 }
 
@@ -39,35 +80,178 @@ enum Commands {
 
     Participant{
         id: usize,
-    }
+    },
+
+    /// Hands the key of the running committee over to a committee of `-n` participants
+    Reconfigure,
+
+    /// Ranks the digit layouts (b, b') for `-n`, `-k`, `-m` by their predicted cost on this network
+    Optimize {
+        #[arg(long = "latency-ms", default_value_t = 0.5)]
+        latency_ms: f64,
+
+        #[arg(long = "bandwidth-mbit", default_value_t = 10.0)]
+        bandwidth_mbit: f64,
+
+        /// Local time of one operation on a ciphertext-sized value
+        #[arg(long = "operation-ns", default_value_t = 100.0)]
+        operation_ns: f64,
+    },
+
+    /// Compares the analytic decryption failure probability with the rate of local protocol runs
+    FailureRate {
+        /// Ciphertexts summed into the decrypted one, minus one
+        #[arg(long = "additions", default_value_t = 0)]
+        additions: usize,
+
+        #[arg(long = "trials", default_value_t = 1000)]
+        trials: usize,
+    },
 }
 
 
+/// Parameters without the digit layout, which the optimizer chooses
+fn parameters_builder(cli: &Cli) -> PublicParametersBuilder {
+    let mut builder = PublicParameters::builder()
+        .with_parties(cli.n)
+        .with_ciphertext_bits(cli.k)
+        .with_plaintext_bits(cli.m);
+    if let Some(lwe_dimension) = cli.lwe_dimension {
+        builder = builder.with_lwe_dimension(lwe_dimension);
+    }
+    if let Some(mac_s) = cli.mac_s {
+        builder = builder.with_mac_security(mac_s);
+    }
+    if let Some(min_security) = cli.min_security {
+        builder = builder.with_min_security(min_security);
+    }
+    builder
+}
+
+/// Parameters of a command that runs the protocol, exits when one of them is missing
+fn public_parameters(cli: &Cli) -> Option<PublicParameters> {
+    for (value, argument) in [(cli.b, "--digit-bits <B>"), (cli.lwe_dimension, "--lwe-bits <LWE_DIMENSION>"), (cli.mac_s, "--mac-s <MAC_S>")] {
+        if value.is_none() {
+            Cli::command().error(ErrorKind::MissingRequiredArgument, format!("the argument '{}' is required by this command", argument)).exit();
+        }
+    }
 
+    let mut builder = parameters_builder(cli).with_digit_bits(cli.b.unwrap());
+    if let Some(b_prime) = cli.b_prime {
+        builder = builder.with_top_digit_bits(b_prime);
+    }
 
+    match builder.build() {
+        Ok(public_parameters) => Some(public_parameters),
+        Err(err) => {
+            eprintln!("Invalid parameters: {}", err);
+            None
+        }
+    }
+}
 
 pub  fn main() {
     env_logger::builder().filter_level(log::LevelFilter::Info).init();
     let cli = Cli::parse();
 
-    let public_parameters =
-        PublicParameters::init(cli.n, cli.k ,cli.m, cli.b, cli.lwe_dimension, cli.mac_s);
-
     match &cli.command {
+        Commands::Optimize { latency_ms, bandwidth_mbit, operation_ns } => {
+            let profile = NetworkProfile::new(*latency_ms, *bandwidth_mbit).with_operation_ns(*operation_ns);
+            let ranked = optimize(&parameters_builder(&cli), &profile);
+            if ranked.is_empty() {
+                eprintln!("No digit layout gives valid parameters");
+            } else {
+                print!("{}", RankingTable(&ranked));
+            }
+        }
         Commands::DiscoveryServer => {
-            let preprocessing = Preprocessing::new(&public_parameters);
+            let Some(public_parameters) = public_parameters(&cli) else { return };
+            let mut preprocessing = Preprocessing::new(&public_parameters);
+            if cli.seed_compression {
+                preprocessing = preprocessing.with_seed_compression();
+            }
+            let config = load_config("participant_config.toml");
+            if cli.privacy_threshold.is_some() && config.preprocessing_low_water_mark.is_some() {
+                eprintln!("The preprocessing producer deals nothing with --distributed-preprocessing, unset preprocessing_low_water_mark");
+                return;
+            }
+            if let Some(threshold) = cli.threshold {
+                if threshold >= public_parameters.n {
+                    eprintln!("A threshold of {} needs more than {} participants", threshold, public_parameters.n);
+                    return;
+                }
+                if cli.privacy_threshold.is_some() || cli.distributed_key_generation || config.preprocessing_low_water_mark.is_some()
+                    || config.key_refresh_interval_secs.is_some() {
+                    eprintln!("A threshold committee runs with a dealt key and dealt preprocessing only, unset --distributed-preprocessing, --distributed-keygen, preprocessing_low_water_mark and key_refresh_interval_secs");
+                    return;
+                }
+            }
             match DiscoveryServer::new(&public_parameters, &preprocessing) {
-                Ok(discovery_server) => discovery_server.run(),
+                Ok(discovery_server) => {
+                    let mut discovery_server = discovery_server.with_jobs(config.jobs_per_worker, config.ctxt_per_job);
+                    if cli.distributed_key_generation {
+                        discovery_server = discovery_server.with_distributed_key_generation();
+                    }
+                    if let Some(threshold) = cli.threshold {
+                        discovery_server = discovery_server.with_threshold(threshold);
+                    }
+                    if let Some(interval_secs) = config.key_refresh_interval_secs {
+                        discovery_server = discovery_server.with_key_refresh_interval(Duration::from_secs(interval_secs));
+                    }
+                    if let Some(low_water_mark) = config.preprocessing_low_water_mark {
+                        let producer = PreprocessingProducer::new(low_water_mark, config.preprocessing_batch_size());
+                        discovery_server = discovery_server.with_preprocessing_producer(producer.dealer());
+                        producer.spawn();
+                    }
+                    match cli.privacy_threshold {
+                        Some(privacy_threshold) => discovery_server.with_distributed_preprocessing(privacy_threshold).run(),
+                        None => discovery_server.run(),
+                    }
+                },
                 Err(_err) => { //debug!("Can not run the discovery server: {}", _err)
                 },
             }
         }
+        Commands::FailureRate { additions, trials } => {
+            let Some(public_parameters) = public_parameters(&cli) else { return };
+            let pk_rows = if cli.distributed_key_generation { public_key_rows(&public_parameters) } else { 0 };
+
+            let estimate = FailureEstimate::new(&public_parameters, pk_rows, *additions);
+            println!("Noise sigma {:.1}, bound {}, L/2 = {}", estimate.noise_sigma, estimate.noise_bound, &public_parameters.big_l >> 1);
+            println!("Analytic failure probability: 2^{:.1}", estimate.log2_probability);
+
+            let result = monte_carlo(&public_parameters, pk_rows, *additions, *trials);
+            println!("Empirical failure rate: {} / {} = {:e} (standard error {:e})",
+                     result.failures, result.trials, result.rate(), result.standard_error());
+        }
+        Commands::Reconfigure => {
+            if let Err(err) = request_committee_change(cli.n) {
+                eprintln!("Can not reach the discovery server: {}", err);
+            }
+        }
         Commands::Participant{id} => {
+            let Some(public_parameters) = public_parameters(&cli) else { return };
             // let party = Party::new(id.clone(), &public_parameters);
 
-            match Participant::new(id.clone(), &public_parameters) {
+            let protocol = match cli.smudging_bits {
+                Some(smudging_bits) => {
+                    // The dealer encrypts with the key, after the distributed key generation the public samples add noise
+                    let pk_rows = if cli.distributed_key_generation { public_key_rows(&public_parameters) } else { 0 };
+                    let noise_bound = LweParameters::from_public_parameters(&public_parameters, pk_rows).noise_bound();
+
+                    let max_bits = max_smudging_bits(&public_parameters, &noise_bound);
+                    if max_bits.is_none_or(|max_bits| smudging_bits > max_bits) {
+                        eprintln!("Smudging noise of {} bits exceeds the noise budget, at most {:?} bits fit", smudging_bits, max_bits);
+                        return;
+                    }
+                    DecryptionProtocol::NoiseFlooding(smudging_bits)
+                },
+                None => DecryptionProtocol::Rounds,
+            };
+
+            match Participant::new(*id, &public_parameters) {
                 Ok(participant) => {
-                    participant.run()
+                    participant.with_decryption_protocol(protocol).run()
                 },
                 Err(_err) => {
                     //debug!("Can not run the participant: {}", _err);
@@ -4,10 +4,14 @@ pub mod participant;
 pub mod discovery_server;
 pub mod common;
 pub mod worker;
+pub mod storage;
+pub mod preprocessing_store;
+pub mod preprocessing_producer;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProtocolTransferredData {
     pub preprocessed: Option<Vec<u8>>,
+    pub sk: Option<Vec<u8>>,
     pub a: Option<Vec<u8>>,
     pub b: Option<Vec<u8>>,
     pub z_prime: Option<Vec<u8>>,
@@ -16,13 +20,22 @@ pub struct ProtocolTransferredData {
 
     // pub random_id: u64,
 
-    // pub alpha: Option<Vec<u8>>,
+    /// MAC shares of sk under the global MAC key
+    pub sk_mac: Option<Vec<u8>>,
+    /// Share of the mask and of its MAC the participant inputs its generated key share with
+    pub sk_input_mask: Option<Vec<u8>>,
+    /// Share of the global MAC key, no party learns the key itself
     pub mac_alpha: Option<Vec<u8>>,
-    pub mac_r: Option<Vec<u8>>,
+    /// PRSS keys the MAC pads and, with dealt preprocessing, the masks s and r are derived from
+    pub prss: Option<Vec<u8>>,
     // pub mac_x_tilde_collection: Option<Vec<u8>>,
     // pub mac_m_tilde_collection: Option<Vec<u8>>,
-    pub mac_chi_vals: Option<Vec<u8>>,
-    pub mac_z: Option<Vec<u8>>,
+    /// Commitment to the check values of the MAC check
+    pub mac_commitment: Option<Vec<u8>>,
+    /// Party number, check values and nonce the commitment opens to
+    pub mac_check: Option<Vec<u8>>,
+    /// <a, sk_i> + e_i of the noise flooding decryption
+    pub partial_decryption: Option<Vec<u8>>,
 
 }
 
@@ -30,6 +43,7 @@ impl ProtocolTransferredData {
     pub fn empty() -> ProtocolTransferredData {
         ProtocolTransferredData{
             preprocessed:None,
+            sk: None,
 
             a: None,
             b: None,
@@ -37,13 +51,15 @@ impl ProtocolTransferredData {
             z_prime: None,
             y_prime: None,
             o_prime: None,
-            // alpha: None,
+            sk_mac: None,
+            sk_input_mask: None,
             mac_alpha: None,
-            mac_r: None,
+            prss: None,
             // mac_x_tilde_collection: None,
             // mac_m_tilde_collection: None,
-            mac_chi_vals: None,
-            mac_z: None
+            mac_commitment: None,
+            mac_check: None,
+            partial_decryption: None,
 
         }
     }
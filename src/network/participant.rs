@@ -1,32 +1,57 @@
-use super::common::Message;
+use super::common::{DecryptionProtocol, JobAssignment, Message};
 use message_io::network::{NetEvent, Transport, Endpoint, SendStatus};
 use message_io::node::{self, NodeHandler, NodeListener};
 use std::net::SocketAddr;
 use std::collections::HashMap;
 use dashmap::DashMap;
 
-use std::io::{self, Read};
+use std::io;
 use std::sync::{Arc, Mutex, RwLock};
 use std::{fs, thread};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use log::debug;
-use rayon::prelude::*;
+use nalgebra::DVector;
+use num_bigint::BigInt;
+use num_integer::Integer;
 use rayon::{ThreadPool, ThreadPoolBuilder};
+use crate::mpc::distributed_preprocessing::{DistributedPreprocessing, PreprocessingMessage, PreprocessingParty};
+use crate::mpc::key_generation::DistributedKeyGeneration;
+use crate::mpc::key_refresh::KeyRefresh;
 use crate::mpc::public_params::PublicParameters;
+use crate::mpc::resharing::Resharing;
 use crate::network::{ProtocolTransferredData};
-use crate::network::worker::{handle_protocol_execute_step, handle_protocol_start, Worker};
+use crate::network::worker::{handle_protocol_execute_step, handle_protocol_start, load_key_input_mask, load_mac_key_share, load_sk, load_threshold_sk, remove_participant_data, store_preprocessed_share, store_sk, store_refreshed_sk, store_reshared_sk, ExecutionResult, Worker};
 
 use bitcode::serialize as serialize;
 use bitcode::deserialize as deserialize;
 
 #[derive(Debug, Deserialize)]
-struct ParticipantConfig {
-    thread_count: usize,
-    ctxt_per_job: usize,
-    jobs_per_worker: usize,
+pub struct ParticipantConfig {
+    pub thread_count: usize,
+    pub ctxt_per_job: usize,
+    /// Jobs the discovery server keeps running at a time
+    pub jobs_per_worker: usize,
+    /// Seconds between two proactive refreshes of the key share, which the discovery server runs between jobs. Never
+    /// refreshed when missing
+    pub key_refresh_interval_secs: Option<u64>,
+    /// Instances left in a store below which the discovery server deals another batch, dealt only once when missing
+    pub preprocessing_low_water_mark: Option<usize>,
+    /// Instances dealt per batch, `preprocessing_instances()` when missing
+    pub preprocessing_batch_size: Option<usize>,
 }
 
-fn load_config(path: &str) -> ParticipantConfig {
+impl ParticipantConfig {
+    /// Preprocessing instances a participant consumes, one per ciphertext of every job
+    pub fn preprocessing_instances(&self) -> usize {
+        self.jobs_per_worker * self.ctxt_per_job
+    }
+
+    pub fn preprocessing_batch_size(&self) -> usize {
+        self.preprocessing_batch_size.unwrap_or(self.preprocessing_instances())
+    }
+}
+
+pub fn load_config(path: &str) -> ParticipantConfig {
     let config_content = fs::read_to_string(path)
         .unwrap_or_else(|_| panic!("Failed to read the configuration file: {}", path));
     toml::from_str(&config_content)
@@ -82,6 +107,18 @@ lazy_static! {
     static ref known_participants: RwLock<HashMap<String, Endpoint>> = RwLock::new(HashMap::new());
 }
 
+// Pieces of a key share and of its MACs
+type KeyPieces = (DVector<BigInt>, DVector<BigInt>);
+
+// Key refresh pieces received so far, per refresh epoch and sender
+type KeyRefreshPieces = HashMap<u64, HashMap<usize, KeyPieces>>;
+
+// What the discovery server started while the generated key share still had to be input
+enum DeferredStart {
+    Protocol(JobAssignment),
+    Preprocessing(usize, usize, JobAssignment),
+}
+
 pub struct Participant {
     id: usize,
     greetings: HashMap<Endpoint, String>,
@@ -90,7 +127,34 @@ pub struct Participant {
     thread_pool: ThreadPool,
     public_parameters: PublicParameters,
 
-    config: ParticipantConfig
+    config: ParticipantConfig,
+
+    preprocessing: Option<(DistributedPreprocessing, PreprocessingParty)>,
+    preprocessing_step: usize,
+    preprocessing_inbox: HashMap<usize, Vec<(usize, PreprocessingMessage)>>,
+
+    key_refresh_pieces: KeyRefreshPieces,
+
+    // Key share from the distributed key generation, input once the data file with its mask was written
+    generated_sk: Option<DVector<BigInt>>,
+    // Key share minus input mask per sender, they may arrive before this participant got its start message
+    key_inputs: HashMap<usize, DVector<BigInt>>,
+    deferred_start: Option<DeferredStart>,
+
+    resharing: Option<Resharing>,
+    // Resharing pieces per sender, they may arrive before ReshareStart
+    reshare_pieces: HashMap<usize, KeyPieces>,
+
+    // Members of the threshold committee the participant belongs to, None when every participant is a member
+    committee: Option<Vec<usize>>,
+
+    protocol: DecryptionProtocol,
+    // Jobs the distributed preprocessing is run for, started once it is stored
+    pending_jobs: Option<JobAssignment>,
+    // Jobs below this id were started here, the discovery server assigns them in increasing order
+    jobs_end: u64,
+    // Steps of other participants received before this participant got the job
+    early_steps: Vec<(usize, usize, Vec<ProtocolTransferredData>, u64)>,
 }
 
 impl Participant {
@@ -116,7 +180,7 @@ impl Participant {
         debug!("Done initialized network");
         // ********
 
-        /// Load configuration from a file
+        // Load configuration from a file
 
 
 
@@ -126,10 +190,30 @@ impl Participant {
             public_parameters: params.clone(),
             job_data: job_data.into(),
             thread_pool,
-            config
+            config,
+            preprocessing: None,
+            preprocessing_step: 0,
+            preprocessing_inbox: HashMap::new(),
+            key_refresh_pieces: HashMap::new(),
+            generated_sk: None,
+            key_inputs: HashMap::new(),
+            deferred_start: None,
+            resharing: None,
+            reshare_pieces: HashMap::new(),
+            committee: None,
+            protocol: DecryptionProtocol::Rounds,
+            pending_jobs: None,
+            jobs_end: 0,
+            early_steps: Vec::new(),
         })
     }
 
+    /// Runs the jobs with `protocol` instead of the protocol of `Party`
+    pub fn with_decryption_protocol(mut self, protocol: DecryptionProtocol) -> Self {
+        self.protocol = protocol;
+        self
+    }
+
     pub fn run(mut self) {
 
         let handler = NODE_LISTENER.lock().unwrap().take().unwrap();
@@ -160,7 +244,7 @@ impl Participant {
                 }
 
                 NetEvent::Message(_endpoint, input_data) => {
-                    let message: Message = match deserialize(&input_data) {
+                    let message: Message = match deserialize(input_data) {
                         Ok(msg) => msg,
                         Err(e) => {
                             eprintln!("Failed to deserialize message: {}", e);
@@ -189,52 +273,60 @@ impl Participant {
                                 sender_mut.handler.network().remove(endpoint.resource_id());
                             }
                         }
-                        Message::ProtocolStart => {
-                            for batch in 0..self.config.jobs_per_worker as u64 {
-
-                                let job_data = Arc::clone(&self.job_data);
-                                let params = self.public_parameters.clone();
-                                self.thread_pool.spawn(move || {
-                                    // Update job_data using DashMap's concurrent API
-                                    let (worker, bulk_data) = handle_protocol_start(&params, self.id, self.config.ctxt_per_job).map_err(|e| {
-                                        eprintln!("Worker failed to handle ProtocolStart: {}", e);
-                                        let mut network_sender = NETWORK_SENDER.lock().unwrap();
-                                        let sender_mut = network_sender.as_mut().unwrap();
-                                        sender_mut.handler.stop();
-                                    }).unwrap();
-                                    job_data.insert(batch, worker);
-                                    debug!("Worker batch {} started.", batch);
-                                    // Send ProtocolExecuteStep to known participants for each worker
-                                    send_result_to_everyone(&bulk_data, 0, batch as usize, self.id);
-                                });
-                            }
+                        Message::KeyGenerationStart(public_a) => {
+                            let contribution = DistributedKeyGeneration::with_public_matrix(&self.public_parameters, public_a)
+                                .contribute();
+                            self.generated_sk = Some(contribution.sk_share);
+                            send_to_discovery_server(&Message::PublicKeyShare(self.id, contribution.b_share));
                         }
 
-                        Message::ProtocolExecuteStep(participant_num, step_num, input_data, job_id) => {
+                        Message::ProtocolStart(assignment) => {
+                            self.start(DeferredStart::Protocol(assignment));
+                        }
 
-                            let job_data = Arc::clone(&self.job_data);
-                            self.thread_pool.spawn(move || {
-                                // Update job_data using DashMap's concurrent API
-                                // job_data.entry(job_id).and_modify(|worker| {
-                                //     handle_protocol_execute_step(worker, job_id, self.id, participant_num, step_num, input_data);
-                                // });
-
-                                // in the multithreaded case it's possible the worker needs to receive data but we didn't even finish initalizing it yet
-                                loop {
-                                    if let Some(mut worker) = job_data.get_mut(&job_id) {
-                                        handle_protocol_execute_step(&mut *worker, job_id, self.id, participant_num, step_num, input_data);
-                                        break;
-                                    } else {
-                                        // Entry not found yet, wait before retrying
-                                        thread::sleep(Duration::from_millis(10)); // Adjust delay as needed
-                                    }
-                                }
-                            });
-
-                            // self.thread_pool.spawn(|| {
-                            //     handle_protocol_execute_step(&mut self.job_data, job_id, self.id, participant_num, step_num, input_data);
-                            // });
+                        Message::PreprocessingStart(privacy_threshold, instances, assignment) => {
+                            self.start(DeferredStart::Preprocessing(privacy_threshold, instances, assignment));
+                        }
 
+                        Message::KeyInput(participant_num, delta) => {
+                            self.key_inputs.insert(participant_num, delta);
+                            self.advance_key_input();
+                        }
+
+                        Message::PreprocessingStep(participant_num, step_num, message) => {
+                            self.preprocessing_inbox.entry(step_num).or_default().push((participant_num, message));
+                            self.advance_preprocessing();
+                        }
+
+                        Message::KeyRefreshStart(epoch) => {
+                            self.start_key_refresh(epoch);
+                        }
+
+                        Message::KeyRefreshPiece(participant_num, epoch, piece, mac_piece) => {
+                            self.receive_key_refresh_piece(participant_num, epoch, (piece, mac_piece));
+                        }
+
+                        Message::ReshareStart(old_committee_size, new_committee_size) => {
+                            self.start_resharing(old_committee_size, new_committee_size);
+                        }
+
+                        Message::CommitteeMembers(members) => {
+                            self.join_committee(members);
+                        }
+
+                        Message::ResharePiece(participant_num, piece, mac_piece) => {
+                            self.reshare_pieces.insert(participant_num, (piece, mac_piece));
+                            self.advance_resharing();
+                        }
+
+                        Message::ProtocolExecuteStep(participant_num, step_num, input_data, job_id) => {
+                            // Waiting for a worker that is not even being set up would occupy the thread pool for good
+                            if job_id >= self.jobs_end {
+                                self.early_steps.push((participant_num, step_num, input_data, job_id));
+                                return;
+                            }
+
+                            self.execute_step_message(participant_num, step_num, input_data, job_id);
                         }
                         _ => {}
                     }
@@ -253,6 +345,291 @@ impl Participant {
         });
     }
 
+    // A generated key share is input first, the start runs once the key share is stored with its MACs
+    fn start(&mut self, start: DeferredStart) {
+        let Some(sk) = self.generated_sk.as_ref() else {
+            self.run_start(start);
+            return;
+        };
+
+        let mask = match load_key_input_mask(&self.public_parameters, self.id) {
+            Ok(mask) => mask,
+            Err(e) => {
+                eprintln!("Failed to load the key input mask: {}", e);
+                return;
+            }
+        };
+        let delta = (sk - mask).map(|x| x.mod_floor(&self.public_parameters.mac_big_ks));
+        for receiver in (0..self.public_parameters.n).filter(|receiver| *receiver != self.id) {
+            send_to_participant(receiver, &Message::KeyInput(self.id, delta.clone()));
+        }
+
+        self.deferred_start = Some(start);
+        self.key_inputs.insert(self.id, delta);
+        self.advance_key_input();
+    }
+
+    fn run_start(&mut self, start: DeferredStart) {
+        match start {
+            DeferredStart::Protocol(assignment) => self.start_protocol(assignment),
+            DeferredStart::Preprocessing(privacy_threshold, instances, assignment) => {
+                let mac_key_share = match load_mac_key_share(&self.public_parameters, self.id) {
+                    Ok(mac_key_share) => mac_key_share,
+                    Err(e) => {
+                        eprintln!("Failed to load the MAC key share: {}", e);
+                        return;
+                    }
+                };
+                self.pending_jobs = Some(assignment);
+                let setup = DistributedPreprocessing::new(&self.public_parameters, privacy_threshold)
+                    .with_instances(instances);
+                let party = setup.new_party(self.id, &mac_key_share);
+                self.preprocessing = Some((setup, party));
+                self.advance_preprocessing();
+            }
+        }
+    }
+
+    // Stores the generated key share once the inputs of every participant arrived
+    fn advance_key_input(&mut self) {
+        if self.deferred_start.is_none() || self.key_inputs.len() < self.public_parameters.n {
+            return;
+        }
+
+        let delta = std::mem::take(&mut self.key_inputs).into_values()
+            .fold(DVector::zeros(self.public_parameters.lwe_dimension), |acc, delta| acc + delta);
+        let sk = self.generated_sk.take().unwrap();
+        if let Err(e) = store_sk(&self.public_parameters, self.id, &sk, &delta) {
+            eprintln!("Failed to store the generated key share: {}", e);
+            return;
+        }
+
+        let start = self.deferred_start.take().unwrap();
+        self.run_start(start);
+    }
+
+    /// Participants of the committee running the jobs, in party order
+    fn members(&self) -> Vec<usize> {
+        self.committee.clone().unwrap_or_else(|| (0..self.public_parameters.n).collect())
+    }
+
+    fn start_protocol(&mut self, assignment: JobAssignment) {
+        for (job_id, first_instance) in assignment.jobs(self.config.ctxt_per_job) {
+
+            let job_data = Arc::clone(&self.job_data);
+            let params = self.public_parameters.clone();
+            let members = self.members();
+            let id = self.id;
+            let ctxt_per_job = self.config.ctxt_per_job;
+            let protocol = self.protocol;
+            self.thread_pool.spawn(move || {
+                // Update job_data using DashMap's concurrent API
+                let (worker, bulk_data) = match handle_protocol_start(&params, id, &members, job_id, first_instance, ctxt_per_job, protocol) {
+                    Ok(started) => started,
+                    Err(e) => {
+                        eprintln!("Worker failed to handle ProtocolStart for job {}: {}", job_id, e);
+                        let mut network_sender = NETWORK_SENDER.lock().unwrap();
+                        let sender_mut = network_sender.as_mut().unwrap();
+                        sender_mut.handler.stop();
+                        return;
+                    }
+                };
+                job_data.insert(job_id, worker);
+                debug!("Worker job {} started.", job_id);
+                // Send ProtocolExecuteStep to known participants for each worker
+                send_result_to_everyone(&bulk_data, 0, job_id as usize, id, &members);
+            });
+        }
+
+        self.jobs_end = self.jobs_end.max(assignment.end());
+        let (started, waiting) = std::mem::take(&mut self.early_steps)
+            .into_iter()
+            .partition(|(_, _, _, job_id)| *job_id < self.jobs_end);
+        self.early_steps = waiting;
+        for (participant_num, step_num, input_data, job_id) in started {
+            self.execute_step_message(participant_num, step_num, input_data, job_id);
+        }
+    }
+
+    fn execute_step_message(&self, participant_num: usize, step_num: usize, input_data: Vec<ProtocolTransferredData>, job_id: u64) {
+        let job_data = Arc::clone(&self.job_data);
+        let id = self.id;
+        self.thread_pool.spawn(move || {
+            // Update job_data using DashMap's concurrent API
+            // job_data.entry(job_id).and_modify(|worker| {
+            //     handle_protocol_execute_step(worker, job_id, self.id, participant_num, step_num, input_data);
+            // });
+
+            // in the multithreaded case it's possible the worker needs to receive data but we didn't even finish initalizing it yet
+            loop {
+                if let Some(mut worker) = job_data.get_mut(&job_id) {
+                    let result = handle_protocol_execute_step(&mut worker, job_id, id, participant_num, step_num, input_data);
+
+                    // A finished job gets no more steps
+                    if let ExecutionResult::Finished = result {
+                        let online_time = worker.online_time();
+                        drop(worker);
+                        job_data.remove(&job_id);
+                        send_to_discovery_server(&Message::JobDone(id, job_id, online_time.as_micros() as u64));
+                    }
+                    break;
+                } else {
+                    // Entry not found yet, wait before retrying
+                    thread::sleep(Duration::from_millis(10)); // Adjust delay as needed
+                }
+            }
+        });
+
+        // self.thread_pool.spawn(|| {
+        //     handle_protocol_execute_step(&mut self.job_data, job_id, self.id, participant_num, step_num, input_data);
+        // });
+    }
+
+    // The discovery server starts an epoch once no job is running, so every job uses shares of a single epoch
+    fn start_key_refresh(&mut self, epoch: u64) {
+        let key_refresh = KeyRefresh::new(&self.public_parameters);
+        let mut pieces = key_refresh.zero_sharing(self.public_parameters.lwe_dimension);
+        let mut mac_pieces = key_refresh.zero_sharing(self.public_parameters.lwe_dimension);
+        for (receiver, (piece, mac_piece)) in pieces.iter().zip(&mac_pieces).enumerate() {
+            if receiver != self.id {
+                send_to_participant(receiver, &Message::KeyRefreshPiece(self.id, epoch, piece.clone(), mac_piece.clone()));
+            }
+        }
+
+        self.receive_key_refresh_piece(self.id, epoch, (pieces.swap_remove(self.id), mac_pieces.swap_remove(self.id)));
+    }
+
+    // Pieces of an epoch are buffered until those of every participant arrived, they may come before KeyRefreshStart
+    fn receive_key_refresh_piece(&mut self, sender: usize, epoch: u64, piece: KeyPieces) {
+        let params = &self.public_parameters;
+        let epoch_pieces = self.key_refresh_pieces.entry(epoch).or_default();
+        epoch_pieces.insert(sender, piece);
+        if epoch_pieces.len() < params.n {
+            return;
+        }
+
+        let (pieces, mac_pieces): (Vec<_>, Vec<_>) = self.key_refresh_pieces.remove(&epoch).unwrap().into_values().unzip();
+        match store_refreshed_sk(params, self.id, &KeyRefresh::new(params), &pieces, &mac_pieces) {
+            Ok(()) => {
+                println!("Key share refreshed. participant: {}, epoch: {}", self.id, epoch);
+                send_to_discovery_server(&Message::KeyRefreshDone(self.id, epoch));
+            }
+            Err(e) => eprintln!("Failed to store the refreshed key share: {}", e),
+        }
+    }
+
+    // Old members split their key share among the new committee, new members wait for a piece from every old member
+    fn start_resharing(&mut self, old_committee_size: usize, new_committee_size: usize) {
+        let old_params = self.public_parameters.with_committee_size(old_committee_size);
+        let resharing = Resharing::new(&old_params, new_committee_size);
+
+        // The new committee runs the protocol from scratch
+        self.job_data.clear();
+        self.preprocessing_step = 0;
+        self.pending_jobs = None;
+        self.early_steps.clear();
+
+        if self.id < old_committee_size {
+            let (sk, sk_mac) = match load_sk(&old_params, self.id) {
+                Ok(key_share) => key_share,
+                Err(e) => {
+                    eprintln!("Failed to load the key share to reshare: {}", e);
+                    return;
+                }
+            };
+
+            for (receiver, (piece, mac_piece)) in resharing.split(&sk).into_iter().zip(resharing.split(&sk_mac)).enumerate() {
+                if receiver == self.id {
+                    self.reshare_pieces.insert(self.id, (piece, mac_piece));
+                } else {
+                    send_to_participant(receiver, &Message::ResharePiece(self.id, piece, mac_piece));
+                }
+            }
+
+            if self.id >= new_committee_size {
+                match remove_participant_data(self.id) {
+                    Ok(()) => println!("Left the committee. participant: {}", self.id),
+                    Err(e) => eprintln!("Failed to delete the key share: {}", e),
+                }
+                return;
+            }
+        }
+
+        self.public_parameters = resharing.new_params.clone();
+        self.resharing = Some(resharing);
+        self.advance_resharing();
+    }
+
+    fn advance_resharing(&mut self) {
+        let Some(resharing) = self.resharing.as_ref() else {
+            return;
+        };
+        if self.reshare_pieces.len() < resharing.old_params.n {
+            return;
+        }
+
+        let (pieces, mac_pieces): (Vec<_>, Vec<_>) = std::mem::take(&mut self.reshare_pieces).into_values().unzip();
+        let (sk, sk_mac) = (resharing.combine(&pieces), resharing.combine(&mac_pieces));
+        self.resharing = None;
+
+        match store_reshared_sk(&self.public_parameters, self.id, &sk, &sk_mac) {
+            Ok(()) => {
+                println!("Key share reshared. participant: {}, committee size: {}", self.id, self.public_parameters.n);
+                send_to_discovery_server(&Message::ReshareDone(self.id));
+            }
+            Err(e) => eprintln!("Failed to store the reshared key share: {}", e),
+        }
+    }
+
+    // The jobs of the old committee wait for steps of members that left, the new committee runs its own from scratch
+    // on the data the discovery server staged for it
+    fn join_committee(&mut self, members: Vec<usize>) {
+        self.job_data.clear();
+        self.early_steps.clear();
+
+        let committee_params = self.public_parameters.with_committee_size(members.len());
+        let stored = load_threshold_sk(&self.public_parameters, self.id, &members)
+            .and_then(|(sk, sk_mac)| store_reshared_sk(&committee_params, self.id, &sk, &sk_mac));
+        match stored {
+            Ok(()) => println!("Key share turned additive. participant: {}, committee: {:?}", self.id, members),
+            Err(e) => eprintln!("Failed to store the key share of the committee: {}", e),
+        }
+
+        self.committee = Some(members);
+    }
+
+    // Executes every preprocessing step whose messages have all arrived
+    fn advance_preprocessing(&mut self) {
+        while let Some((setup, party)) = self.preprocessing.as_mut() {
+            let expected = setup.expected_senders(self.id, self.preprocessing_step).len();
+            let received = self.preprocessing_inbox.get(&self.preprocessing_step).map_or(0, |inbox| inbox.len());
+            if received < expected {
+                return;
+            }
+
+            let input = self.preprocessing_inbox.remove(&self.preprocessing_step).unwrap_or_default();
+            let output = party.execute_step(self.preprocessing_step, input);
+            self.preprocessing_step += 1;
+
+            for (receiver, message) in output {
+                send_to_participant(receiver, &Message::PreprocessingStep(self.id, self.preprocessing_step, message));
+            }
+
+            if self.preprocessing_step == setup.step_count() {
+                let started = Instant::now();
+                let (_, party) = self.preprocessing.take().unwrap();
+                if let Err(e) = store_preprocessed_share(&self.public_parameters, self.id, &party) {
+                    eprintln!("Failed to store the distributed preprocessing: {}", e);
+                    return;
+                }
+                debug!("Distributed preprocessing stored in {} microseconds", started.elapsed().as_micros());
+                if let Some(assignment) = self.pending_jobs.take() {
+                    self.start_protocol(assignment);
+                }
+            }
+        }
+    }
+
     fn discovered_participant(&mut self, name: &str, addr: SocketAddr) {
         let mut network_sender = NETWORK_SENDER.lock().unwrap();
         let sender_mut = network_sender.as_mut().unwrap();
@@ -262,15 +639,46 @@ impl Participant {
     }
 }
 
-pub fn send_result_to_everyone(data: &Vec<ProtocolTransferredData>, step: usize, job_id: usize, participant_id: usize) {
+pub fn send_to_discovery_server(message: &Message) {
+    let mut network_sender = NETWORK_SENDER.lock().unwrap();
+    let sender_mut = network_sender.as_mut().unwrap();
+
+    let output_data = serialize(message).unwrap();
+    sender_mut.handler.network().send(sender_mut.discovery_endpoint, &output_data);
+}
+
+pub fn send_to_participant(participant_id: usize, message: &Message) {
+    let participants = known_participants.read().unwrap();
+    let mut network_sender = NETWORK_SENDER.lock().unwrap();
+    let sender_mut = network_sender.as_mut().unwrap();
+
+    let Some(endpoint) = participants.get(&participant_id.to_string()) else {
+        eprintln!("Unknown participant '{}'", participant_id);
+        return;
+    };
+
+    let output_data = serialize(message).unwrap();
+    match sender_mut.handler.network().send(*endpoint, &output_data) {
+        SendStatus::Sent => debug!("Successfully sent message to participant '{}'", participant_id),
+        _ => eprintln!("Failed to send message to participant '{}'", participant_id),
+    }
+}
+
+/// Sends a protocol step to the other members of the committee, participants waiting to join it or left out of it
+/// get nothing
+pub fn send_result_to_everyone(data: &[ProtocolTransferredData], step: usize, job_id: usize, participant_id: usize, members: &[usize]) {
     let participants = known_participants.read().unwrap();
     let mut network_sender = NETWORK_SENDER.lock().unwrap();
     let sender_mut = network_sender.as_mut().unwrap();
 
     for (participant, info) in participants.iter() {
+        if participant.parse::<usize>().map_or(true, |id| !members.contains(&id)) {
+            continue;
+        }
+
         debug!("JOB {}, Sending ProtocolExecuteStep {} to participant '{}'", job_id, step, participant);
 
-        let message = Message::ProtocolExecuteStep(participant_id, step, data.clone(), job_id as u64);
+        let message = Message::ProtocolExecuteStep(participant_id, step, data.to_vec(), job_id as u64);
         let output_data = match bitcode::serialize(&message) {
             Ok(data) => data,
             Err(e) => {
@@ -279,7 +687,7 @@ pub fn send_result_to_everyone(data: &Vec<ProtocolTransferredData>, step: usize,
             }
         };
 
-        match sender_mut.handler.network().send(info.clone(), &output_data) {
+        match sender_mut.handler.network().send(*info, &output_data) {
             SendStatus::Sent => debug!("Successfully sent ProtocolExecuteStep to participant '{}'", participant),
             _ => eprintln!("Failed to send ProtocolExecuteStep to participant '{}'", participant),
         }
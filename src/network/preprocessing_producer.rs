@@ -0,0 +1,184 @@
+use std::io;
+use std::ops::Range;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use crate::mpc::preprocessing::Preprocessing;
+use crate::network::preprocessing_store::PreprocessingStore;
+
+// Offline phase that keeps running next to the online one: a thread of the dealer watches how many instances the
+// stores of the committee have left and deals another batch to every store once one of them drops below the
+// low-water mark. The batch is appended at the same index everywhere, so instance j stays the same at every party,
+// and appending does not block the jobs taking instances meanwhile.
+//
+// The dealer is shared with the discovery server, which sets it once the committee holds its first batch and clears
+// it while a committee change replaces the stores.
+
+/// How often the stores are checked
+pub const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Preprocessing the producer deals with, None while there is nothing to top up
+pub type Dealer = Arc<Mutex<Option<Preprocessing>>>;
+
+/// A batch dealt by the producer
+#[derive(Debug, Clone, PartialEq)]
+pub struct TopUp {
+    pub instances: Range<usize>,
+    /// Instances the emptiest store had left before the batch
+    pub remaining: usize,
+    /// Time to generate the batch and write it to every store
+    pub offline_time: Duration,
+}
+
+pub struct PreprocessingProducer {
+    pub low_water_mark: usize,
+    pub batch_size: usize,
+    dealer: Dealer,
+    store_directory: fn(usize) -> PathBuf,
+}
+
+impl PreprocessingProducer {
+    pub fn new(low_water_mark: usize, batch_size: usize) -> PreprocessingProducer {
+        PreprocessingProducer {
+            low_water_mark,
+            batch_size,
+            dealer: Arc::new(Mutex::new(None)),
+            store_directory: PreprocessingStore::directory,
+        }
+    }
+
+    /// Looks for the store of a party somewhere else than `PreprocessingStore::directory`
+    pub fn with_store_directory(mut self, store_directory: fn(usize) -> PathBuf) -> PreprocessingProducer {
+        self.store_directory = store_directory;
+        self
+    }
+
+    /// Handle to set the preprocessing that is dealt
+    pub fn dealer(&self) -> Dealer {
+        Arc::clone(&self.dealer)
+    }
+
+    /// Deals a batch when a store of the committee of `preprocessing` is below the low-water mark
+    pub fn top_up(&self, preprocessing: &Preprocessing) -> io::Result<Option<TopUp>> {
+        let params = &preprocessing.params;
+
+        let mut stores = (0..params.n)
+            .map(|i| PreprocessingStore::open(&(self.store_directory)(i), params, i))
+            .collect::<io::Result<Vec<_>>>()?;
+
+        let first = stores[0].end();
+        if stores.iter().any(|store| store.end() != first) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "The preprocessing stores of the committee end at different instances"));
+        }
+
+        let remaining = stores.iter().map(|store| store.remaining()).min().unwrap_or(0);
+        if remaining >= self.low_water_mark {
+            return Ok(None);
+        }
+
+        let started = Instant::now();
+        let shares = preprocessing.run_instances_from(first, self.batch_size);
+        for (store, shares) in stores.iter_mut().zip(shares) {
+            store.append(first, &shares)?;
+        }
+
+        Ok(Some(TopUp {
+            instances: first..first + self.batch_size,
+            remaining,
+            offline_time: started.elapsed(),
+        }))
+    }
+
+    /// Checks the stores every `POLL_INTERVAL` for as long as the process runs
+    pub fn spawn(self) -> JoinHandle<()> {
+        thread::spawn(move || loop {
+            thread::sleep(POLL_INTERVAL);
+
+            // Held during the whole top-up, a committee change waits for the batch to be written
+            let dealer = self.dealer.lock().unwrap();
+            let Some(preprocessing) = dealer.as_ref() else {
+                continue;
+            };
+
+            match self.top_up(preprocessing) {
+                Ok(Some(top_up)) => println!(
+                    "Offline: preprocessing topped up. instances: {:?}, left before: {}, microseconds: {}",
+                    top_up.instances, top_up.remaining, top_up.offline_time.as_micros()),
+                Ok(None) => {}
+                Err(e) => eprintln!("Failed to top up the preprocessing: {}", e),
+            }
+        })
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+    use nalgebra::{DMatrix, DVector};
+    use num_bigint::BigInt;
+    use num_integer::Integer;
+    use num_traits::One;
+    use crate::mpc::preprocessing::{PreprocessedShare, Preprocessing};
+    use crate::mpc::prss::PrssKeys;
+    use crate::mpc::public_params::PublicParameters;
+    use crate::network::preprocessing_producer::PreprocessingProducer;
+    use crate::network::preprocessing_store::PreprocessingStore;
+
+    fn open_tables(params: &PublicParameters, shares: &[Vec<PreprocessedShare>], instance: usize) -> (DVector<BigInt>, DMatrix<BigInt>) {
+        let (ltz, signs) = shares.iter()
+            .map(|party_shares| party_shares[instance].tables.expand(params))
+            .map(|tables| (tables.ltz, tables.signs))
+            .reduce(|(ltz, signs), (x, y)| (ltz + x, signs + y))
+            .unwrap();
+
+        let ltz_modulo = BigInt::one() << params.m;
+        let signs_modulo = BigInt::from(params.big_d);
+        (ltz.map(|x| x.mod_floor(&ltz_modulo)), signs.map(|x| x.mod_floor(&signs_modulo)))
+    }
+
+    fn store_directory(party: usize) -> PathBuf {
+        std::env::temp_dir().join(format!("preprocessing_producer_test_{}_{}", std::process::id(), party))
+    }
+
+    #[test]
+    fn test_producer_tops_up_below_the_low_water_mark() {
+        let params = PublicParameters::init(3, 32, 1, 5, 8, 0);
+        let preprocessing = Preprocessing::new(&params).with_prss(PrssKeys::deal(params.n));
+
+        let mut stores: Vec<PreprocessingStore> = (0..params.n)
+            .map(|i| PreprocessingStore::create(&store_directory(i), &params, i).unwrap())
+            .collect();
+        for (store, shares) in stores.iter_mut().zip(preprocessing.run_instances(4)) {
+            store.append(0, &shares).unwrap();
+        }
+
+        let producer = PreprocessingProducer::new(3, 5).with_store_directory(store_directory);
+        assert_eq!(producer.top_up(&preprocessing).unwrap(), None);
+
+        // One party running ahead is enough
+        stores[1].take(0, 2).unwrap();
+        let top_up = producer.top_up(&preprocessing).unwrap().unwrap();
+        assert_eq!((top_up.instances, top_up.remaining), (4..9, 2));
+        assert_eq!(producer.top_up(&preprocessing).unwrap(), None);
+
+        // The new instances are those of their PRSS nonces, their tables open to the ones of dealing them up front
+        let taken: Vec<Vec<PreprocessedShare>> = (0..params.n)
+            .map(|i| {
+                let mut store = PreprocessingStore::open(&store_directory(i), &params, i).unwrap();
+                assert_eq!(store.capacity(), 9);
+                store.take(4, 5).unwrap()
+            })
+            .collect();
+        let expected = preprocessing.run_instances_from(4, 5);
+        for j in 0..5 {
+            assert_eq!(open_tables(&params, &taken, j), open_tables(&params, &expected, j));
+        }
+
+        for i in 0..params.n {
+            fs::remove_dir_all(store_directory(i)).unwrap();
+        }
+    }
+}
@@ -0,0 +1,254 @@
+use std::collections::BTreeSet;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use crate::mpc::preprocessing::PreprocessedShare;
+use crate::mpc::public_params::PublicParameters;
+use crate::network::storage::{decode_value, encode_value, write_atomically, ContentType};
+
+// Preprocessing instances of one party on disk, every instance is handed out at most once, across restarts too.
+//
+// The store is a directory holding
+//  - batch-<first>-<count>.bin: instances first..first + count in the framed format, written to a temporary file,
+//    synced and renamed into place, so a batch is either complete or absent,
+//  - consumed.log: 16-byte records (first u64, count u64 little endian) of the instances handed out.
+// A record is synced before `take` returns the instances, so nothing derived from them can have left the process
+// before their use is on disk. A crash while writing a record leaves a torn tail, whose `take` never returned, and
+// which is ignored. Instances taken but never used are lost, never reused.
+//
+// Instance j is the same at every party, it has nonce j for the PRSS, and the parties take the instances of a job by
+// their indices, since jobs start concurrently and in different orders at different parties.
+
+const CONSUMED_LOG: &str = "consumed.log";
+
+const RECORD_LEN: usize = 16;
+
+pub struct PreprocessingStore {
+    dir: PathBuf,
+    params: PublicParameters,
+    party: usize,
+
+    /// Index ranges of the batches, in order
+    batches: Vec<Range<usize>>,
+    consumed: BTreeSet<usize>,
+}
+
+impl PreprocessingStore {
+    /// Directory of the store of a participant
+    pub fn directory(party: usize) -> PathBuf {
+        PathBuf::from(format!("/tmp/participant_data/{}.preprocessing", party))
+    }
+
+    /// Directory where the store of the next committee is prepared during a committee change
+    pub fn staged_directory(party: usize) -> PathBuf {
+        PathBuf::from(format!("/tmp/participant_data/{}.next.preprocessing", party))
+    }
+
+    /// Empty store in `dir`, replacing whatever was there
+    pub fn create(dir: &Path, params: &PublicParameters, party: usize) -> io::Result<PreprocessingStore> {
+        if dir.exists() {
+            fs::remove_dir_all(dir)?;
+        }
+        fs::create_dir_all(dir)?;
+
+        PreprocessingStore::open(dir, params, party)
+    }
+
+    /// Opens an existing store and replays what was consumed
+    pub fn open(dir: &Path, params: &PublicParameters, party: usize) -> io::Result<PreprocessingStore> {
+        let mut batches = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let name = entry?.file_name().into_string().unwrap_or_default();
+            if let Some(range) = parse_batch_name(&name) {
+                batches.push(range);
+            }
+        }
+        batches.sort_by_key(|range| range.start);
+
+        if batches.windows(2).any(|pair| pair[0].end != pair[1].start) || batches.first().is_some_and(|first| first.start != 0) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("Preprocessing batches in {:?} leave gaps", dir)));
+        }
+
+        let mut consumed = BTreeSet::new();
+        match fs::read(dir.join(CONSUMED_LOG)) {
+            Ok(log) => {
+                for record in log.chunks_exact(RECORD_LEN) {
+                    let first = u64::from_le_bytes(record[..8].try_into().unwrap()) as usize;
+                    let count = u64::from_le_bytes(record[8..].try_into().unwrap()) as usize;
+                    consumed.extend(first..first + count);
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+
+        Ok(PreprocessingStore {
+            dir: dir.to_path_buf(),
+            params: params.clone(),
+            party,
+            batches,
+            consumed,
+        })
+    }
+
+    /// Index the next appended batch starts at
+    pub fn end(&self) -> usize {
+        self.batches.last().map_or(0, |range| range.end)
+    }
+
+    /// Number of instances ever appended
+    pub fn capacity(&self) -> usize {
+        self.end()
+    }
+
+    /// Number of instances not handed out yet
+    pub fn remaining(&self) -> usize {
+        self.capacity() - self.consumed.len()
+    }
+
+    /// Lowest index not handed out yet
+    pub fn next_unused(&self) -> Option<usize> {
+        (0..self.end()).find(|index| !self.consumed.contains(index))
+    }
+
+    /// Adds the instances `first..first + shares.len()`, `first` must be `end()` so that the indices stay the same at
+    /// every party
+    pub fn append(&mut self, first: usize, shares: &[PreprocessedShare]) -> io::Result<Range<usize>> {
+        if first != self.end() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                format!("Batch starts at instance {}, the store of party {} ends at {}", first, self.party, self.end())));
+        }
+        let range = first..first + shares.len();
+        if range.is_empty() {
+            return Ok(range);
+        }
+
+        let bytes = encode_value(&self.params, self.party, ContentType::PreprocessingBatch, &(first as u64, shares));
+        write_atomically(self.dir.join(batch_name(&range)), &bytes)?;
+
+        self.batches.push(range.clone());
+        Ok(range)
+    }
+
+    /// Hands out the instances `first..first + count`, or none when any of them is missing or already used. They are
+    /// recorded as consumed on disk before this returns
+    pub fn take(&mut self, first: usize, count: usize) -> io::Result<Vec<PreprocessedShare>> {
+        let range = first..first + count;
+        if range.end > self.end() || range.clone().any(|index| self.consumed.contains(&index)) {
+            return Err(io::Error::other(format!(
+                "Instances {:?} are not all fresh, {} of {} left", range, self.remaining(), self.capacity())));
+        }
+
+        // Read before recording, a batch that fails to load does not cost the instances
+        let mut shares = Vec::with_capacity(count);
+        for batch in self.batches.iter().filter(|batch| batch.start < range.end && range.start < batch.end) {
+            let mut batch_shares = self.read_batch(batch)?;
+            let from = range.start.max(batch.start) - batch.start;
+            let to = range.end.min(batch.end) - batch.start;
+            shares.extend(batch_shares.drain(from..to));
+        }
+
+        let mut record = [0u8; RECORD_LEN];
+        record[..8].copy_from_slice(&(first as u64).to_le_bytes());
+        record[8..].copy_from_slice(&(count as u64).to_le_bytes());
+
+        let mut log = OpenOptions::new().create(true).append(true).open(self.dir.join(CONSUMED_LOG))?;
+        self.trim_torn_record(&log)?;
+        log.write_all(&record)?;
+        log.sync_data()?;
+
+        self.consumed.extend(range);
+        Ok(shares)
+    }
+
+    /// Hands out the next `count` unused instances in a row, with the index of the first
+    pub fn take_next(&mut self, count: usize) -> io::Result<(usize, Vec<PreprocessedShare>)> {
+        let first = self.next_unused().unwrap_or(self.end());
+        self.take(first, count).map(|shares| (first, shares))
+    }
+
+    fn read_batch(&self, range: &Range<usize>) -> io::Result<Vec<PreprocessedShare>> {
+        let bytes = fs::read(self.dir.join(batch_name(range)))?;
+        let (first, shares): (u64, Vec<PreprocessedShare>) =
+            decode_value(&bytes, &self.params, self.party, ContentType::PreprocessingBatch)?;
+
+        if first as usize != range.start || shares.len() != range.len() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("Batch {:?} holds other instances", range)));
+        }
+
+        Ok(shares)
+    }
+
+    // Records start at multiples of RECORD_LEN, a torn one from a crash is cut off before appending
+    fn trim_torn_record(&self, log: &File) -> io::Result<()> {
+        let len = log.metadata()?.len();
+        let whole = len - len % RECORD_LEN as u64;
+        if whole != len {
+            log.set_len(whole)?;
+        }
+        Ok(())
+    }
+}
+
+fn batch_name(range: &Range<usize>) -> String {
+    format!("batch-{}-{}.bin", range.start, range.len())
+}
+
+fn parse_batch_name(name: &str) -> Option<Range<usize>> {
+    let (first, count) = name.strip_prefix("batch-")?.strip_suffix(".bin")?.split_once('-')?;
+    let (first, count): (usize, usize) = (first.parse().ok()?, count.parse().ok()?);
+
+    Some(first..first + count)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{self, OpenOptions};
+    use std::io::Write;
+    use crate::mpc::preprocessing::Preprocessing;
+    use crate::mpc::public_params::PublicParameters;
+    use crate::network::preprocessing_store::{PreprocessingStore, CONSUMED_LOG};
+
+    #[test]
+    fn test_instances_are_handed_out_once_across_restarts() {
+        let params = PublicParameters::init(3, 32, 1, 5, 8, 0);
+        let shares = Preprocessing::new(&params).run_instances(6).swap_remove(1);
+
+        let dir = std::env::temp_dir().join(format!("preprocessing_store_test_{}", std::process::id()));
+        let mut store = PreprocessingStore::create(&dir, &params, 1).unwrap();
+        assert_eq!(store.append(0, &shares[..4]).unwrap(), 0..4);
+        assert!(store.append(0, &shares[4..]).is_err());
+        assert_eq!(store.append(4, &shares[4..]).unwrap(), 4..6);
+
+        // Across the two batches
+        let taken = store.take(3, 2).unwrap();
+        assert_eq!(taken[0].tables, shares[3].tables);
+        assert_eq!(taken[1].tables, shares[4].tables);
+        assert!(store.take(4, 1).is_err());
+        assert!(store.take(5, 2).is_err());
+        assert_eq!(store.remaining(), 4);
+
+        let (first, taken) = store.take_next(2).unwrap();
+        assert_eq!((first, taken[1].tables.clone()), (0, shares[1].tables.clone()));
+
+        // A crash in the middle of the next record
+        let mut log = OpenOptions::new().append(true).open(dir.join(CONSUMED_LOG)).unwrap();
+        log.write_all(&[5, 0, 0]).unwrap();
+        drop(store);
+
+        let mut store = PreprocessingStore::open(&dir, &params, 1).unwrap();
+        assert_eq!((store.capacity(), store.remaining(), store.next_unused()), (6, 2, Some(2)));
+        assert!(store.take(0, 1).is_err());
+        assert_eq!(store.take(5, 1).unwrap()[0].tables, shares[5].tables);
+        assert_eq!(PreprocessingStore::open(&dir, &params, 1).unwrap().remaining(), 1);
+
+        // Batches of another committee are refused
+        let mut other = PreprocessingStore::open(&dir, &params.with_committee_size(4), 1).unwrap();
+        assert!(other.take(2, 1).is_err());
+        assert_eq!(PreprocessingStore::open(&dir, &params, 1).unwrap().remaining(), 1);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
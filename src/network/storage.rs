@@ -0,0 +1,447 @@
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::Path;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use crate::mpc::public_params::PublicParameters;
+use crate::network::ProtocolTransferredData;
+
+use bitcode::{serialize, deserialize};
+
+// Framed format of the files the participants keep: keys, ciphertexts and preprocessing.
+//
+//   magic "TDEC" | version u16 | content type u8 | party u32 | parameter fingerprint u64 | section count u16
+//   | per section: tag u8, length u64, payload | checksum u64
+//
+// Integers are little endian, the payloads are bitcode and the checksum is FNV-1a over everything before it. A
+// loader states the parameters, the party and the content it expects, so a file of another run or another party is
+// refused before any payload is decoded.
+
+pub const MAGIC: [u8; 4] = *b"TDEC";
+
+pub const FORMAT_VERSION: u16 = 1;
+
+const HEADER_LEN: usize = 4 + 2 + 1 + 4 + 8 + 2;
+
+const CHECKSUM_LEN: usize = 8;
+
+/// What a file holds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentType {
+    /// Everything a participant loads at the start of a job
+    ParticipantData = 1,
+    KeyShare = 2,
+    Ciphertext = 3,
+    PublicKey = 4,
+    /// Instances of a `PreprocessingStore` batch
+    PreprocessingBatch = 5,
+}
+
+impl ContentType {
+    fn from_byte(byte: u8) -> Result<ContentType, FormatError> {
+        match byte {
+            1 => Ok(ContentType::ParticipantData),
+            2 => Ok(ContentType::KeyShare),
+            3 => Ok(ContentType::Ciphertext),
+            4 => Ok(ContentType::PublicKey),
+            5 => Ok(ContentType::PreprocessingBatch),
+            _ => Err(FormatError::UnknownContentType(byte)),
+        }
+    }
+}
+
+/// Type of a payload section
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Section {
+    /// The single value of a file that is not participant data
+    Value = 1,
+    KeyShare = 2,
+    CiphertextA = 3,
+    CiphertextB = 4,
+    /// MAC shares of the key share
+    KeyMac = 5,
+    MacKeyShare = 6,
+    PrssKeys = 7,
+    /// Mask and its MAC of a generated key share
+    KeyInputMask = 8,
+}
+
+impl Section {
+    fn from_byte(byte: u8) -> Result<Section, FormatError> {
+        match byte {
+            1 => Ok(Section::Value),
+            2 => Ok(Section::KeyShare),
+            3 => Ok(Section::CiphertextA),
+            4 => Ok(Section::CiphertextB),
+            5 => Ok(Section::KeyMac),
+            6 => Ok(Section::MacKeyShare),
+            7 => Ok(Section::PrssKeys),
+            8 => Ok(Section::KeyInputMask),
+            _ => Err(FormatError::UnknownSection(byte)),
+        }
+    }
+}
+
+/// Why a file cannot be loaded
+#[derive(Debug, Clone, PartialEq)]
+pub enum FormatError {
+    /// The file ends inside the header, a section or the checksum
+    Truncated,
+    BadMagic,
+    UnsupportedVersion { found: u16 },
+    ChecksumMismatch,
+    UnknownContentType(u8),
+    UnknownSection(u8),
+    DuplicateSection(Section),
+    MissingSection(Section),
+    UnexpectedContent { expected: ContentType, found: ContentType },
+    /// The file belongs to another participant
+    WrongParty { expected: usize, found: usize },
+    /// The file was written for other public parameters, e.g. another committee size
+    ParameterMismatch { expected: u64, found: u64 },
+    /// A section does not decode to the type it stands for
+    InvalidPayload(Section),
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FormatError::Truncated =>
+                write!(f, "File is truncated"),
+            FormatError::BadMagic =>
+                write!(f, "Not a threshold decryption file, the magic number is missing"),
+            FormatError::UnsupportedVersion { found } =>
+                write!(f, "Format version {} is not supported, expected {}", found, FORMAT_VERSION),
+            FormatError::ChecksumMismatch =>
+                write!(f, "Checksum mismatch, the file is corrupted"),
+            FormatError::UnknownContentType(byte) =>
+                write!(f, "Unknown content type {}", byte),
+            FormatError::UnknownSection(byte) =>
+                write!(f, "Unknown section type {}", byte),
+            FormatError::DuplicateSection(section) =>
+                write!(f, "Section {:?} appears twice", section),
+            FormatError::MissingSection(section) =>
+                write!(f, "Section {:?} is missing", section),
+            FormatError::UnexpectedContent { expected, found } =>
+                write!(f, "File holds {:?}, expected {:?}", found, expected),
+            FormatError::WrongParty { expected, found } =>
+                write!(f, "File belongs to party {}, expected party {}", found, expected),
+            FormatError::ParameterMismatch { expected, found } =>
+                write!(f, "File was written for parameters {:016x}, these are {:016x}", found, expected),
+            FormatError::InvalidPayload(section) =>
+                write!(f, "Section {:?} does not decode", section),
+        }
+    }
+}
+
+impl std::error::Error for FormatError {}
+
+impl From<FormatError> for io::Error {
+    fn from(error: FormatError) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, error)
+    }
+}
+
+/// 64-bit FNV-1a, stable across builds and platforms
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, &byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3))
+}
+
+/// Fingerprint of the parameters everything else is derived from
+pub fn fingerprint(params: &PublicParameters) -> u64 {
+    let mut bytes = Vec::new();
+    for value in [params.n, params.k, params.m, params.b, params.b_prime, params.lwe_dimension, params.mac_s] {
+        bytes.extend_from_slice(&(value as u64).to_le_bytes());
+    }
+    bytes.extend(serialize(&params.error_distribution).unwrap());
+    bytes.extend(serialize(&params.key_distribution).unwrap());
+
+    fnv1a(&bytes)
+}
+
+/// Header of a file
+#[derive(Debug, Clone, PartialEq)]
+pub struct Header {
+    pub version: u16,
+    pub content_type: ContentType,
+    pub party: usize,
+    pub fingerprint: u64,
+}
+
+impl Header {
+    pub fn new(params: &PublicParameters, party: usize, content_type: ContentType) -> Header {
+        Header {
+            version: FORMAT_VERSION,
+            content_type,
+            party,
+            fingerprint: fingerprint(params),
+        }
+    }
+}
+
+/// A decoded file
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    pub header: Header,
+    pub sections: Vec<(Section, Vec<u8>)>,
+}
+
+impl Frame {
+    pub fn new(header: Header) -> Frame {
+        Frame {
+            header,
+            sections: Vec::new(),
+        }
+    }
+
+    pub fn with_section(mut self, section: Section, payload: Vec<u8>) -> Frame {
+        self.sections.push((section, payload));
+        self
+    }
+
+    pub fn section(&self, section: Section) -> Option<&[u8]> {
+        self.sections.iter()
+            .find(|(tag, _)| *tag == section)
+            .map(|(_, payload)| payload.as_slice())
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_LEN + CHECKSUM_LEN);
+        bytes.extend_from_slice(&MAGIC);
+        bytes.extend_from_slice(&self.header.version.to_le_bytes());
+        bytes.push(self.header.content_type as u8);
+        bytes.extend_from_slice(&(self.header.party as u32).to_le_bytes());
+        bytes.extend_from_slice(&self.header.fingerprint.to_le_bytes());
+        bytes.extend_from_slice(&(self.sections.len() as u16).to_le_bytes());
+
+        for (section, payload) in &self.sections {
+            bytes.push(*section as u8);
+            bytes.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+            bytes.extend_from_slice(payload);
+        }
+
+        let checksum = fnv1a(&bytes);
+        bytes.extend_from_slice(&checksum.to_le_bytes());
+        bytes
+    }
+
+    /// Decodes any well-formed file, `decode_expected` also checks whom it belongs to
+    pub fn decode(bytes: &[u8]) -> Result<Frame, FormatError> {
+        if bytes.len() < MAGIC.len() {
+            return Err(FormatError::Truncated);
+        }
+        if bytes[..MAGIC.len()] != MAGIC {
+            return Err(FormatError::BadMagic);
+        }
+        if bytes.len() < HEADER_LEN + CHECKSUM_LEN {
+            return Err(FormatError::Truncated);
+        }
+
+        // The version decides the layout of the rest, it is checked before the checksum
+        let version = u16::from_le_bytes(bytes[4..6].try_into().unwrap());
+        if version != FORMAT_VERSION {
+            return Err(FormatError::UnsupportedVersion { found: version });
+        }
+
+        let (body, checksum) = bytes.split_at(bytes.len() - CHECKSUM_LEN);
+        if fnv1a(body) != u64::from_le_bytes(checksum.try_into().unwrap()) {
+            return Err(FormatError::ChecksumMismatch);
+        }
+
+        let header = Header {
+            version,
+            content_type: ContentType::from_byte(body[6])?,
+            party: u32::from_le_bytes(body[7..11].try_into().unwrap()) as usize,
+            fingerprint: u64::from_le_bytes(body[11..19].try_into().unwrap()),
+        };
+        let section_count = u16::from_le_bytes(body[19..21].try_into().unwrap());
+
+        let mut frame = Frame::new(header);
+        let mut rest = &body[HEADER_LEN..];
+        for _ in 0..section_count {
+            if rest.len() < 9 {
+                return Err(FormatError::Truncated);
+            }
+            let section = Section::from_byte(rest[0])?;
+            let len = u64::from_le_bytes(rest[1..9].try_into().unwrap());
+            rest = &rest[9..];
+
+            if (rest.len() as u64) < len {
+                return Err(FormatError::Truncated);
+            }
+            if frame.section(section).is_some() {
+                return Err(FormatError::DuplicateSection(section));
+            }
+
+            let (payload, tail) = rest.split_at(len as usize);
+            frame.sections.push((section, payload.to_vec()));
+            rest = tail;
+        }
+
+        if !rest.is_empty() {
+            return Err(FormatError::Truncated);
+        }
+
+        Ok(frame)
+    }
+
+    /// Decodes a file and checks that it holds `content_type` of `party` for `params`
+    pub fn decode_expected(bytes: &[u8], params: &PublicParameters, party: usize, content_type: ContentType) -> Result<Frame, FormatError> {
+        let frame = Frame::decode(bytes)?;
+
+        if frame.header.content_type != content_type {
+            return Err(FormatError::UnexpectedContent { expected: content_type, found: frame.header.content_type });
+        }
+        if frame.header.party != party {
+            return Err(FormatError::WrongParty { expected: party, found: frame.header.party });
+        }
+        let expected = fingerprint(params);
+        if frame.header.fingerprint != expected {
+            return Err(FormatError::ParameterMismatch { expected, found: frame.header.fingerprint });
+        }
+
+        Ok(frame)
+    }
+}
+
+/// Replaces the file at `path` with `bytes` such that a crash leaves either the old or the new file, never a torn one:
+/// the bytes go to a temporary file next to it, which is synced and renamed into place
+pub fn write_atomically(path: impl AsRef<Path>, bytes: &[u8]) -> io::Result<()> {
+    let path = path.as_ref();
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+
+    let mut file = File::create(&temporary)?;
+    file.write_all(bytes)?;
+    file.sync_all()?;
+    fs::rename(&temporary, path)?;
+
+    // The rename is durable only once the directory is synced
+    match path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        Some(dir) => File::open(dir)?.sync_all(),
+        None => Ok(()),
+    }
+}
+
+/// A single value as a file of `content_type`
+pub fn encode_value<T: Serialize>(params: &PublicParameters, party: usize, content_type: ContentType, value: &T) -> Vec<u8> {
+    Frame::new(Header::new(params, party, content_type))
+        .with_section(Section::Value, serialize(value).unwrap())
+        .encode()
+}
+
+pub fn decode_value<T: DeserializeOwned>(bytes: &[u8], params: &PublicParameters, party: usize, content_type: ContentType) -> Result<T, FormatError> {
+    let frame = Frame::decode_expected(bytes, params, party, content_type)?;
+    let payload = frame.section(Section::Value).ok_or(FormatError::MissingSection(Section::Value))?;
+
+    deserialize(payload).map_err(|_| FormatError::InvalidPayload(Section::Value))
+}
+
+/// The sections of participant data, the round messages of `ProtocolTransferredData` are never stored and the
+/// preprocessing lives in the `PreprocessingStore`
+fn participant_data_sections(data: &mut ProtocolTransferredData) -> [(Section, &mut Option<Vec<u8>>); 7] {
+    [
+        (Section::KeyShare, &mut data.sk),
+        (Section::CiphertextA, &mut data.a),
+        (Section::CiphertextB, &mut data.b),
+        (Section::KeyMac, &mut data.sk_mac),
+        (Section::MacKeyShare, &mut data.mac_alpha),
+        (Section::PrssKeys, &mut data.prss),
+        (Section::KeyInputMask, &mut data.sk_input_mask),
+    ]
+}
+
+pub fn encode_participant_data(params: &PublicParameters, party: usize, data: &ProtocolTransferredData) -> Vec<u8> {
+    let mut data = data.clone();
+
+    participant_data_sections(&mut data).into_iter()
+        .filter_map(|(section, payload)| payload.take().map(|payload| (section, payload)))
+        .fold(Frame::new(Header::new(params, party, ContentType::ParticipantData)),
+              |frame, (section, payload)| frame.with_section(section, payload))
+        .encode()
+}
+
+pub fn decode_participant_data(bytes: &[u8], params: &PublicParameters, party: usize) -> Result<ProtocolTransferredData, FormatError> {
+    let mut frame = Frame::decode_expected(bytes, params, party, ContentType::ParticipantData)?;
+
+    let mut data = ProtocolTransferredData::empty();
+    for (section, field) in participant_data_sections(&mut data) {
+        if let Some(index) = frame.sections.iter().position(|(tag, _)| *tag == section) {
+            *field = Some(frame.sections.swap_remove(index).1);
+        }
+    }
+
+    match frame.sections.first() {
+        Some((section, _)) => Err(FormatError::UnknownSection(*section as u8)),
+        None => Ok(data),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::DVector;
+    use num_bigint::BigInt;
+    use crate::mpc::public_params::PublicParameters;
+    use crate::network::ProtocolTransferredData;
+    use crate::network::storage::{decode_participant_data, decode_value, encode_participant_data, encode_value,
+                                  write_atomically, ContentType, FormatError, Frame, Header, FORMAT_VERSION};
+
+    use bitcode::serialize;
+
+    #[test]
+    fn test_loaders_reject_mismatched_files() {
+        let params = PublicParameters::init(4, 32, 2, 6, 64, 40);
+
+        let data = ProtocolTransferredData {
+            sk: Some(serialize(&DVector::from_element(3, BigInt::from(7))).unwrap()),
+            b: Some(serialize(&BigInt::from(5)).unwrap()),
+            ..ProtocolTransferredData::empty()
+        };
+        let bytes = encode_participant_data(&params, 2, &data);
+
+        let decoded = decode_participant_data(&bytes, &params, 2).unwrap();
+        assert_eq!((decoded.sk, decoded.b, decoded.a), (data.sk.clone(), data.b.clone(), None));
+
+        // A file of an n = 8 run, of another party or of another kind is refused
+        assert!(matches!(decode_participant_data(&bytes, &params.with_committee_size(8), 2),
+                         Err(FormatError::ParameterMismatch { .. })));
+        assert_eq!(decode_participant_data(&bytes, &params, 1).unwrap_err(), FormatError::WrongParty { expected: 1, found: 2 });
+        assert_eq!(decode_value::<BigInt>(&bytes, &params, 2, ContentType::Ciphertext).unwrap_err(),
+                   FormatError::UnexpectedContent { expected: ContentType::Ciphertext, found: ContentType::ParticipantData });
+
+        // Damaged files
+        let mut corrupted = bytes.clone();
+        corrupted[30] ^= 1;
+        assert_eq!(Frame::decode(&corrupted).unwrap_err(), FormatError::ChecksumMismatch);
+        assert_eq!(Frame::decode(&bytes[..bytes.len() - 1]).unwrap_err(), FormatError::ChecksumMismatch);
+        assert_eq!(Frame::decode(&bytes[..10]).unwrap_err(), FormatError::Truncated);
+        assert_eq!(Frame::decode(&serialize(&data).unwrap()).unwrap_err(), FormatError::BadMagic);
+
+        let mut newer = bytes.clone();
+        newer[4..6].copy_from_slice(&(FORMAT_VERSION + 1).to_le_bytes());
+        assert_eq!(Frame::decode(&newer).unwrap_err(), FormatError::UnsupportedVersion { found: FORMAT_VERSION + 1 });
+
+        // A single value
+        let bytes = encode_value(&params, 0, ContentType::KeyShare, &BigInt::from(11));
+        assert_eq!(decode_value::<BigInt>(&bytes, &params, 0, ContentType::KeyShare), Ok(BigInt::from(11)));
+        let empty = Frame::new(Header::new(&params, 0, ContentType::KeyShare)).encode();
+        assert_eq!(decode_value::<BigInt>(&empty, &params, 0, ContentType::KeyShare).unwrap_err().to_string(),
+                   "Section Value is missing");
+    }
+
+    #[test]
+    fn test_atomic_write_replaces_the_file() {
+        let dir = std::env::temp_dir().join(format!("atomic_write_test_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("0.bin");
+
+        write_atomically(&path, b"old").unwrap();
+        write_atomically(&path, b"new").unwrap();
+
+        assert_eq!(std::fs::read(&path).unwrap(), b"new");
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
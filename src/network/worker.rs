@@ -1,28 +1,51 @@
 use std::collections::HashMap;
-use std::fs::File;
+use std::fs;
 use std::io;
-use std::io::Read;
-use std::time::Instant;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 use log::debug;
 use nalgebra::DVector;
 use num_bigint::BigInt;
-use crate::mpc::party::Party;
+use num_integer::Integer;
+use serde::{Deserialize, Serialize};
+use crate::mpc::distributed_preprocessing::PreprocessingParty;
+use crate::mpc::key_refresh::KeyRefresh;
+use crate::mpc::noise_flooding::NoiseFloodingParty;
+use crate::mpc::party::{DecryptionParty, Party, ProtocolAbort};
 use crate::mpc::preprocessing::PreprocessedShare;
+use crate::mpc::prss::PrssKeys;
 use crate::mpc::public_params::PublicParameters;
+use crate::mpc::ring::{RingElement, Z128};
+use crate::mpc::secret_sharing::SecretSharing;
+use crate::mpc::shamir_sharing::ShamirSecretSharing;
 use crate::network::{ProtocolTransferredData};
-use crate::network::common::STEP_COUNT;
+use crate::network::common::DecryptionProtocol;
+use crate::network::preprocessing_store::PreprocessingStore;
+use crate::network::storage::{decode_participant_data, decode_value, encode_participant_data, write_atomically, ContentType};
 use crate::network::participant::{send_result_to_everyone};
-use crate::network::worker::ExecutionResult::{Finished, NextStep, NoReady};
+use crate::network::worker::ExecutionResult::{Aborted, Finished, NextStep, NoReady};
 
-use bitcode::deserialize;
+use bitcode::{serialize, deserialize};
+use lazy_static::lazy_static;
+
+lazy_static! {
+    // Jobs start concurrently, taking instances out of the participant data file must not interleave
+    static ref PARTICIPANT_DATA_LOCK: Mutex<()> = Mutex::new(());
+}
 
 pub struct Worker {
     steps_bulk_data: HashMap<(usize, usize), Vec<ProtocolTransferredData>>,
     params: PublicParameters,
-    mpc_decryptions: Vec<Party>,
+    mpc_decryptions: Vec<Box<dyn DecryptionParty>>,
     ctxt_per_job: usize,
+    step_count: usize,
     start_time: Option<Instant>,
-    pub id: usize
+    pub id: usize,
+    /// Participants of the committee in party order
+    members: Vec<usize>,
+    next_step: usize,
+    /// Set once a decryption of the job aborted, the job is then failed as a whole
+    pub failure: Option<ProtocolAbort>,
 }
 
 
@@ -30,99 +53,365 @@ pub enum ExecutionResult<T> {
     NoReady,
     NextStep(T),
     Finished,
+    Aborted(ProtocolAbort),
 }
 
 impl Worker {
-    pub fn new(id: usize, params: PublicParameters, ctxt_per_job: usize) -> Self {
-
-        let mpc_decryptions: Vec<Party> = (0..ctxt_per_job)
-            .map(|_| Party::new(id, &params))
-            .collect();
+    pub fn new(id: usize, params: PublicParameters, members: Vec<usize>, mpc_decryptions: Vec<Box<dyn DecryptionParty>>) -> Self {
+        let ctxt_per_job = mpc_decryptions.len();
+        let step_count = mpc_decryptions.first().map_or(0, |mpc_decryption| mpc_decryption.step_count());
 
         Worker {
             steps_bulk_data: HashMap::new(),
             params,
             mpc_decryptions,
             ctxt_per_job,
+            step_count,
             start_time: None,
-            id
+            id,
+            members,
+            next_step: 0,
+            failure: None,
         }
     }
 
+    /// Time since the job started
+    pub fn online_time(&self) -> Duration {
+        self.start_time.map_or(Duration::ZERO, |start| start.elapsed())
+    }
+
+    /// Plaintexts of a finished job, in ciphertext order
+    pub fn plaintexts(&self) -> Vec<BigInt> {
+        self.mpc_decryptions.iter()
+            .map(|mpc_decryption| mpc_decryption.msg().clone())
+            .collect()
+    }
+
 
 
 }
 
+/// t-of-n Shamir shares of the key and of its MACs, dealt once to every participant of a threshold committee
+#[derive(Serialize, Deserialize)]
+pub struct ThresholdKeyShare {
+    pub threshold: usize,
+    pub sk: Vec<DVector<BigInt>>,
+    pub sk_mac: Vec<DVector<BigInt>>,
+}
+
+/// Starts a job of the committee `members`, whose party i is participant `members[i]`
 pub fn handle_protocol_start(
     public_parameters: &PublicParameters,
     my_id: usize,
+    members: &[usize],
+    job_id: u64,
+    first_instance: usize,
     ctxt_per_job: usize,
+    protocol: DecryptionProtocol,
 )
     -> Result<(Worker, Vec<ProtocolTransferredData>), io::Error> {
 
     debug!("Starting handle_protocol_start for participant ID: {}", my_id);
 
-    let file_path = format!("/tmp/participant_data/{}.bin", my_id);
-    debug!("Attempting to open participant data file: {}", file_path);
-    let mut file = File::open(&file_path)?;
+    let party_number = members.iter().position(|member| *member == my_id)
+        .ok_or_else(|| io::Error::other(format!("Participant {} is no member of the committee", my_id)))?;
+    let public_parameters = &public_parameters.with_committee_size(members.len());
 
-    let mut buffer = Vec::new();
-    debug!("Reading file content into buffer...");
-    file.read_to_end(&mut buffer)?;
+    let _guard = PARTICIPANT_DATA_LOCK.lock().unwrap();
 
-    debug!("Deserializing ProtocolTransferredData...");
-    let input_data: ProtocolTransferredData = match deserialize(&buffer) {
-        Ok(data) => data,
-        Err(e) => {
-            eprintln!("Failed to deserialize ProtocolTransferredData: {}", e);
-            return Err(io::Error::new(io::ErrorKind::InvalidData, e));
-        }
-    };
+    let file_path = format!("/tmp/participant_data/{}.bin", my_id);
+    debug!("Attempting to read participant data file: {}", file_path);
+    let buffer = fs::read(&file_path)?;
 
-    debug!("Deserializing PreprocessedShare...");
-    let preprocessed: PreprocessedShare = match deserialize(&input_data.preprocessed.clone().unwrap()) {
+    debug!("Decoding ProtocolTransferredData...");
+    let input_data = match decode_participant_data(&buffer, public_parameters, my_id) {
         Ok(data) => data,
         Err(e) => {
-            eprintln!("Failed to deserialize PreprocessedShare: {}", e);
-            return Err(io::Error::new(io::ErrorKind::InvalidData, e));
+            eprintln!("Failed to load {}: {}", file_path, e);
+            return Err(e.into());
         }
     };
 
-    debug!("Deserializing individual fields (a, b, alpha, mac_alpha, mac_r, mac_chi_values)...");
+    if let DecryptionProtocol::NoiseFlooding(smudging_bits) = protocol {
+        return start_noise_flooding(public_parameters, my_id, party_number, members, ctxt_per_job, smudging_bits, input_data);
+    }
+
+    // Every ciphertext of the job gets its own instance, the same one at every participant, from the range the
+    // discovery server assigned to the job. The store records them as consumed before it hands them out, so before
+    // any value derived from them is sent
+    let mut store = PreprocessingStore::open(&PreprocessingStore::directory(my_id), public_parameters, my_id)?;
+    let instances = store.take(first_instance, ctxt_per_job).map_err(|e| {
+        io::Error::other(format!("Not enough fresh preprocessing instances for job {}: {}", job_id, e))
+    })?;
+    println!("Preprocessing taken. participant: {}, job: {}, instances left: {}", my_id, job_id, store.remaining());
+
+    debug!("Deserializing individual fields (sk, sk_mac, a, b, mac_alpha, prss)...");
+    let (sk, sk_mac) = key_share(&input_data)?;
     let a: DVector<BigInt> = deserialize(&input_data.a.unwrap()).unwrap();
     let b: BigInt = deserialize(&input_data.b.unwrap()).unwrap();
-    // let alpha: BigInt = deserialize(&input_data.alpha.unwrap()).unwrap();
     let mac_alpha: BigInt = deserialize(&input_data.mac_alpha.unwrap()).unwrap();
-    let mac_r: DVector<BigInt> = deserialize(&input_data.mac_r.unwrap()).unwrap();
-    let mac_chi_values: DVector<BigInt> = deserialize(&input_data.mac_chi_vals.unwrap()).unwrap();
-
-    let mut worker = Worker::new(my_id, public_parameters.clone(), ctxt_per_job);
+    let prss: PrssKeys = match input_data.prss.as_ref() {
+        Some(prss) => deserialize(prss).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+        None => return Err(io::Error::new(io::ErrorKind::InvalidData, "Participant data holds no PRSS keys")),
+    };
 
     debug!("Setting up MPC decryption values...");
-    let message_input_data: Vec<ProtocolTransferredData> = worker.mpc_decryptions.iter_mut()
-        .map(|mpc_party| {
-            mpc_party.set_r(preprocessed.r.clone());
-            mpc_party.set_s(preprocessed.s.clone());
-            mpc_party.set_sk(preprocessed.sk.clone());
-            mpc_party.set_ltz(preprocessed.ltz.clone());
-            mpc_party.set_signs(preprocessed.signs.clone());
+    let inputs = PartyInputs { party_number, first_instance, sk, sk_mac, a, b, mac_alpha, prss };
+    // Every party of the committee has the same parameters and so picks the same ring
+    let mpc_decryptions = if Z128::fits(public_parameters.mac_ks) {
+        decryption_parties::<Z128>(public_parameters, instances, &inputs)
+    } else {
+        decryption_parties::<BigInt>(public_parameters, instances, &inputs)
+    };
+
+    debug!("MPC decryption setup complete. Setting start_time...");
+    let mut worker = Worker::new(my_id, public_parameters.clone(), members.to_vec(), mpc_decryptions);
+    worker.start_time = Some(Instant::now());
+
+    debug!("Returning initial ProtocolTransferredBulkData...");
+    Ok((worker, vec![ProtocolTransferredData::empty(); ctxt_per_job]))
+}
+
+/// What a party of a job holds besides its preprocessing instances
+struct PartyInputs {
+    party_number: usize,
+    first_instance: usize,
+    sk: DVector<BigInt>,
+    sk_mac: DVector<BigInt>,
+    a: DVector<BigInt>,
+    b: BigInt,
+    mac_alpha: BigInt,
+    prss: PrssKeys,
+}
+
+/// One party in the ring R for every instance of a job
+fn decryption_parties<R: RingElement>(
+    public_parameters: &PublicParameters,
+    instances: Vec<PreprocessedShare>,
+    inputs: &PartyInputs,
+)
+    -> Vec<Box<dyn DecryptionParty>> {
+
+    let lift = |x: &BigInt| R::from_bigint(x);
+    let (sk, sk_mac, a) = (inputs.sk.map(|x| lift(&x)), inputs.sk_mac.map(|x| lift(&x)), inputs.a.map(|x| lift(&x)));
+
+    instances.into_iter()
+        .enumerate()
+        .map(|(c, preprocessed)| {
+            let mut mpc_party = Party::<R>::new(inputs.party_number, public_parameters);
+
+            // The masks and pads of an instance are derived with its index as nonce, unless the preprocessing brought them
+            let nonce = (inputs.first_instance + c) as u64;
+            mpc_party.set_preprocessing(preprocessed, &inputs.prss, nonce);
+            mpc_party.set_sk(sk.clone());
+            mpc_party.set_sk_mac(sk_mac.clone());
 
+            mpc_party.set_a(a.clone());
+            mpc_party.set_b(lift(&inputs.b));
+            mpc_party.set_mac_alpha(lift(&inputs.mac_alpha));
+
+            Box::new(mpc_party) as Box<dyn DecryptionParty>
+        })
+        .collect()
+}
+
+/// Worker of a noise flooding job, it needs the key share and the ciphertext only
+fn start_noise_flooding(
+    public_parameters: &PublicParameters,
+    my_id: usize,
+    party_number: usize,
+    members: &[usize],
+    ctxt_per_job: usize,
+    smudging_bits: usize,
+    input_data: ProtocolTransferredData,
+)
+    -> Result<(Worker, Vec<ProtocolTransferredData>), io::Error> {
+
+    let sk: DVector<BigInt> = match input_data.sk.as_ref() {
+        Some(sk) => deserialize(sk).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+        None => return Err(io::Error::new(io::ErrorKind::InvalidData, "Participant data holds no secret key share")),
+    };
+    let a: DVector<BigInt> = deserialize(&input_data.a.unwrap()).unwrap();
+    let b: BigInt = deserialize(&input_data.b.unwrap()).unwrap();
+
+    let mpc_decryptions: Vec<Box<dyn DecryptionParty>> = (0..ctxt_per_job)
+        .map(|_| {
+            let mut mpc_party = NoiseFloodingParty::new(party_number, public_parameters, smudging_bits);
+            mpc_party.set_sk(sk.clone());
             mpc_party.set_a(a.clone());
             mpc_party.set_b(b.clone());
-            // mpc_party.set_alpha(alpha.clone());
-            mpc_party.set_mac_alpha(mac_alpha.clone());
-            mpc_party.set_mac_r(mac_r.clone());
-            mpc_party.set_mac_chi_values(mac_chi_values.clone());
 
-            ProtocolTransferredData::empty()
+            Box::new(mpc_party) as Box<dyn DecryptionParty>
         })
         .collect();
 
-    debug!("MPC decryption setup complete. Setting start_time...");
+    let mut worker = Worker::new(my_id, public_parameters.clone(), members.to_vec(), mpc_decryptions);
     worker.start_time = Some(Instant::now());
 
-    debug!("Returning initial ProtocolTransferredBulkData...");
-    Ok((worker, message_input_data))
+    Ok((worker, vec![ProtocolTransferredData::empty(); ctxt_per_job]))
+}
+
+/// Appends the instances produced by the distributed preprocessing to the preprocessing store
+pub fn store_preprocessed_share(params: &PublicParameters, my_id: usize, party: &PreprocessingParty) -> Result<(), io::Error> {
+    let _guard = PARTICIPANT_DATA_LOCK.lock().unwrap();
+
+    let mut store = PreprocessingStore::open(&PreprocessingStore::directory(my_id), params, my_id)?;
+
+    let first = store.end();
+    store.append(first, &party.finish())?;
+    Ok(())
+}
+
+/// Replaces the stored key share and its MACs with their refreshed versions, the old ones are overwritten
+pub fn store_refreshed_sk(
+    params: &PublicParameters,
+    my_id: usize,
+    key_refresh: &KeyRefresh,
+    sk_pieces: &[DVector<BigInt>],
+    mac_pieces: &[DVector<BigInt>],
+) -> Result<(), io::Error> {
+    let _guard = PARTICIPANT_DATA_LOCK.lock().unwrap();
+
+    let file_path = format!("/tmp/participant_data/{}.bin", my_id);
+    let buffer = fs::read(&file_path)?;
+
+    let mut input_data = decode_participant_data(&buffer, params, my_id)?;
+    let (sk, sk_mac) = key_share(&input_data)?;
+
+    input_data.sk = Some(serialize(&key_refresh.refresh(&sk, sk_pieces)).unwrap());
+    input_data.sk_mac = Some(serialize(&key_refresh.refresh(&sk_mac, mac_pieces)).unwrap());
+
+    write_atomically(&file_path, &encode_participant_data(params, my_id, &input_data))
+}
+
+/// Puts the key share generated by the participant itself into its data file. `delta` is the sum over all
+/// participants of their key share minus their input mask, the MAC of the key share follows from the MAC of the
+/// mask. The mask hides the key share from the other participants only: the discovery server dealt it, so the
+/// server, or anyone it shares the masks with, learns the key share from the participant's input and the key from
+/// `delta`. The mask is deleted, it would reveal the key share to anyone who learns the others' inputs
+pub fn store_sk(params: &PublicParameters, my_id: usize, sk: &DVector<BigInt>, delta: &DVector<BigInt>) -> Result<(), io::Error> {
+    let _guard = PARTICIPANT_DATA_LOCK.lock().unwrap();
+
+    let file_path = format!("/tmp/participant_data/{}.bin", my_id);
+    let buffer = fs::read(&file_path)?;
+
+    let mut input_data = decode_participant_data(&buffer, params, my_id)?;
+    let (_, mask_mac): (DVector<BigInt>, DVector<BigInt>) = match input_data.sk_input_mask.as_ref() {
+        Some(mask) => deserialize(mask).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+        None => return Err(io::Error::new(io::ErrorKind::InvalidData, "Participant data holds no key input mask")),
+    };
+    let mac_alpha: BigInt = match input_data.mac_alpha.as_ref() {
+        Some(mac_alpha) => deserialize(mac_alpha).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+        None => return Err(io::Error::new(io::ErrorKind::InvalidData, "Participant data holds no MAC key share")),
+    };
+
+    let sk_mac = (mask_mac + delta.map(|x| x * &mac_alpha)).map(|x| x.mod_floor(&params.mac_big_ks));
+
+    input_data.sk = Some(serialize(sk).unwrap());
+    input_data.sk_mac = Some(serialize(&sk_mac).unwrap());
+    input_data.sk_input_mask = None;
+
+    write_atomically(&file_path, &encode_participant_data(params, my_id, &input_data))
+}
+
+/// Share of the mask the participant inputs its generated key share with
+pub fn load_key_input_mask(params: &PublicParameters, my_id: usize) -> Result<DVector<BigInt>, io::Error> {
+    let _guard = PARTICIPANT_DATA_LOCK.lock().unwrap();
+
+    let buffer = fs::read(format!("/tmp/participant_data/{}.bin", my_id))?;
+    let input_data = decode_participant_data(&buffer, params, my_id)?;
+
+    match input_data.sk_input_mask.as_ref() {
+        Some(mask) => deserialize::<(DVector<BigInt>, DVector<BigInt>)>(mask)
+            .map(|(mask, _)| mask)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+        None => Err(io::Error::new(io::ErrorKind::InvalidData, "Participant data holds no key input mask")),
+    }
+}
+
+/// Key share held by the participant, with its MACs
+pub fn load_sk(params: &PublicParameters, my_id: usize) -> Result<(DVector<BigInt>, DVector<BigInt>), io::Error> {
+    let _guard = PARTICIPANT_DATA_LOCK.lock().unwrap();
+
+    let buffer = fs::read(format!("/tmp/participant_data/{}.bin", my_id))?;
+    key_share(&decode_participant_data(&buffer, params, my_id)?)
+}
+
+fn key_share(input_data: &ProtocolTransferredData) -> Result<(DVector<BigInt>, DVector<BigInt>), io::Error> {
+    let sk = match input_data.sk.as_ref() {
+        Some(sk) => deserialize(sk).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+        None => return Err(io::Error::new(io::ErrorKind::InvalidData, "Participant data holds no secret key share")),
+    };
+    let sk_mac = match input_data.sk_mac.as_ref() {
+        Some(sk_mac) => deserialize(sk_mac).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+        None => return Err(io::Error::new(io::ErrorKind::InvalidData, "Participant data holds no MAC of the key share")),
+    };
+    Ok((sk, sk_mac))
+}
+
+/// Completes the data staged for the new committee with the key share and its MACs it holds there and puts it in
+/// place of the current data, the old key share is overwritten
+pub fn store_reshared_sk(params: &PublicParameters, my_id: usize, sk: &DVector<BigInt>, sk_mac: &DVector<BigInt>) -> Result<(), io::Error> {
+    let _guard = PARTICIPANT_DATA_LOCK.lock().unwrap();
+
+    let staged_path = format!("/tmp/participant_data/{}.next.bin", my_id);
+    let buffer = fs::read(&staged_path)?;
+
+    let mut input_data = decode_participant_data(&buffer, params, my_id)?;
+    input_data.sk = Some(serialize(sk).unwrap());
+    input_data.sk_mac = Some(serialize(sk_mac).unwrap());
+
+    write_atomically(&staged_path, &encode_participant_data(params, my_id, &input_data))?;
+
+    // The preprocessing of the old committee goes with it
+    let store_dir = PreprocessingStore::directory(my_id);
+    if store_dir.exists() {
+        fs::remove_dir_all(&store_dir)?;
+    }
+    fs::rename(PreprocessingStore::staged_directory(my_id), store_dir)?;
+
+    fs::rename(&staged_path, format!("/tmp/participant_data/{}.bin", my_id))
+}
+
+/// Additive shares among `members` of the key and of its MACs, from the Shamir shares dealt to the participant.
+/// `params` are those of the committee of every participant holding a Shamir share
+pub fn load_threshold_sk(params: &PublicParameters, my_id: usize, members: &[usize]) -> Result<(DVector<BigInt>, DVector<BigInt>), io::Error> {
+    let _guard = PARTICIPANT_DATA_LOCK.lock().unwrap();
+
+    let buffer = fs::read(format!("/tmp/participant_data/{}.key.bin", my_id))?;
+    let key_share: ThresholdKeyShare = decode_value(&buffer, params, my_id, ContentType::KeyShare)?;
+
+    let scheme = ShamirSecretSharing::new(params.n, key_share.threshold, params.mac_ks);
+    if members.len() < scheme.reconstruction_threshold() {
+        return Err(io::Error::other(format!("{} live parties cannot decrypt, {} are needed", members.len(), scheme.reconstruction_threshold())));
+    }
+
+    Ok((scheme.to_additive_all(my_id, &key_share.sk, members), scheme.to_additive_all(my_id, &key_share.sk_mac, members)))
+}
+
+/// Share of the global MAC key held by the participant
+pub fn load_mac_key_share(params: &PublicParameters, my_id: usize) -> Result<BigInt, io::Error> {
+    let _guard = PARTICIPANT_DATA_LOCK.lock().unwrap();
+
+    let buffer = fs::read(format!("/tmp/participant_data/{}.bin", my_id))?;
+    let input_data = decode_participant_data(&buffer, params, my_id)?;
+
+    match input_data.mac_alpha.as_ref() {
+        Some(mac_alpha) => deserialize(mac_alpha).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+        None => Err(io::Error::new(io::ErrorKind::InvalidData, "Participant data holds no MAC key share")),
+    }
+}
+
+/// Deletes the data of a participant leaving the committee, its key share included
+pub fn remove_participant_data(my_id: usize) -> Result<(), io::Error> {
+    let _guard = PARTICIPANT_DATA_LOCK.lock().unwrap();
+
+    let store_dir = PreprocessingStore::directory(my_id);
+    if store_dir.exists() {
+        fs::remove_dir_all(store_dir)?;
+    }
+
+    fs::remove_file(format!("/tmp/participant_data/{}.bin", my_id))
 }
 
 pub fn handle_protocol_execute_step(
@@ -139,6 +428,10 @@ pub fn handle_protocol_execute_step(
             received_from_participant,  step_num, job_id
         );
 
+    if let Some(failure) = &worker_data.failure {
+        return Aborted(failure.clone());
+    }
+
     // Insert new data
     worker_data.steps_bulk_data.insert((step_num, received_from_participant), input_data);
     debug!(
@@ -149,6 +442,24 @@ pub fn handle_protocol_execute_step(
             worker_data.steps_bulk_data.len()
         );
 
+    // Steps run in order, the inputs of a later step wait until this participant caught up
+    let mut result = NoReady;
+    loop {
+        match execute_next_step(worker_data, job_id, my_participant_id) {
+            NextStep(output) => result = NextStep(output),
+            NoReady => return result,
+            other => return other,
+        }
+    }
+}
+
+fn execute_next_step(
+    worker_data: &mut Worker,
+    job_id: u64,
+    my_participant_id: usize,
+) -> ExecutionResult<(usize, Vec<ProtocolTransferredData>)> {
+    let step_num = worker_data.next_step;
+
     // Collect current step bulk data
     let step_bulk_data: Vec<_> = worker_data.steps_bulk_data
         .iter()
@@ -166,6 +477,7 @@ pub fn handle_protocol_execute_step(
             );
         return NoReady;
     }
+    worker_data.steps_bulk_data.retain(|(step, _), _| step != &step_num);
 
     // Check if `self.ctxt_per_job` and `step_bulk_data` contain data to prevent out-of-bounds
     let mut output_data = Vec::new();
@@ -184,15 +496,23 @@ pub fn handle_protocol_execute_step(
 
         // Execute step if `worker_data.mpc_decryptions` has enough data and can be accessed mutably
         if let Some(mpc_decryption) = worker_data.mpc_decryptions.get_mut(ctxt_index) {
-            output_data.push(mpc_decryption.execute_step(step_num, step_input));
+            match mpc_decryption.execute_step(step_num, step_input) {
+                Ok(output) => output_data.push(output),
+                Err(abort) => {
+                    eprintln!("JOB {}: aborted at ctxt_index {}: {}", job_id, ctxt_index, abort);
+                    worker_data.failure = Some(abort.clone());
+                    return Aborted(abort);
+                }
+            }
         } else {
             debug!("Warning: No MPC decryption data available for ctxt_index {}", ctxt_index);
         }
     }
 
-    let next_step_num = if step_num == STEP_COUNT { 0 } else { step_num + 1 };
+    let next_step_num = if step_num == worker_data.step_count { 0 } else { step_num + 1 };
+    worker_data.next_step = next_step_num;
 
-    if next_step_num == STEP_COUNT {
+    if next_step_num == worker_data.step_count {
         if let Some(start) = worker_data.start_time {
             let elapsed = start.elapsed();
             println!(
@@ -216,7 +536,7 @@ pub fn handle_protocol_execute_step(
             next_step_num
             );
         // NextStep((next_step_num, output_data))
-        send_result_to_everyone(&output_data, next_step_num, job_id as usize, my_participant_id);
+        send_result_to_everyone(&output_data, next_step_num, job_id as usize, my_participant_id, &worker_data.members);
         NextStep((next_step_num, output_data))
     }
 }
\ No newline at end of file